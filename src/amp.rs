@@ -0,0 +1,191 @@
+use once_cell::sync::Lazy;
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Returns the URL of the original page of the given AMP cache URL, such as
+/// `https://www.google.com/amp/s/example.com/a` or
+/// `https://example-com.cdn.ampproject.org/c/s/example.com/a`, which both
+/// serve `https://example.com/a`.
+pub fn unwrap_cache_url(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let path = parsed.path();
+    let rest = if host.ends_with(".cdn.ampproject.org") {
+        // The first segment is the kind of the cached content: `c` for pages,
+        // `v` for viewer pages, `i` for images...
+        let (_, rest) = path.strip_prefix('/')?.split_once('/')?;
+        rest
+    } else if host == "google.com" || host.starts_with("www.google.") {
+        path.strip_prefix("/amp/")?
+    } else {
+        return None;
+    };
+    let (scheme, rest) = match rest.strip_prefix("s/") {
+        Some(rest) => ("https", rest),
+        None => ("http", rest),
+    };
+    let mut unwrapped = Url::parse(&format!("{scheme}://{rest}")).ok()?;
+    unwrapped.set_query(parsed.query());
+    unwrapped.host_str()?;
+    Some(unwrapped.into())
+}
+
+/// Returns the canonical URL of the given page if it is an AMP page (one
+/// whose `<html>` tag has the `amp` or `⚡` attribute), along with whether it
+/// was only guessed from its URL. Its `<link rel="canonical">` is used when it
+/// has one and, otherwise, the URL without its AMP marker, such as a trailing
+/// `/amp` segment or an `amp=1` query parameter.
+pub fn canonical_url(url: &str, html: &str) -> Option<(Url, bool)> {
+    static HTML: Lazy<Selector> = Lazy::new(|| Selector::parse("html").unwrap());
+    static CANONICAL: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(r#"link[rel~="canonical"][href]"#).unwrap());
+
+    let base = Url::parse(url).ok()?;
+    let document = Html::parse_document(html);
+    let root = document.select(&HTML).next()?;
+    if !root
+        .value()
+        .attrs()
+        .any(|(name, _)| name == "amp" || name == "⚡")
+    {
+        return None;
+    }
+    let linked = document
+        .select(&CANONICAL)
+        .filter_map(|el| base.join(el.value().attr("href")?.trim()).ok())
+        .find(|canonical| *canonical != base);
+    match linked {
+        Some(canonical) => Some((canonical, false)),
+        None => Some((strip_amp_marker(&base)?, true)),
+    }
+}
+
+/// Whether the given (non-AMP) page links to the given URL as its AMP
+/// variant, with a `<link rel="amphtml">`.
+pub fn links_to_amp(url: &str, html: &str, amp_url: &str) -> bool {
+    static AMPHTML: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(r#"link[rel~="amphtml"][href]"#).unwrap());
+
+    let (Ok(base), Ok(amp_url)) = (Url::parse(url), Url::parse(amp_url)) else {
+        return false;
+    };
+    let document = Html::parse_document(html);
+    let linked = document
+        .select(&AMPHTML)
+        .filter_map(|el| base.join(el.value().attr("href")?.trim()).ok())
+        .any(|linked| linked == amp_url);
+    linked
+}
+
+/// Removes the AMP marker from the given URL, returning `None` if it has none.
+fn strip_amp_marker(url: &Url) -> Option<Url> {
+    let mut stripped = url.clone();
+    let segments: Vec<_> = url.path_segments()?.collect();
+    let trailing_slash = segments.last() == Some(&"");
+    let segments = if trailing_slash {
+        &segments[..segments.len() - 1]
+    } else {
+        &segments[..]
+    };
+    if segments.last() == Some(&"amp") {
+        let path = segments[..segments.len() - 1].join("/");
+        stripped.set_path(&format!("{path}/"));
+    } else if let Some(page) = segments
+        .last()
+        .and_then(|last| last.strip_suffix(".amp.html"))
+    {
+        let page = format!("{page}.html");
+        let mut segments = segments.to_vec();
+        *segments.last_mut().unwrap() = &page;
+        stripped.set_path(&segments.join("/"));
+    }
+
+    const PARAMS: &[&str] = &["amp", "outputType", "output"];
+    let is_marker = |(name, value): &(String, String)| {
+        PARAMS.contains(&name.as_str()) && (name == "amp" || value.eq_ignore_ascii_case("amp"))
+    };
+    let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    if pairs.iter().any(is_marker) {
+        let kept: Vec<_> = pairs.iter().filter(|pair| !is_marker(pair)).collect();
+        if kept.is_empty() {
+            stripped.set_query(None);
+        } else {
+            stripped.query_pairs_mut().clear().extend_pairs(kept);
+        }
+    }
+    (stripped != *url).then_some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the canonical URL of the given AMP page, as a string, and
+    /// whether it was guessed.
+    fn canonical(url: &str, html: &str) -> Option<(String, bool)> {
+        canonical_url(url, html).map(|(url, guessed)| (url.into(), guessed))
+    }
+
+    #[test]
+    fn unwraps_cache_urls() {
+        assert_eq!(
+            unwrap_cache_url("https://www.google.com/amp/s/example.com/a?b=1").as_deref(),
+            Some("https://example.com/a?b=1")
+        );
+        assert_eq!(
+            unwrap_cache_url("https://example-com.cdn.ampproject.org/c/s/example.com/a").as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(
+            unwrap_cache_url("https://example-com.cdn.ampproject.org/v/example.com/a").as_deref(),
+            Some("http://example.com/a")
+        );
+        assert_eq!(
+            unwrap_cache_url("https://www.google.com/search?q=amp"),
+            None
+        );
+        assert_eq!(unwrap_cache_url("https://example.com/amp/s/a"), None);
+    }
+
+    #[test]
+    fn finds_the_canonical_urls_of_amp_pages() {
+        let linked = r#"<html amp><head><link rel="canonical" href="/article"></head></html>"#;
+        assert_eq!(
+            canonical("https://example.com/article/amp", linked),
+            Some(("https://example.com/article".to_owned(), false))
+        );
+        let unlinked = "<html ⚡><head></head></html>";
+        assert_eq!(
+            canonical("https://example.com/article/amp/", unlinked),
+            Some(("https://example.com/article/".to_owned(), true))
+        );
+        assert_eq!(
+            canonical("https://example.com/a/page.amp.html", unlinked),
+            Some(("https://example.com/a/page.html".to_owned(), true))
+        );
+        assert_eq!(
+            canonical("https://example.com/a?amp=1&id=2", unlinked),
+            Some(("https://example.com/a?id=2".to_owned(), true))
+        );
+        assert_eq!(canonical("https://example.com/a", unlinked), None);
+        assert_eq!(
+            canonical("https://example.com/a/amp", "<html></html>"),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_links_to_amp_variants() {
+        let html = r#"<link rel="amphtml" href="/a/amp">"#;
+        assert!(links_to_amp(
+            "https://example.com/a",
+            html,
+            "https://example.com/a/amp"
+        ));
+        assert!(!links_to_amp(
+            "https://example.com/a",
+            html,
+            "https://example.com/b/amp"
+        ));
+    }
+}
@@ -0,0 +1,645 @@
+use std::collections::BTreeMap;
+
+use eyre::{eyre, Result, WrapErr};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use tracing::warn;
+use url::Url;
+
+use crate::{
+    amp,
+    config::OneOrMany,
+    fetch::{load_page, load_page_if_modified, Page, Revalidated, Validators},
+    overrides,
+    render::{is_placeholder_title, RenderMode, Renderer},
+    sites, urls,
+};
+
+/// Patterns of the titles and headings of pages that say they don't exist,
+/// matched against their lowercased text. A `404` only counts next to words
+/// of error pages, so that "Flight 404" or "RFC 4041" don't.
+const SOFT_404_PATTERNS: &[&str] = &[
+    r"(?-u:\b)404(?-u:\b).*(not found|error|page)",
+    r"(not found|error|page).*(?-u:\b)404(?-u:\b)",
+    r"page (was )?not found",
+    r"page (doesn't|does not) exist",
+    r"page (is )?no longer available",
+    r"page unavailable",
+    r"(couldn't|could not|can't|cannot) find (the|this|that) page",
+];
+
+/// Phrases of the titles and headings of pages that say they don't exist,
+/// which are common in other titles too ("Where the Treasure Could Not Be
+/// Found"), so that they only count as whole words in short ones such as "Not
+/// Found".
+const SHORT_SOFT_404_PHRASES: &[&str] = &[
+    "404",
+    "not found",
+    "doesn't exist",
+    "does not exist",
+    "no longer exists",
+    "no longer available",
+    "couldn't be found",
+    "could not be found",
+];
+
+/// How many words a title or heading may have, without its site name, for
+/// the [`SHORT_SOFT_404_PHRASES`] to count.
+const SHORT_TITLE_WORDS: usize = 4;
+
+/// The lowercased titles of login walls and paywalls, which are matched
+/// against the whole parts of the titles, as in "Sign in | Example", so that
+/// "How OAuth login works" isn't one.
+const PAYWALL_TITLES: &[&str] = &[
+    "sign in",
+    "log in",
+    "login",
+    "sign up",
+    "subscribe",
+    "subscription required",
+    "subscribers only",
+    "create an account",
+];
+
+/// The lowercased beginnings of the titles of login walls and paywalls, as in
+/// "Sign in to continue".
+const PAYWALL_TITLE_PREFIXES: &[&str] = &[
+    "sign in to ",
+    "log in to ",
+    "login to ",
+    "subscribe to continue",
+    "subscribe to read",
+    "subscribe to unlock",
+    "create an account to ",
+];
+
+/// The separators between the parts of titles, such as the page and the name
+/// of its site.
+const TITLE_SEPARATORS: &[&str] = &[" | ", " - ", " – ", " — ", " · ", ": "];
+
+/// The title of a page, along with the passthrough fields its analysis
+/// produced.
+#[derive(Default)]
+pub struct Resolution {
+    pub title: Option<String>,
+    /// The URL to output instead of the requested one, such as the canonical
+    /// URL of an AMP page.
+    pub url: Option<String>,
+    /// The status code of the page.
+    pub status: Option<u16>,
+    /// The size of the downloaded page, which is zero if it wasn't downloaded.
+    pub bytes: usize,
+    pub fields: Vec<(String, String)>,
+}
+
+/// Runs the enabled heuristics over the fetched pages, annotating the results
+/// with passthrough fields. Each enabled heuristic always adds its field, so
+/// that all the results have the same fields.
+#[derive(Default)]
+pub struct Analyzer {
+    /// Patterns of soft 404 pages, if their detection is enabled.
+    soft_404: Option<Vec<Regex>>,
+    paywalls: bool,
+    amp: bool,
+    expand: bool,
+    /// Whether the URLs of some sites are resolved through their APIs.
+    sites: bool,
+    /// Renders the pages, if enabled.
+    renderer: Option<(Renderer, RenderMode)>,
+    /// The selectors of the titles of the pages of some domains.
+    title_selectors: Vec<(String, Vec<Selector>)>,
+}
+
+impl Analyzer {
+    /// Enables the detection of soft 404 pages, which are "not found" pages
+    /// served with a successful status. The given patterns are used along with
+    /// the default ones.
+    pub fn detect_soft_404(mut self, patterns: &[String]) -> Result<Self> {
+        let patterns = SOFT_404_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(patterns.iter().cloned())
+            .map(|p| Regex::new(&p).wrap_err_with(|| format!("invalid pattern `{p}`")))
+            .collect::<Result<_>>()?;
+        self.soft_404 = Some(patterns);
+        Ok(self)
+    }
+
+    /// Renders the pages with the given renderer, so that their scripts run,
+    /// either instead of just fetching them or only when their static title
+    /// is a placeholder.
+    pub fn render_with(mut self, renderer: Renderer, mode: RenderMode) -> Self {
+        self.renderer = Some((renderer, mode));
+        self
+    }
+
+    /// Enables the detection of paywalls and login walls.
+    pub fn detect_paywalls(mut self) -> Self {
+        self.paywalls = true;
+        self
+    }
+
+    /// Enables the unwrapping of AMP pages, which are replaced by their
+    /// canonical page.
+    pub fn unwrap_amp(mut self) -> Self {
+        self.amp = true;
+        self
+    }
+
+    /// Enables the expansion of the URLs, which are replaced by their final
+    /// URL, after redirects.
+    pub fn expand(mut self) -> Self {
+        self.expand = true;
+        self
+    }
+
+    /// Enables the resolution of the URLs of some sites through their APIs,
+    /// rather than their pages.
+    pub fn use_site_apis(mut self) -> Self {
+        self.sites = true;
+        self
+    }
+
+    /// Reads the titles of the pages of the given domains and their subdomains
+    /// from the first of their selectors that matches an element with some
+    /// text, rather than from their `title` tag.
+    pub fn select_titles(mut self, rules: &BTreeMap<String, OneOrMany>) -> Result<Self> {
+        for (domain, selectors) in rules {
+            let selectors = selectors
+                .values()
+                .iter()
+                .map(|s| {
+                    Selector::parse(s).map_err(|error| eyre!("invalid selector `{s}`: {error}"))
+                })
+                .collect::<Result<_>>()?;
+            self.title_selectors.push((domain.clone(), selectors));
+        }
+        Ok(self)
+    }
+
+    /// Whether the resolutions may replace the requested URLs.
+    pub fn rewrites_urls(&self) -> bool {
+        self.amp || self.expand
+    }
+
+    /// Returns the names of the fields added by the enabled heuristics.
+    pub fn fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.soft_404.is_some() {
+            fields.push("soft_404");
+        }
+        if self.paywalls {
+            fields.push("paywalled");
+        }
+        fields.extend(self.site_fields());
+        fields
+    }
+
+    /// Returns the names of the fields given by the APIs of the sites: those
+    /// of the built-in resolvers, if enabled, and those of the resolvers of
+    /// the configuration file.
+    fn site_fields(&self) -> Vec<&'static str> {
+        let mut fields = match self.sites {
+            true => sites::FIELDS.to_vec(),
+            false => Vec::new(),
+        };
+        for name in sites::custom_fields() {
+            if !fields.contains(&name) {
+                fields.push(name);
+            }
+        }
+        fields
+    }
+
+    /// Fetches the given URL and analyzes the page.
+    pub async fn resolve(&self, url: &str) -> Result<Resolution> {
+        if let Some(page) = self.resolve_site(url).await {
+            return Ok(self.analyze(url, page));
+        }
+        let page = match self.renderer(url) {
+            Some((renderer, RenderMode::Always)) => renderer.render(url).await?,
+            _ => self.render_if_placeholder(url, load_page(url).await?).await,
+        };
+        Ok(self.analyze(url, self.follow_canonical(page).await))
+    }
+
+    /// Fetches the given URL with a conditional request, like
+    /// [`load_page_if_modified`]. Pages that are always rendered are always
+    /// loaded again.
+    pub async fn fetch_if_modified(
+        &self,
+        url: &str,
+        validators: &Validators,
+    ) -> Result<Revalidated> {
+        if let Some(page) = self.resolve_site(url).await {
+            return Ok(Revalidated::Modified(page));
+        }
+        match self.renderer(url) {
+            Some((renderer, RenderMode::Always)) => {
+                let page = renderer.render(url).await?;
+                Ok(Revalidated::Modified(self.follow_canonical(page).await))
+            }
+            _ => match load_page_if_modified(url, validators).await? {
+                Revalidated::Modified(page) => {
+                    let page = self.render_if_placeholder(url, page).await;
+                    Ok(Revalidated::Modified(self.follow_canonical(page).await))
+                }
+                Revalidated::NotModified => Ok(Revalidated::NotModified),
+            },
+        }
+    }
+
+    /// Resolves the given URL through the API of its site, if enabled and
+    /// supported. The page is fetched instead if the API fails.
+    async fn resolve_site(&self, url: &str) -> Option<Page> {
+        if !self.sites && !sites::has_custom_resolvers() {
+            return None;
+        }
+        match sites::resolve(url, self.sites).await? {
+            Ok(page) => Some(page),
+            Err(error) => {
+                warn!("failed to resolve `{url}` through the API of its site: {error:#}");
+                None
+            }
+        }
+    }
+
+    /// Returns the renderer of the given URL, if enabled, and when it renders
+    /// its page, which the rules of the configuration file may override.
+    fn renderer(&self, url: &str) -> Option<(&Renderer, RenderMode)> {
+        let (renderer, mode) = self.renderer.as_ref()?;
+        Some((renderer, overrides::render_mode(url).unwrap_or(*mode)))
+    }
+
+    /// Renders the given URL if automatic rendering is enabled and the title
+    /// of its fetched page is a placeholder. The fetched page is kept if the
+    /// rendering fails or doesn't have a title either.
+    async fn render_if_placeholder(&self, url: &str, page: Page) -> Page {
+        let Some((renderer, RenderMode::Auto)) = self.renderer(url) else {
+            return page;
+        };
+        if !is_placeholder_title(url, page.title.as_deref()) {
+            return page;
+        }
+        match renderer.render(url).await {
+            Ok(rendered) if rendered.title.is_some() => Page {
+                validators: page.validators,
+                ..rendered
+            },
+            Ok(_) => page,
+            Err(error) => {
+                warn!("failed to render `{url}`: {error:#}");
+                page
+            }
+        }
+    }
+
+    /// Replaces the given page by its canonical page if AMP unwrapping is
+    /// enabled and it is an AMP page. Canonical URLs guessed from the URL of
+    /// the page are only followed if their page links back to the AMP page.
+    /// The given page is kept if its canonical page fails to load.
+    async fn follow_canonical(&self, page: Page) -> Page {
+        if !self.amp {
+            return page;
+        }
+        let Some((canonical, guessed)) = amp::canonical_url(&page.url, &page.html) else {
+            return page;
+        };
+        match load_page(canonical.as_str()).await {
+            Ok(target) if !guessed || amp::links_to_amp(&target.url, &target.html, &page.url) => {
+                Page {
+                    unwrapped_url: Some(canonical.into()),
+                    validators: page.validators,
+                    ..target
+                }
+            }
+            Ok(_) => page,
+            Err(error) => {
+                warn!(
+                    "failed to load the canonical page of `{}`: {error:#}",
+                    page.url
+                );
+                page
+            }
+        }
+    }
+
+    /// Analyzes the given page.
+    pub fn analyze(&self, url: &str, mut page: Page) -> Resolution {
+        if page.unwrapped_url.is_none() && self.expand && page.url != url {
+            page.unwrapped_url = Some(page.url.clone());
+        }
+        let mut fields = Vec::new();
+        let bytes = page.html.len();
+        let consent_wall = page.title.as_deref().is_some_and(is_consent_title);
+        let host = Url::parse(&page.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        // The rules of the most specific domain apply.
+        let selectors = self
+            .title_selectors
+            .iter()
+            .filter(|(domain, _)| urls::is_within(&host, domain))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, selectors)| selectors);
+        if self.fields().is_empty() && !consent_wall && selectors.is_none() {
+            return Resolution {
+                title: page.title,
+                url: page.unwrapped_url,
+                status: Some(page.status.as_u16()),
+                bytes,
+                fields,
+            };
+        }
+
+        let document = Html::parse_document(&page.html);
+        if let Some(title) = selectors.and_then(|selectors| select_title(&document, selectors)) {
+            page.title = Some(title);
+        }
+        if consent_wall {
+            page.title = meta_title(&document).filter(|title| !is_consent_title(title));
+            if page.title.is_none() {
+                warn!(
+                    "`{url}` served a cookie consent page; \
+                     its consent cookie may be given with `--consent-cookie`"
+                );
+            }
+        }
+        if let Some(patterns) = &self.soft_404 {
+            let soft_404 = page.status.is_success() && is_soft_404(&page, &document, patterns);
+            fields.push(("soft_404".into(), soft_404.to_string()));
+        }
+        if self.paywalls {
+            fields.push((
+                "paywalled".into(),
+                is_paywalled(&page, &document).to_string(),
+            ));
+        }
+        for name in self.site_fields() {
+            let value = page.fields.iter().find(|(field, _)| field == name);
+            let value = value.map(|(_, value)| value.clone()).unwrap_or_default();
+            fields.push((name.to_string(), value));
+        }
+        Resolution {
+            title: page.title,
+            url: page.unwrapped_url,
+            status: Some(page.status.as_u16()),
+            bytes,
+            fields,
+        }
+    }
+}
+
+/// Returns the text of the first element matched by the given selectors, in
+/// order, that has some text.
+fn select_title(document: &Html, selectors: &[Selector]) -> Option<String> {
+    selectors.iter().find_map(|selector| {
+        document.select(selector).find_map(|el| {
+            let text = el.text().collect::<Vec<_>>().join(" ");
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!text.is_empty()).then_some(text)
+        })
+    })
+}
+
+/// Whether the given title is that of a cookie consent page, which some sites
+/// serve instead of the requested page until their cookies are accepted.
+fn is_consent_title(title: &str) -> bool {
+    const PATTERNS: &[&str] = &[
+        "before you continue",
+        "bevor sie fortfahren",
+        "avant d'accéder",
+        "cookie consent",
+        "cookie settings",
+        "privacy settings",
+        "we value your privacy",
+    ];
+    let title = title.to_lowercase();
+    PATTERNS.iter().any(|p| title.contains(p))
+}
+
+/// Returns the title of the page given by its Open Graph or Twitter meta tags,
+/// which consent pages sometimes keep from the requested page.
+fn meta_title(document: &Html) -> Option<String> {
+    static META: Lazy<Selector> = Lazy::new(|| {
+        Selector::parse(r#"meta[property="og:title"], meta[name="twitter:title"]"#).unwrap()
+    });
+    document
+        .select(&META)
+        .filter_map(|el| el.value().attr("content"))
+        .map(str::trim)
+        .find(|title| !title.is_empty())
+        .map(str::to_owned)
+}
+
+/// Whether the title or the main heading of the page matches any of the given
+/// patterns of "not found" pages or, if short, has a phrase of them.
+fn is_soft_404(page: &Page, document: &Html, patterns: &[Regex]) -> bool {
+    static HEADING: Lazy<Selector> = Lazy::new(|| Selector::parse("h1").unwrap());
+    static SHORT: Lazy<Regex> = Lazy::new(|| {
+        let phrases: Vec<_> = SHORT_SOFT_404_PHRASES
+            .iter()
+            .map(|p| regex::escape(p))
+            .collect();
+        Regex::new(&format!(r"(?-u:\b)({})(?-u:\b)", phrases.join("|"))).unwrap()
+    });
+
+    let heading = document
+        .select(&HEADING)
+        .next()
+        .map(|el| el.text().collect::<String>());
+    let matches = [page.title.as_deref(), heading.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::to_lowercase)
+        .any(|text| {
+            let short = title_parts(&text)[0].split_whitespace().count() <= SHORT_TITLE_WORDS;
+            patterns.iter().any(|p| p.is_match(&text)) || (short && SHORT.is_match(&text))
+        });
+    matches
+}
+
+/// Returns the parts of the given title split at its separators, such as the
+/// page and the name of its site, trimmed.
+fn title_parts(title: &str) -> Vec<&str> {
+    let mut parts = vec![title.trim()];
+    for separator in TITLE_SEPARATORS {
+        parts = parts
+            .into_iter()
+            .flat_map(|part| part.split(separator))
+            .map(str::trim)
+            .collect();
+    }
+    parts
+}
+
+/// Whether the page is behind a paywall or a login wall: either its structured
+/// data says that it isn't free, its `article:content_tier` meta tag says that
+/// it is locked or metered, or its title is that of a login or subscription
+/// page, unless they say that it is free.
+fn is_paywalled(page: &Page, document: &Html) -> bool {
+    static JSON_LD: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(r#"script[type="application/ld+json"]"#).unwrap());
+    static CONTENT_TIER: Lazy<Selector> = Lazy::new(|| {
+        Selector::parse(
+            r#"meta[property="article:content_tier"], meta[name="article:content_tier"]"#,
+        )
+        .unwrap()
+    });
+
+    /// Whether the JSON-LD value has `isAccessibleForFree` set to the given
+    /// value.
+    fn is_accessible_for_free(value: &Value, free: bool) -> bool {
+        match value {
+            Value::Object(map) => map.iter().any(|(k, v)| {
+                let matches = v.as_bool() == Some(free)
+                    || v.as_str()
+                        .is_some_and(|s| s.eq_ignore_ascii_case(&free.to_string()));
+                (k == "isAccessibleForFree" && matches) || is_accessible_for_free(v, free)
+            }),
+            Value::Array(values) => values.iter().any(|v| is_accessible_for_free(v, free)),
+            _ => false,
+        }
+    }
+
+    let structured: Vec<Value> = document
+        .select(&JSON_LD)
+        .filter_map(|el| serde_json::from_str(&el.text().collect::<String>()).ok())
+        .collect();
+    let tiers: Vec<_> = document
+        .select(&CONTENT_TIER)
+        .map(|el| {
+            el.value()
+                .attr("content")
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+        .collect();
+    let locked = structured
+        .iter()
+        .any(|value| is_accessible_for_free(value, false))
+        || tiers
+            .iter()
+            .any(|tier| tier == "locked" || tier == "metered");
+    let free = structured
+        .iter()
+        .any(|value| is_accessible_for_free(value, true))
+        || tiers.iter().any(|tier| tier == "free");
+    locked || (!free && is_paywall_title(page.title.as_deref().unwrap_or_default()))
+}
+
+/// Whether the given title is that of a login or subscription page: one of
+/// its parts is such a title, or it begins like one.
+fn is_paywall_title(title: &str) -> bool {
+    let title = title.trim().to_lowercase();
+    title_parts(&title)
+        .into_iter()
+        .any(|part| PAYWALL_TITLES.contains(&part))
+        || PAYWALL_TITLE_PREFIXES
+            .iter()
+            .any(|prefix| title.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a page with the given title and HTML.
+    fn page(title: &str, html: &str) -> Page {
+        Page {
+            url: "https://example.com/".to_string(),
+            unwrapped_url: None,
+            status: reqwest::StatusCode::OK,
+            title: Some(title.to_string()),
+            html: html.to_string(),
+            validators: Validators::default(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Whether the page with the given title and HTML looks like a "not found"
+    /// page to the default patterns.
+    fn soft_404(title: &str, html: &str) -> bool {
+        let analyzer = Analyzer::default().detect_soft_404(&[]).unwrap();
+        let patterns = analyzer.soft_404.unwrap();
+        is_soft_404(&page(title, html), &Html::parse_document(html), &patterns)
+    }
+
+    /// Whether the page with the given title and HTML looks paywalled.
+    fn paywalled(title: &str, html: &str) -> bool {
+        is_paywalled(&page(title, html), &Html::parse_document(html))
+    }
+
+    #[test]
+    fn detects_soft_404s() {
+        assert!(soft_404("404 - Not Found", ""));
+        assert!(soft_404("Page not found | Example", ""));
+        assert!(soft_404("Error 404", ""));
+        assert!(soft_404("404", ""));
+        assert!(soft_404("Not Found", ""));
+        assert!(soft_404("This page doesn't exist – Example", ""));
+        assert!(soft_404(
+            "Example",
+            "<h1>Sorry, we couldn't find that page</h1>"
+        ));
+    }
+
+    #[test]
+    fn leaves_titles_that_only_mention_404s() {
+        assert!(!soft_404(
+            "Flight 404 to Lisbon was delayed by three hours",
+            ""
+        ));
+        assert!(!soft_404(
+            "RFC 4041: Requirements for Morality Sections",
+            ""
+        ));
+        assert!(!soft_404(
+            "Where the treasure could not be found, and why",
+            ""
+        ));
+        assert!(!soft_404("The book that was not found in any library", ""));
+    }
+
+    #[test]
+    fn detects_paywalls_by_title() {
+        assert!(paywalled("Sign in | Example", ""));
+        assert!(paywalled("Login - Medium", ""));
+        assert!(paywalled("Subscribe to read | Financial Times", ""));
+        assert!(paywalled("Sign in to continue", ""));
+    }
+
+    #[test]
+    fn leaves_titles_that_only_mention_logins() {
+        assert!(!paywalled("How OAuth login works", ""));
+        assert!(!paywalled("Design sign in flows", ""));
+        assert!(!paywalled("Why you should subscribe to newsletters", ""));
+    }
+
+    #[test]
+    fn prefers_structured_data_to_titles() {
+        let free = r#"<script type="application/ld+json">{"isAccessibleForFree": true}</script>"#;
+        let locked =
+            r#"<script type="application/ld+json">{"isAccessibleForFree": "False"}</script>"#;
+        let tier = r#"<meta property="article:content_tier" content="free">"#;
+        assert!(!paywalled("Sign in | Example", free));
+        assert!(!paywalled("Sign in | Example", tier));
+        assert!(paywalled("An article", locked));
+        assert!(paywalled(
+            "An article",
+            r#"<meta property="article:content_tier" content="metered">"#
+        ));
+    }
+
+    #[test]
+    fn splits_titles_into_parts() {
+        assert_eq!(
+            title_parts("Sign in | Example - Blog"),
+            ["Sign in", "Example", "Blog"]
+        );
+        assert_eq!(title_parts(" Title "), ["Title"]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use eyre::{bail, Result, WrapErr};
+use once_cell::sync::Lazy;
+use reqwest::{
+    header::{CONTENT_LOCATION, REFRESH},
+    StatusCode,
+};
+use serde_json::Value;
+use tokio::{
+    sync::Mutex,
+    time::{self, Instant},
+};
+use url::Url;
+
+use crate::fetch::{get, load_page};
+
+/// The endpoint of the Wayback Machine that returns the closest snapshot of a
+/// URL.
+const WAYBACK_AVAILABILITY: &str = "https://archive.org/wayback/available";
+
+/// The endpoint of the Wayback Machine that saves a snapshot of a URL.
+const WAYBACK_SAVE: &str = "https://web.archive.org/save/";
+
+/// The endpoint of archive.today that redirects to the latest snapshot of a
+/// URL.
+const ARCHIVE_TODAY_NEWEST: &str = "https://archive.ph/newest/";
+
+/// The endpoint of archive.today that saves a snapshot of a URL.
+const ARCHIVE_TODAY_SUBMIT: &str = "https://archive.ph/submit/";
+
+/// The minimum gap between the pages saved to an archive, which limits how
+/// often they can be saved.
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When the next page may be saved.
+static NEXT_SAVE: Lazy<Mutex<Option<Instant>>> = Lazy::new(Default::default);
+
+/// The web archives where snapshots are looked up and saved.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveBackend {
+    /// The Wayback Machine of the Internet Archive, at `web.archive.org`.
+    Wayback,
+    /// archive.today, also known as `archive.ph`.
+    ArchiveToday,
+}
+
+/// A snapshot of a page in a web archive.
+pub struct Snapshot {
+    /// The URL of the snapshot in the archive.
+    pub url: String,
+    /// The title of the archived page, if it has one.
+    pub title: Option<String>,
+}
+
+impl ArchiveBackend {
+    /// Finds the latest snapshot of the given URL in the archive, loading it
+    /// to read its title. Returns `None` if the URL was never archived.
+    pub async fn find_snapshot(self, url: &str) -> Result<Option<Snapshot>> {
+        match self {
+            ArchiveBackend::Wayback => find_wayback_snapshot(url).await,
+            ArchiveBackend::ArchiveToday => find_archive_today_snapshot(url).await,
+        }
+    }
+
+    /// Saves a snapshot of the given URL to the archive, once the gap since
+    /// the previous one passed. Returns the URL of the snapshot.
+    pub async fn save(self, url: &str) -> Result<String> {
+        {
+            let mut next = NEXT_SAVE.lock().await;
+            if let Some(next) = *next {
+                time::sleep_until(next).await;
+            }
+            *next = Some(Instant::now() + SAVE_INTERVAL);
+        }
+        match self {
+            ArchiveBackend::Wayback => save_to_wayback(url).await,
+            ArchiveBackend::ArchiveToday => save_to_archive_today(url).await,
+        }
+    }
+}
+
+/// Finds the latest snapshot of the given URL in the Wayback Machine.
+async fn find_wayback_snapshot(url: &str) -> Result<Option<Snapshot>> {
+    let api = Url::parse_with_params(WAYBACK_AVAILABILITY, [("url", url)])?;
+    let response: Value = get(api.as_str())
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("invalid response from the Wayback Machine")?;
+    let closest = &response["archived_snapshots"]["closest"];
+    let (Some(snapshot), Some(timestamp)) =
+        (closest["url"].as_str(), closest["timestamp"].as_str())
+    else {
+        return Ok(None);
+    };
+    if closest["available"] == false {
+        return Ok(None);
+    }
+    // The `id_` flag serves the page as it was archived, without the toolbar
+    // of the Wayback Machine.
+    let page = load_page(&format!("https://web.archive.org/web/{timestamp}id_/{url}")).await?;
+    Ok(Some(Snapshot {
+        url: snapshot.replacen("http://", "https://", 1),
+        title: page.title,
+    }))
+}
+
+/// Finds the latest snapshot of the given URL in archive.today, whose
+/// snapshots keep the title of the archived page.
+async fn find_archive_today_snapshot(url: &str) -> Result<Option<Snapshot>> {
+    let page = load_page(&format!("{ARCHIVE_TODAY_NEWEST}{url}"))
+        .await
+        .wrap_err("failed to look up the snapshot in archive.today")?;
+    // The endpoint redirects to the snapshot, if there is one.
+    if page.status == StatusCode::NOT_FOUND || page.url.starts_with(ARCHIVE_TODAY_NEWEST) {
+        return Ok(None);
+    }
+    Ok(Some(Snapshot {
+        url: page.url,
+        title: page.title,
+    }))
+}
+
+/// Saves a snapshot of the given URL to the Wayback Machine.
+async fn save_to_wayback(url: &str) -> Result<String> {
+    let response = get(&format!("{WAYBACK_SAVE}{url}"))
+        .await?
+        .error_for_status()
+        .wrap_err_with(|| format!("the Wayback Machine failed to save `{url}`"))?;
+    // The snapshot is either where the endpoint redirects to, or given by
+    // the `Content-Location` header.
+    let snapshot = match response.headers().get(CONTENT_LOCATION) {
+        Some(path) => format!("https://web.archive.org{}", path.to_str()?),
+        None => response.url().to_string(),
+    };
+    if !snapshot.contains("/web/") {
+        bail!("the Wayback Machine didn't return the snapshot of `{url}`");
+    }
+    Ok(snapshot)
+}
+
+/// Saves a snapshot of the given URL to archive.today.
+async fn save_to_archive_today(url: &str) -> Result<String> {
+    let submit = Url::parse_with_params(ARCHIVE_TODAY_SUBMIT, [("url", url)])?;
+    let response = get(submit.as_str())
+        .await?
+        .error_for_status()
+        .wrap_err_with(|| format!("archive.today failed to save `{url}`"))?;
+    // The snapshot is given by the `Refresh` header when it already exists.
+    // Otherwise, the endpoint redirects to the page where it is being made,
+    // whose URL becomes that of the snapshot.
+    let refresh = response
+        .headers()
+        .get(REFRESH)
+        .and_then(|refresh| refresh.to_str().ok())
+        .and_then(|refresh| refresh.split_once("url="))
+        .map(|(_, snapshot)| snapshot.to_owned());
+    let snapshot = refresh.unwrap_or_else(|| response.url().as_str().replacen("/wip/", "/", 1));
+    if snapshot.starts_with(ARCHIVE_TODAY_SUBMIT) {
+        bail!("archive.today didn't return the snapshot of `{url}`");
+    }
+    Ok(snapshot)
+}
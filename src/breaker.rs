@@ -0,0 +1,92 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use url::Url;
+
+/// Stops loading the URLs of the hosts that failed too many times in a row,
+/// such as dead domains, so that each of their URLs doesn't wait for its own
+/// failure.
+pub struct Breaker {
+    threshold: u32,
+    /// How many times in a row each host failed, if it did.
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl Breaker {
+    /// Creates a breaker for the hosts that fail `threshold` times in a row.
+    pub fn new(threshold: u32) -> Self {
+        Breaker {
+            threshold,
+            failures: Mutex::default(),
+        }
+    }
+
+    /// Whether the host of the given URL failed too many times in a row, so
+    /// that the URL shouldn't be loaded.
+    pub fn is_open(&self, url: &str) -> bool {
+        let failures = self.failures.lock().unwrap();
+        failures
+            .get(&host_of(url))
+            .is_some_and(|&count| count >= self.threshold)
+    }
+
+    /// Records whether the given URL failed to load. Returns whether its host
+    /// just reached the threshold.
+    pub fn record(&self, url: &str, failed: bool) -> bool {
+        let mut failures = self.failures.lock().unwrap();
+        let host = host_of(url);
+        if !failed {
+            failures.remove(&host);
+            return false;
+        }
+        let count = failures.entry(host).or_default();
+        *count += 1;
+        *count == self.threshold
+    }
+}
+
+/// Returns the host and port of the given URL, since different servers may
+/// listen on the ports of a host, or an empty string if it has none.
+fn host_of(url: &str) -> String {
+    let Ok(url) = Url::parse(url) else {
+        return String::new();
+    };
+    match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (host, _) => host.unwrap_or_default().to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_the_failures_in_a_row_of_a_host() {
+        let breaker = Breaker::new(2);
+        assert!(!breaker.record("https://dead.example/a", true));
+        assert!(!breaker.is_open("https://dead.example/b"));
+        assert!(breaker.record("https://dead.example/b", true));
+        assert!(breaker.is_open("https://dead.example/c"));
+        // The threshold is only reached once.
+        assert!(!breaker.record("https://dead.example/c", true));
+        assert!(!breaker.is_open("https://other.example/"));
+        assert!(!breaker.is_open("https://dead.example:8443/"));
+    }
+
+    #[test]
+    fn resets_on_success() {
+        let breaker = Breaker::new(2);
+        breaker.record("https://flaky.example/a", true);
+        breaker.record("https://flaky.example/b", false);
+        breaker.record("https://flaky.example/c", true);
+        assert!(!breaker.is_open("https://flaky.example/d"));
+    }
+
+    #[test]
+    fn tells_hosts_apart_by_their_port() {
+        assert_eq!(host_of("https://example.com/a"), "example.com:443");
+        assert_eq!(host_of("http://example.com/a"), "example.com:80");
+        assert_eq!(host_of("http://example.com:8080/a"), "example.com:8080");
+        assert_eq!(host_of("not a url"), "");
+    }
+}
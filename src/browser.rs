@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+
+use crate::input::{Entry, FOLDER_SEPARATOR};
+
+/// Seconds between the Windows epoch (1601-01-01), used by Chrome, and the
+/// Unix epoch.
+const CHROME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// Reads the visited pages from a Firefox `places.sqlite` database. The time of
+/// the last visit (in Unix seconds) and the visit count are passed through.
+pub fn firefox_history(path: &Path, since: Option<SystemTime>) -> Result<Vec<Entry>> {
+    let since = since.map_or(0, unix_micros);
+    with_copy(path, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT url, last_visit_date, visit_count FROM moz_places
+             WHERE last_visit_date >= ?1 AND (url LIKE 'http://%' OR url LIKE 'https://%')
+             ORDER BY last_visit_date DESC",
+        )?;
+        let entries = stmt
+            .query_map([since], |row| {
+                let visited: i64 = row.get(1)?;
+                let count: i64 = row.get(2)?;
+                Ok(entry(
+                    row.get(0)?,
+                    [
+                        ("last_visit", (visited / 1_000_000).to_string()),
+                        ("visit_count", count.to_string()),
+                    ],
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    })
+}
+
+/// Reads the bookmarks from a Firefox `places.sqlite` database. The folder and
+/// the time it was added (in Unix seconds) are passed through, like the
+/// `bookmarks` input format does.
+pub fn firefox_bookmarks(path: &Path, since: Option<SystemTime>) -> Result<Vec<Entry>> {
+    let since = since.map_or(0, unix_micros);
+    with_copy(path, |conn| {
+        // Maps the id of each folder to its parent and title.
+        let mut folders = HashMap::new();
+        let mut stmt =
+            conn.prepare("SELECT id, parent, title FROM moz_bookmarks WHERE type = 2")?;
+        let rows = stmt.query_map([], |row| {
+            let title: Option<String> = row.get(2)?;
+            Ok((row.get::<_, i64>(0)?, (row.get::<_, i64>(1)?, title)))
+        })?;
+        for row in rows {
+            let (id, folder) = row?;
+            folders.insert(id, folder);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT p.url, b.parent, b.dateAdded FROM moz_bookmarks b
+             JOIN moz_places p ON p.id = b.fk
+             WHERE b.type = 1 AND b.dateAdded >= ?1
+               AND (p.url LIKE 'http://%' OR p.url LIKE 'https://%')
+             ORDER BY b.parent, b.position",
+        )?;
+        let entries = stmt
+            .query_map([since], |row| {
+                let parent: i64 = row.get(1)?;
+                let added: i64 = row.get(2)?;
+                Ok(entry(
+                    row.get(0)?,
+                    [
+                        ("folder", firefox_folder_path(&folders, parent)),
+                        ("add_date", (added / 1_000_000).to_string()),
+                    ],
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    })
+}
+
+/// Builds the path of the given Firefox folder. The root folder, which has no
+/// parent, is left out.
+fn firefox_folder_path(folders: &HashMap<i64, (i64, Option<String>)>, mut id: i64) -> String {
+    let mut path = Vec::new();
+    while let Some((parent, title)) = folders.get(&id) {
+        if *parent == 0 {
+            break;
+        }
+        path.push(title.as_deref().unwrap_or_default());
+        id = *parent;
+    }
+    path.reverse();
+    path.join(FOLDER_SEPARATOR)
+}
+
+/// Reads the visited pages from a Chrome `History` database. The time of the
+/// last visit (in Unix seconds) and the visit count are passed through.
+pub fn chrome_history(path: &Path, since: Option<SystemTime>) -> Result<Vec<Entry>> {
+    let since = since.map_or(0, |t| unix_micros(t) + CHROME_EPOCH_OFFSET * 1_000_000);
+    with_copy(path, |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT url, last_visit_time, visit_count FROM urls
+             WHERE last_visit_time >= ?1 AND (url LIKE 'http://%' OR url LIKE 'https://%')
+             ORDER BY last_visit_time DESC",
+        )?;
+        let entries = stmt
+            .query_map([since], |row| {
+                let visited: i64 = row.get(1)?;
+                let count: i64 = row.get(2)?;
+                Ok(entry(
+                    row.get(0)?,
+                    [
+                        ("last_visit", chrome_to_unix(visited).to_string()),
+                        ("visit_count", count.to_string()),
+                    ],
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    })
+}
+
+/// Reads the bookmarks from a Chrome `Bookmarks` file, which is JSON. The
+/// folder and the time it was added (in Unix seconds) are passed through.
+pub fn chrome_bookmarks(contents: &str, since: Option<SystemTime>) -> Result<Vec<Entry>> {
+    fn walk(node: &Value, folders: &mut Vec<String>, since: i64, entries: &mut Vec<Entry>) {
+        let added = node["date_added"]
+            .as_str()
+            .and_then(|d| d.parse::<i64>().ok())
+            .map_or(0, chrome_to_unix);
+        match node["type"].as_str() {
+            Some("url") if added >= since => {
+                let url = node["url"].as_str().unwrap_or_default();
+                if url.starts_with("http://") || url.starts_with("https://") {
+                    entries.push(entry(
+                        url.to_owned(),
+                        [
+                            ("folder", folders.join(FOLDER_SEPARATOR)),
+                            ("add_date", added.to_string()),
+                        ],
+                    ));
+                }
+            }
+            Some("folder") => {
+                folders.push(node["name"].as_str().unwrap_or_default().to_owned());
+                for child in node["children"].as_array().into_iter().flatten() {
+                    walk(child, folders, since, entries);
+                }
+                folders.pop();
+            }
+            _ => (),
+        }
+    }
+
+    let root: Value = serde_json::from_str(contents).wrap_err("invalid Chrome bookmarks file")?;
+    let since = since.map_or(0, |t| unix_micros(t) / 1_000_000);
+    let mut entries = Vec::new();
+    for node in root["roots"]
+        .as_object()
+        .into_iter()
+        .flat_map(|r| r.values())
+    {
+        walk(node, &mut Vec::new(), since, &mut entries);
+    }
+    Ok(entries)
+}
+
+/// Builds an entry with the given passthrough fields.
+fn entry<const N: usize>(url: String, fields: [(&str, String); N]) -> Entry {
+    Entry {
+        url,
+        fields: fields.map(|(k, v)| (k.to_owned(), v)).into(),
+    }
+}
+
+/// Opens a copy of the given database and runs `f` on it. Browsers keep their
+/// databases locked while running, so reading a copy avoids both the lock and
+/// any risk of modifying the original. Each copy has a directory of its own,
+/// which is removed afterwards.
+fn with_copy<T>(path: &Path, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    let dir = tempfile::Builder::new()
+        .prefix("titlelist-")
+        .tempdir()
+        .wrap_err("failed to create a temporary directory")?;
+    let copy = dir.path().join("db.sqlite");
+    fs::copy(path, &copy).wrap_err_with(|| format!("failed to copy `{}`", path.display()))?;
+    // Recent changes may still live in the write-ahead log, whose index is in
+    // the shared-memory file.
+    for suffix in ["-wal", "-shm"] {
+        let file = append_to_path(path, suffix);
+        if file.exists() {
+            fs::copy(&file, append_to_path(&copy, suffix))
+                .wrap_err_with(|| format!("failed to copy `{}`", file.display()))?;
+        }
+    }
+    let conn = Connection::open_with_flags(&copy, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .wrap_err_with(|| format!("failed to open `{}`", path.display()))?;
+    f(&conn)
+}
+
+/// Appends the given suffix to the file name of the path.
+fn append_to_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Converts the given time to microseconds since the Unix epoch.
+fn unix_micros(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_micros() as i64)
+}
+
+/// Converts a Chrome timestamp (microseconds since the Windows epoch) to
+/// seconds since the Unix epoch.
+fn chrome_to_unix(micros: i64) -> i64 {
+    micros / 1_000_000 - CHROME_EPOCH_OFFSET
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_firefox_history_with_its_write_ahead_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("places.sqlite");
+        // The connection is kept open, so that the rows stay in the log.
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA wal_autocheckpoint = 0;
+             CREATE TABLE moz_places (url TEXT, last_visit_date INTEGER, visit_count INTEGER);
+             INSERT INTO moz_places VALUES
+                 ('https://example.com/', 1700000000000000, 3),
+                 ('https://example.org/', 1600000000000000, 1),
+                 ('place:sort=8', 1700000000000000, 1);",
+        )
+        .unwrap();
+        assert!(append_to_path(&path, "-wal").exists());
+
+        let entries = firefox_history(&path, None).unwrap();
+        let urls: Vec<_> = entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/", "https://example.org/"]);
+        assert_eq!(entries[0].field("last_visit"), Some("1700000000"));
+        assert_eq!(entries[0].field("visit_count"), Some("3"));
+
+        let since = UNIX_EPOCH + std::time::Duration::from_secs(1_650_000_000);
+        assert_eq!(firefox_history(&path, Some(since)).unwrap().len(), 1);
+        // Both reads can run at once, as each copy is its own.
+        let (a, b) = std::thread::scope(|s| {
+            let a = s.spawn(|| firefox_history(&path, None).unwrap().len());
+            let b = s.spawn(|| firefox_history(&path, None).unwrap().len());
+            (a.join().unwrap(), b.join().unwrap())
+        });
+        assert_eq!((a, b), (2, 2));
+        drop(conn);
+    }
+
+    #[test]
+    fn parses_chrome_bookmarks() {
+        let contents = r#"{"roots": {"bookmark_bar": {"type": "folder", "name": "Bar", "children": [
+            {"type": "url", "url": "https://example.com/", "date_added": "13300000000000000"},
+            {"type": "folder", "name": "Docs", "children": [
+                {"type": "url", "url": "https://docs.rs/", "date_added": "13300000000000000"}
+            ]},
+            {"type": "url", "url": "chrome://settings", "date_added": "13300000000000000"}
+        ]}}}"#;
+        let entries = chrome_bookmarks(contents, None).unwrap();
+        let urls: Vec<_> = entries.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, ["https://example.com/", "https://docs.rs/"]);
+        assert_eq!(
+            entries[1].field("folder"),
+            Some(&*format!("Bar{FOLDER_SEPARATOR}Docs"))
+        );
+        assert_eq!(entries[0].field("add_date"), Some("1655526400"));
+    }
+}
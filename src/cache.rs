@@ -0,0 +1,487 @@
+use std::{
+    collections::HashSet,
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{Map, Value};
+use tracing::info;
+use url::Url;
+
+use crate::{
+    analyze::{Analyzer, Resolution},
+    fetch::{Revalidated, Validators},
+    output::NO_TITLE,
+};
+
+/// Name of the database file inside the cache directory.
+const DATABASE_NAME: &str = "cache.sqlite";
+
+#[derive(clap::Args)]
+pub struct CacheArgs {
+    /// Directory of the cache, as given to `--cache`.
+    #[arg(long)]
+    dir: PathBuf,
+
+    #[command(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(clap::Subcommand)]
+enum CacheCommand {
+    /// Lists the cached titles, with how long ago they were fetched.
+    List {
+        /// Only lists the titles of these hosts.
+        #[arg(long)]
+        host: Vec<String>,
+    },
+    /// Shows the size of the cache and its hit rate.
+    Stats,
+    /// Removes the titles fetched longer ago than the given duration.
+    Prune {
+        /// Age of the oldest titles that are kept (e.g., `30d`).
+        #[arg(long, value_parser = humantime::parse_duration)]
+        older_than: Duration,
+    },
+    /// Removes all the titles, or only those of the given hosts.
+    Clear {
+        /// Only removes the titles of these hosts.
+        #[arg(long)]
+        host: Vec<String>,
+    },
+}
+
+/// Runs a cache management command.
+pub fn manage(args: CacheArgs) -> Result<()> {
+    let conn = open_database(&args.dir)?;
+    match args.command {
+        CacheCommand::List { host } => {
+            let now = unix_secs(SystemTime::now());
+            let mut stmt =
+                conn.prepare("SELECT url, title, fetched_at FROM titles ORDER BY url")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let url: String = row.get(0)?;
+                if !host.is_empty() && !host.contains(&url_host(&url)) {
+                    continue;
+                }
+                let title: Option<String> = row.get(1)?;
+                let age = Duration::from_secs((now - row.get::<_, i64>(2)?).max(0) as u64);
+                println!(
+                    "{url}\t{}\t{} ago",
+                    title.as_deref().unwrap_or(NO_TITLE),
+                    humantime::format_duration(age)
+                );
+            }
+        }
+        CacheCommand::Stats => {
+            let urls = cached_urls(&conn)?;
+            let hosts: HashSet<_> = urls.iter().map(|url| url_host(url)).collect();
+            let size = fs::metadata(args.dir.join(DATABASE_NAME))?.len();
+            let [hits, revalidated, misses] = ["hits", "revalidated", "misses"].map(|name| {
+                conn.query_row("SELECT value FROM stats WHERE name = ?1", [name], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .optional()
+                .map(Option::unwrap_or_default)
+            });
+            let (hits, revalidated, misses) = (hits?, revalidated?, misses?);
+            let lookups = hits + revalidated + misses;
+            println!("entries: {}", urls.len());
+            println!("hosts: {}", hosts.len());
+            println!("size: {size} bytes");
+            println!("hits: {hits}");
+            println!("revalidated: {revalidated}");
+            println!("misses: {misses}");
+            if lookups > 0 {
+                let rate = (hits + revalidated) as f64 / lookups as f64 * 100.0;
+                println!("hit rate: {rate:.1}%");
+            }
+        }
+        CacheCommand::Prune { older_than } => {
+            let oldest = unix_secs(SystemTime::now()) - older_than.as_secs() as i64;
+            let removed = conn.execute("DELETE FROM titles WHERE fetched_at < ?1", [oldest])?;
+            conn.execute("VACUUM", [])?;
+            info!("removed {removed} titles");
+        }
+        CacheCommand::Clear { host } if host.is_empty() => {
+            let removed = conn.execute("DELETE FROM titles", [])?;
+            conn.execute_batch("DELETE FROM stats; VACUUM;")?;
+            info!("removed {removed} titles");
+        }
+        CacheCommand::Clear { host } => {
+            let mut removed = 0;
+            for url in cached_urls(&conn)?
+                .iter()
+                .filter(|url| host.contains(&url_host(url)))
+            {
+                removed += conn.execute("DELETE FROM titles WHERE url = ?1", [url])?;
+            }
+            conn.execute("VACUUM", [])?;
+            info!("removed {removed} titles");
+        }
+    }
+    Ok(())
+}
+
+/// An on-disk cache of the resolved titles, keyed by URL, so that running
+/// titlelist again over a mostly-unchanged list only fetches the new or
+/// expired URLs. Expired URLs are revalidated with a conditional request when
+/// the server provided an `ETag` or a `Last-Modified` header.
+pub struct Cache {
+    conn: Mutex<Connection>,
+    ttl: Duration,
+}
+
+impl Cache {
+    /// Opens the cache in the given directory, creating it if needed.
+    pub fn open(dir: &Path, ttl: Duration) -> Result<Self> {
+        Ok(Cache {
+            conn: Mutex::new(open_database(dir)?),
+            ttl,
+        })
+    }
+
+    /// Returns the title of the given URL, along with the fields of its
+    /// analysis, fetching it only if the cache has no fresh entry for it. A
+    /// page that the server reports as not modified counts as fresh again.
+    /// Entries that lack any of the fields of the analyzer are fetched again.
+    /// Failures aren't cached, nor are the pages answered with an error
+    /// status, so that a transient one is fetched again by the next run or by
+    /// `--retry-failed`.
+    pub async fn resolve(&self, url: &str, analyzer: &Analyzer) -> Result<Resolution> {
+        let cached = self.lookup(url)?.filter(|entry| {
+            let has = |name| entry.fields.iter().any(|(k, _)| k == name);
+            analyzer.fields().into_iter().all(has)
+        });
+        let validators = match cached {
+            Some(entry) if entry.is_fresh(self.ttl) => {
+                self.count("hits")?;
+                return Ok(entry.into_resolution(analyzer));
+            }
+            Some(ref entry) => &entry.validators,
+            None => &Validators::default(),
+        };
+        match analyzer.fetch_if_modified(url, validators).await? {
+            Revalidated::NotModified => {
+                self.count("revalidated")?;
+                self.touch(url)?;
+                let entry = cached.map(|entry| entry.into_resolution(analyzer));
+                Ok(entry.unwrap_or_default())
+            }
+            Revalidated::Modified(mut page) => {
+                self.count("misses")?;
+                let validators = mem::take(&mut page.validators);
+                let resolution = analyzer.analyze(url, page);
+                self.store(url, &resolution, &validators)?;
+                Ok(resolution)
+            }
+        }
+    }
+
+    /// Returns the cached entry of the given URL, even if it is expired. The
+    /// pages answered with an error status, which older versions cached, are
+    /// left out.
+    fn lookup(&self, url: &str) -> Result<Option<CachedTitle>> {
+        let entry = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT title, fetched_at, etag, last_modified, fields, unwrapped_url, status
+                 FROM titles WHERE url = ?1 AND (status IS NULL OR status < 400)",
+                [url],
+                |row| {
+                    let fields: Option<String> = row.get(4)?;
+                    Ok(CachedTitle {
+                        title: row.get(0)?,
+                        fetched_at: row.get(1)?,
+                        validators: Validators {
+                            etag: row.get(2)?,
+                            last_modified: row.get(3)?,
+                        },
+                        fields: fields.as_deref().map(parse_fields).unwrap_or_default(),
+                        unwrapped_url: row.get(5)?,
+                        status: row.get(6)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(entry)
+    }
+
+    /// Stores the resolution of the given URL, fetched just now, unless it was
+    /// answered with an error status.
+    fn store(&self, url: &str, resolution: &Resolution, validators: &Validators) -> Result<()> {
+        if resolution.status.is_some_and(|status| status >= 400) {
+            return Ok(());
+        }
+        let fields: Map<_, _> = resolution
+            .fields
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from(v.as_str())))
+            .collect();
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO titles
+                 (url, title, fetched_at, etag, last_modified, fields, unwrapped_url, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                url,
+                resolution.title,
+                unix_secs(SystemTime::now()),
+                validators.etag,
+                validators.last_modified,
+                Value::Object(fields).to_string(),
+                resolution.url,
+                resolution.status,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Marks the cached title of the given URL as fetched just now.
+    fn touch(&self, url: &str) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE titles SET fetched_at = ?2 WHERE url = ?1",
+            params![url, unix_secs(SystemTime::now())],
+        )?;
+        Ok(())
+    }
+}
+
+impl Cache {
+    /// Increments the given counter of the cache statistics.
+    fn count(&self, name: &str) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO stats (name, value) VALUES (?1, 1)
+             ON CONFLICT (name) DO UPDATE SET value = value + 1",
+            [name],
+        )?;
+        Ok(())
+    }
+}
+
+/// An entry of the cache.
+struct CachedTitle {
+    title: Option<String>,
+    /// When the title was fetched, in Unix seconds.
+    fetched_at: i64,
+    validators: Validators,
+    /// The fields of the analysis of the page.
+    fields: Vec<(String, String)>,
+    /// The canonical URL of the page, if it was unwrapped.
+    unwrapped_url: Option<String>,
+    /// The status code of the page, which caches created by older versions
+    /// don't have.
+    status: Option<u16>,
+}
+
+impl CachedTitle {
+    /// Converts the entry to a resolution with the fields of the given
+    /// analyzer, leaving out those of heuristics that are now disabled.
+    fn into_resolution(mut self, analyzer: &Analyzer) -> Resolution {
+        let names = analyzer.fields();
+        self.fields.retain(|(k, _)| names.contains(&k.as_str()));
+        Resolution {
+            title: self.title,
+            url: self.unwrapped_url.filter(|_| analyzer.rewrites_urls()),
+            status: self.status,
+            bytes: 0,
+            fields: self.fields,
+        }
+    }
+
+    /// Whether the entry is younger than the given TTL.
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        unix_secs(SystemTime::now()) - self.fetched_at < ttl.as_secs() as i64
+    }
+}
+
+/// Opens the database of the cache in the given directory, creating both if
+/// needed.
+fn open_database(dir: &Path) -> Result<Connection> {
+    fs::create_dir_all(dir).wrap_err_with(|| format!("failed to create `{}`", dir.display()))?;
+    let path = dir.join(DATABASE_NAME);
+    let conn =
+        Connection::open(&path).wrap_err_with(|| format!("failed to open `{}`", path.display()))?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS titles (
+             url TEXT PRIMARY KEY,
+             title TEXT,
+             fetched_at INTEGER NOT NULL
+         )",
+    )?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// Upgrades the schema of caches created by older versions.
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < 1 {
+        conn.execute_batch(
+            "ALTER TABLE titles ADD COLUMN etag TEXT;
+             ALTER TABLE titles ADD COLUMN last_modified TEXT;
+             PRAGMA user_version = 1;",
+        )?;
+    }
+    if version < 2 {
+        conn.execute_batch(
+            "CREATE TABLE stats (name TEXT PRIMARY KEY, value INTEGER NOT NULL);
+             PRAGMA user_version = 2;",
+        )?;
+    }
+    if version < 3 {
+        conn.execute_batch(
+            "ALTER TABLE titles ADD COLUMN fields TEXT;
+             PRAGMA user_version = 3;",
+        )?;
+    }
+    if version < 4 {
+        conn.execute_batch(
+            "ALTER TABLE titles ADD COLUMN unwrapped_url TEXT;
+             PRAGMA user_version = 4;",
+        )?;
+    }
+    if version < 5 {
+        conn.execute_batch(
+            "ALTER TABLE titles ADD COLUMN status INTEGER;
+             PRAGMA user_version = 5;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Parses the fields stored as a JSON object. Invalid ones are ignored.
+fn parse_fields(json: &str) -> Vec<(String, String)> {
+    let Ok(Value::Object(map)) = serde_json::from_str(json) else {
+        return Vec::new();
+    };
+    map.into_iter()
+        .filter_map(|(k, v)| Some((k, v.as_str()?.to_owned())))
+        .collect()
+}
+
+/// Returns all the URLs in the cache.
+fn cached_urls(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT url FROM titles")?;
+    let urls = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(urls)
+}
+
+/// Returns the host of the given URL, or an empty string if it has none.
+fn url_host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+/// Converts the given time to seconds since the Unix epoch.
+fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_and_looks_up_resolutions() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::open(dir.path(), Duration::from_secs(60)).unwrap();
+        assert!(cache.lookup("https://example.com/").unwrap().is_none());
+        let resolution = Resolution {
+            title: Some("Example Domain".to_owned()),
+            url: Some("https://example.com/canonical".to_owned()),
+            status: Some(200),
+            bytes: 1256,
+            fields: vec![("lang".to_owned(), "en".to_owned())],
+        };
+        let validators = Validators {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+        };
+        cache
+            .store("https://example.com/", &resolution, &validators)
+            .unwrap();
+        // The cache is kept on disk.
+        drop(cache);
+        let cache = Cache::open(dir.path(), Duration::from_secs(60)).unwrap();
+        let entry = cache.lookup("https://example.com/").unwrap().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Example Domain"));
+        assert_eq!(entry.validators.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(entry.validators.last_modified, None);
+        assert_eq!(entry.fields, [("lang".to_owned(), "en".to_owned())]);
+        assert_eq!(
+            entry.unwrapped_url.as_deref(),
+            Some("https://example.com/canonical")
+        );
+        assert_eq!(entry.status, Some(200));
+        assert!(entry.is_fresh(cache.ttl));
+        assert!(!entry.is_fresh(Duration::ZERO));
+    }
+
+    #[test]
+    fn migrates_caches_of_older_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = Connection::open(dir.path().join(DATABASE_NAME)).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE titles (url TEXT PRIMARY KEY, title TEXT, fetched_at INTEGER NOT NULL);
+             INSERT INTO titles VALUES ('https://example.com/', 'Old Title', 0);",
+        )
+        .unwrap();
+        drop(conn);
+        let cache = Cache::open(dir.path(), Duration::from_secs(60)).unwrap();
+        let entry = cache.lookup("https://example.com/").unwrap().unwrap();
+        assert_eq!(entry.title.as_deref(), Some("Old Title"));
+        assert!(entry.fields.is_empty());
+        assert_eq!(entry.status, None);
+        assert!(!entry.is_fresh(cache.ttl));
+        cache.count("hits").unwrap();
+    }
+
+    #[test]
+    fn ignores_invalid_fields() {
+        assert_eq!(
+            parse_fields(r#"{"lang": "en", "score": 1}"#),
+            [("lang".to_owned(), "en".to_owned())]
+        );
+        assert!(parse_fields("not json").is_empty());
+        assert!(parse_fields("[]").is_empty());
+    }
+
+    #[test]
+    fn leaves_out_error_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::open(dir.path(), Duration::from_secs(60)).unwrap();
+        let resolution = Resolution {
+            title: Some("503 Service Unavailable".to_owned()),
+            status: Some(503),
+            ..Default::default()
+        };
+        cache
+            .store("https://example.com/", &resolution, &Validators::default())
+            .unwrap();
+        assert!(cache.lookup("https://example.com/").unwrap().is_none());
+        // As cached by older versions.
+        cache
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO titles (url, title, fetched_at, status)
+                 VALUES ('https://example.com/', '503 Service Unavailable', ?1, 503)",
+                [unix_secs(SystemTime::now())],
+            )
+            .unwrap();
+        assert!(cache.lookup("https://example.com/").unwrap().is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashSet, fs, path::PathBuf, pin::Pin, time::Instant};
+
+use eyre::{bail, Result, WrapErr};
+use futures::{stream, StreamExt};
+use tokio::{
+    fs::File,
+    io::{stdin, AsyncRead, BufReader},
+};
+use tracing::info;
+
+use crate::{
+    fetch::{load_page, probe},
+    input, markdown,
+};
+
+/// Link texts that don't describe the page, so they can't be compared with its
+/// title.
+const GENERIC_TEXTS: &[&str] = &[
+    "here",
+    "click here",
+    "link",
+    "this",
+    "this link",
+    "more",
+    "read more",
+    "source",
+];
+
+#[derive(clap::Args)]
+pub struct CheckArgs {
+    /// Markdown files whose links are checked.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct CheckLinksArgs {
+    /// Path of the file that contains the URLs, one per line. Unless this
+    /// option is set, reads from the standard input.
+    #[arg(short, long)]
+    file: Option<PathBuf>,
+
+    /// Only reports the URLs that are broken.
+    #[arg(long, default_value = "false")]
+    only_broken: bool,
+}
+
+/// Checks whether the given URLs are reachable, without fetching their titles.
+/// For each URL, prints the status code, the time it took and, if it was
+/// redirected, the final URL. Fails if any URL is broken.
+pub async fn check_links(args: CheckLinksArgs) -> Result<()> {
+    let reader: Pin<Box<dyn AsyncRead + Send>> = match &args.file {
+        Some(path) => Box::pin(File::open(path).await?),
+        None => Box::pin(stdin()),
+    };
+    let mut results = input::stream_lines(BufReader::new(reader), b'\n')
+        .boxed()
+        .map(|entry| async move {
+            let entry = entry?;
+            let started = Instant::now();
+            let response = probe(&entry.url).await;
+            Ok::<_, eyre::Report>((entry.url, response, started.elapsed()))
+        })
+        .buffered(10);
+
+    let (mut total, mut broken) = (0, 0);
+    while let Some(result) = results.next().await {
+        let (url, response, elapsed) = result?;
+        let ms = elapsed.as_millis();
+        total += 1;
+        match response {
+            Ok(response) => {
+                let status = response.status();
+                let failed = status.is_client_error() || status.is_server_error();
+                broken += usize::from(failed);
+                if failed || !args.only_broken {
+                    let redirect = match response.url().as_str() {
+                        final_url if final_url != url => format!(" -> {final_url}"),
+                        _ => String::new(),
+                    };
+                    println!("{}\t{ms}ms\t{url}{redirect}", status.as_u16());
+                }
+            }
+            Err(error) => {
+                broken += 1;
+                println!("ERR\t{ms}ms\t{url}\t{error:#}");
+            }
+        }
+    }
+
+    if broken > 0 {
+        bail!("{broken} of {total} links are broken");
+    }
+    info!("all {total} links are reachable");
+    Ok(())
+}
+
+/// Checks the `http` and `https` links of the given Markdown files, reporting
+/// the links that fail to load and those whose text doesn't match the title
+/// of the page. Fails if any link was reported, so that it can be used in CI.
+pub async fn check(args: CheckArgs) -> Result<()> {
+    let mut links = Vec::new();
+    for path in &args.files {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+        let file = path.display().to_string();
+        links.extend(
+            markdown::links(&contents)
+                .into_iter()
+                .filter(|link| link.url.starts_with("http://") || link.url.starts_with("https://"))
+                .map(|link| (file.clone(), link)),
+        );
+    }
+
+    let total = links.len();
+    let mut reported = 0;
+    let mut results = stream::iter(links)
+        .map(|(file, link)| async move {
+            let page = load_page(&link.url).await;
+            (file, link, page)
+        })
+        .buffered(10);
+    while let Some((file, link, page)) = results.next().await {
+        let location = format!("{file}:{}", link.line);
+        // The pages that weren't answered with a success status are dead
+        // links, whatever the title of their error page.
+        match page.map(|page| (page.status, page.title)) {
+            Err(error) => {
+                println!("{location}: {}: {error:#}", link.url);
+                reported += 1;
+            }
+            Ok((status, _)) if !status.is_success() => {
+                println!("{location}: {}: answered `{status}`", link.url);
+                reported += 1;
+            }
+            Ok((_, Some(title))) if !matches_title(&link.text, &title) => {
+                println!(
+                    "{location}: {}: text {:?} doesn't match title {title:?}",
+                    link.url, link.text
+                );
+                reported += 1;
+            }
+            Ok((_, None)) => info!("no title for `{}`", link.url),
+            Ok((_, Some(_))) => (),
+        }
+    }
+
+    if reported > 0 {
+        bail!("{reported} of {total} links failed the check");
+    }
+    info!("all {total} links passed the check");
+    Ok(())
+}
+
+/// Whether the given link text plausibly describes a page with the given
+/// title: at least half of the words of the shorter of the two must appear in
+/// the other. Texts that are the URL itself or generic ones, like "here",
+/// always match.
+fn matches_title(text: &str, title: &str) -> bool {
+    let text_words = words(text);
+    if text.contains("://") || GENERIC_TEXTS.contains(&text_words.join(" ").as_str()) {
+        return true;
+    }
+    let text_words: HashSet<_> = text_words.into_iter().collect();
+    let title_words: HashSet<_> = words(title).into_iter().collect();
+    let shorter = text_words.len().min(title_words.len());
+    if shorter == 0 {
+        return true;
+    }
+    let common = text_words.intersection(&title_words).count();
+    common * 2 >= shorter
+}
+
+/// Returns the lowercase words of the given text.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_texts_that_share_most_words_with_the_title() {
+        assert!(matches_title(
+            "The Rust Book",
+            "The Rust Programming Language - The Rust Programming Language"
+        ));
+        assert!(matches_title(
+            "tokio docs",
+            "Tokio - An asynchronous Rust runtime | Docs"
+        ));
+        assert!(matches_title("Serde", "Overview · Serde"));
+        assert!(!matches_title("The Rust Book", "Page not found"));
+        assert!(!matches_title("Tokio tutorial", "Just a moment..."));
+    }
+
+    #[test]
+    fn matches_urls_and_generic_texts() {
+        assert!(matches_title("https://example.com/a", "Anything"));
+        assert!(matches_title("here", "Anything"));
+        assert!(matches_title("Click here", "Anything"));
+        assert!(matches_title("", "Anything"));
+    }
+}
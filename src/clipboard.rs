@@ -0,0 +1,66 @@
+use std::{thread, time::Duration};
+
+use arboard::Clipboard;
+use eyre::{Result, WrapErr};
+use futures::Stream;
+use url::Url;
+
+use crate::{input::Entry, watch::spawn_stream};
+
+/// How often the clipboard is checked for changes, since there is no portable
+/// way to be notified of them.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Returns an endless stream of the URLs copied to the system clipboard. The
+/// contents of the clipboard when the stream starts are ignored, as is any
+/// copied text that isn't a single `http` or `https` URL.
+pub fn watch() -> impl Stream<Item = Result<Entry>> + Send {
+    spawn_stream(|tx| {
+        let mut clipboard = open()?;
+        let mut last = clipboard.get_text().ok();
+        while !tx.is_closed() {
+            thread::sleep(POLL_INTERVAL);
+            // Fails if the clipboard is empty or holds something else than text.
+            let Ok(text) = clipboard.get_text() else {
+                continue;
+            };
+            if last.as_ref() == Some(&text) {
+                continue;
+            }
+            if let Some(url) = as_url(&text) {
+                if tx.blocking_send(Ok(Entry::new(url))).is_err() {
+                    break;
+                }
+            }
+            last = Some(text);
+        }
+        Ok(())
+    })
+}
+
+/// Returns the text in the system clipboard.
+pub fn get_text() -> Result<String> {
+    open()?
+        .get_text()
+        .wrap_err("failed to read text from the clipboard")
+}
+
+/// Replaces the contents of the system clipboard with the given text.
+pub fn set_text(text: String) -> Result<()> {
+    open()?
+        .set_text(text)
+        .wrap_err("failed to copy text to the clipboard")
+}
+
+/// Opens the system clipboard.
+fn open() -> Result<Clipboard> {
+    Clipboard::new().wrap_err("failed to access the clipboard")
+}
+
+/// Returns the given text as a URL if it is a single `http` or `https` URL.
+fn as_url(text: &str) -> Option<String> {
+    let text = text.trim();
+    let url = Url::parse(text).ok()?;
+    (matches!(url.scheme(), "http" | "https") && !text.contains(char::is_whitespace))
+        .then(|| text.to_owned())
+}
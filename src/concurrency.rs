@@ -0,0 +1,198 @@
+use std::{sync::Mutex, time::Duration};
+
+use tokio::{sync::Notify, time::Instant};
+use tracing::debug;
+
+/// How many requests may be in flight at first in the automatic mode.
+const INITIAL_LIMIT: f64 = 4.0;
+
+/// How many requests may be in flight at most in the automatic mode.
+pub const MAX_LIMIT: usize = 64;
+
+/// How much slower than usual a request may be before the limit stops
+/// growing.
+const LATENCY_TOLERANCE: f64 = 2.0;
+
+/// The weight of a request in the average latency.
+const LATENCY_WEIGHT: f64 = 0.1;
+
+/// How many requests are in flight at once.
+#[derive(Clone, Copy)]
+pub enum Concurrency {
+    Fixed(usize),
+    /// Adjusted to how the hosts respond, with a [`Limiter`].
+    Auto,
+}
+
+impl Concurrency {
+    /// Parses a number of requests, or `auto`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(Concurrency::Auto),
+            _ => match s.parse() {
+                Ok(0) => Err("must be at least 1".into()),
+                Ok(n) => Ok(Concurrency::Fixed(n)),
+                Err(_) => Err("expected a number or `auto`".into()),
+            },
+        }
+    }
+
+    /// Returns how many requests may be in flight at most.
+    pub fn max(self) -> usize {
+        match self {
+            Concurrency::Fixed(n) => n,
+            Concurrency::Auto => MAX_LIMIT,
+        }
+    }
+}
+
+/// Limits how many requests are in flight, adjusting the limit like TCP does
+/// with its window: it grows by one request per round of requests that
+/// completed without being much slower than usual, and it halves when a host
+/// seems overloaded, such as when it answers with `429 Too Many Requests`.
+pub struct Limiter {
+    state: Mutex<State>,
+    released: Notify,
+}
+
+struct State {
+    limit: f64,
+    in_flight: usize,
+    /// The average latency of the requests, once one completed.
+    latency: Option<Duration>,
+    /// When the limit was last decreased, since the requests that were in
+    /// flight then shouldn't decrease it again.
+    decreased: Option<Instant>,
+}
+
+impl Limiter {
+    /// Creates a limiter with its initial limit.
+    pub fn new() -> Self {
+        Limiter {
+            state: Mutex::new(State {
+                limit: INITIAL_LIMIT,
+                in_flight: 0,
+                latency: None,
+                decreased: None,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Waits until another request may be sent.
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return Permit {
+                        limiter: self,
+                        started: Instant::now(),
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+}
+
+/// Allows a request to be in flight until dropped.
+pub struct Permit<'a> {
+    limiter: &'a Limiter,
+    started: Instant,
+}
+
+impl Permit<'_> {
+    /// Records that the request completed, adjusting the limit to whether its
+    /// host seemed overloaded.
+    pub fn finish(self, overloaded: bool) {
+        let mut state = self.limiter.state.lock().unwrap();
+        let previous = state.limit as usize;
+        let latency = self.started.elapsed();
+        let average = *state.latency.get_or_insert(latency);
+        if overloaded {
+            if state
+                .decreased
+                .is_none_or(|decreased| decreased < self.started)
+            {
+                state.limit = (state.limit / 2.0).max(1.0);
+                state.decreased = Some(Instant::now());
+            }
+        } else if latency.as_secs_f64() <= average.as_secs_f64() * LATENCY_TOLERANCE {
+            state.limit = (state.limit + 1.0 / state.limit).min(MAX_LIMIT as f64);
+        }
+        state.latency =
+            Some(average.mul_f64(1.0 - LATENCY_WEIGHT) + latency.mul_f64(LATENCY_WEIGHT));
+        if state.limit as usize != previous {
+            debug!("concurrency is now {}", state.limit as usize);
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        state.in_flight -= 1;
+        let free = (state.limit as usize).saturating_sub(state.in_flight);
+        drop(state);
+        for _ in 0..free {
+            self.limiter.released.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the current limit of the given limiter.
+    fn limit(limiter: &Limiter) -> f64 {
+        limiter.state.lock().unwrap().limit
+    }
+
+    #[test]
+    fn parses_numbers_of_requests_or_auto() {
+        assert!(matches!(Concurrency::parse("8"), Ok(Concurrency::Fixed(8))));
+        assert!(matches!(Concurrency::parse("auto"), Ok(Concurrency::Auto)));
+        assert!(Concurrency::parse("0").is_err());
+        assert!(Concurrency::parse("many").is_err());
+        assert_eq!(Concurrency::Auto.max(), MAX_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn halves_the_limit_once_per_round_of_overloaded_requests() {
+        let limiter = Limiter::new();
+        let mut permits = Vec::new();
+        for _ in 0..INITIAL_LIMIT as usize {
+            permits.push(limiter.acquire().await);
+        }
+        for permit in permits {
+            permit.finish(true);
+        }
+        assert_eq!(limit(&limiter), INITIAL_LIMIT / 2.0);
+        // The requests sent after the decrease may decrease it again.
+        limiter.acquire().await.finish(true);
+        assert_eq!(limit(&limiter), INITIAL_LIMIT / 4.0);
+        limiter.acquire().await.finish(true);
+        assert_eq!(limit(&limiter), 1.0);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_permit_beyond_the_limit() {
+        let limiter = Limiter::new();
+        let mut permits = Vec::new();
+        for _ in 0..INITIAL_LIMIT as usize {
+            permits.push(limiter.acquire().await);
+        }
+        let timeout = Duration::from_millis(50);
+        assert!(tokio::time::timeout(timeout, limiter.acquire())
+            .await
+            .is_err());
+        drop(permits.pop());
+        assert!(tokio::time::timeout(timeout, limiter.acquire())
+            .await
+            .is_ok());
+    }
+}
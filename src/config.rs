@@ -0,0 +1,130 @@
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+
+use crate::{push::PushConfig, render::RenderMode, translate::TranslationConfig};
+
+mod toml;
+
+/// The configuration file, whose rules apply to every run. It is written in
+/// TOML.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The CSS selectors of the titles of the pages of some domains and their
+    /// subdomains, by domain, as in `selector."example.com" = "h1.title"`.
+    /// Several selectors may be given as an array, in which case the first
+    /// one that matches an element with some text is used.
+    pub selector: BTreeMap<String, OneOrMany>,
+    /// The rules that override the requests to some URLs, given as
+    /// `[[rule]]` tables. When several rules match a URL, the later ones take
+    /// precedence.
+    pub rule: Vec<Rule>,
+    /// The resolvers of the URLs of some sites through their APIs, given as
+    /// `[[resolver]]` tables, which are tried before the built-in ones of
+    /// `--site-apis`.
+    pub resolver: Vec<Resolver>,
+    /// The translation service of `--translate-to`.
+    pub translation: Option<TranslationConfig>,
+    /// The settings of the services of `--push`.
+    pub push: PushConfig,
+}
+
+/// A resolver of the URLs that match a pattern through an API that answers
+/// with JSON.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Resolver {
+    /// A regular expression of the URLs.
+    pub pattern: String,
+    /// The URL of the API, where `$1` or `${name}` stand for the capture
+    /// groups of the pattern.
+    pub endpoint: String,
+    /// The JSON pointer of the title in the response, as in `/data/title`.
+    pub title: String,
+    /// The JSON pointers of the fields given to the results, by name.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Overrides of the requests to the URLs that match either a domain, which
+/// also matches its subdomains unless it is a glob (as in `*.example.com`),
+/// or a regular expression of the whole URLs.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub domain: Option<String>,
+    pub pattern: Option<String>,
+    /// Headers sent along with the default ones, by name.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The `Cookie` header, which replaces the consent cookies.
+    pub cookie: Option<String>,
+    pub user_agent: Option<String>,
+    /// The HTTP or SOCKS proxy that the requests go through.
+    pub proxy: Option<String>,
+    /// When the pages are rendered, which overrides `--render`: `always`,
+    /// `auto` or `never`.
+    pub render: Option<RenderMode>,
+}
+
+/// A value of the configuration that may be given once or as an array.
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a string or an array of strings")]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    /// Returns the given values.
+    pub fn values(&self) -> &[String] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the given file or, if none is given, from
+    /// `titlelist/config.toml` in the configuration directory of the user, if
+    /// it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default());
+            }
+            Err(error) => {
+                return Err(error).wrap_err_with(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+        let value = toml::parse(&contents)
+            .and_then(|value| Ok(serde_json::from_value(value)?))
+            .wrap_err_with(|| format!("invalid configuration in `{}`", path.display()))?;
+        Ok(value)
+    }
+}
+
+/// Returns the path of the default configuration file, in `$XDG_CONFIG_HOME`
+/// or `~/.config`.
+fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".config")))?;
+    Some(dir.join("titlelist").join("config.toml"))
+}
@@ -0,0 +1,383 @@
+use eyre::{bail, eyre, Result};
+use serde_json::{Map, Value};
+
+/// Parses the given TOML document into a JSON object. Only the subset of TOML
+/// that configurations need is supported: tables, arrays of tables, dotted and
+/// quoted keys, single-line strings, integers, floats, booleans, arrays and
+/// inline tables, but not dates nor multi-line strings.
+pub fn parse(contents: &str) -> Result<Value> {
+    let mut parser = Parser {
+        chars: contents.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    parser
+        .document()
+        .map_err(|error| eyre!("line {}: {error}", parser.line))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    /// Parses the whole document, whose key/value pairs are inserted in the
+    /// table of the last header.
+    fn document(&mut self) -> Result<Value> {
+        let mut root = Map::new();
+        let mut current = Vec::new();
+        loop {
+            self.skip_blank(true);
+            match self.peek() {
+                None => break,
+                Some('[') => {
+                    self.pos += 1;
+                    let array = self.eat('[');
+                    let keys = self.keys()?;
+                    self.expect(']')?;
+                    if array {
+                        self.expect(']')?;
+                    }
+                    let (last, parents) = keys.split_last().unwrap();
+                    let parent = table_at(&mut root, parents)?;
+                    if array {
+                        let tables = parent
+                            .entry(last.clone())
+                            .or_insert_with(|| Value::Array(Vec::new()));
+                        let Value::Array(tables) = tables else {
+                            bail!("`{last}` isn't an array of tables");
+                        };
+                        tables.push(Value::Object(Map::new()));
+                    } else if !parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Object(Map::new()))
+                        .is_object()
+                    {
+                        bail!("`{last}` isn't a table");
+                    }
+                    current = keys;
+                }
+                Some(_) => {
+                    let table = table_at(&mut root, &current)?;
+                    self.key_value(table)?;
+                }
+            }
+            self.end_of_line()?;
+        }
+        Ok(Value::Object(root))
+    }
+
+    /// Parses a key/value pair, inserting it in the given table.
+    fn key_value(&mut self, table: &mut Map<String, Value>) -> Result<()> {
+        let keys = self.keys()?;
+        self.expect('=')?;
+        self.skip_blank(false);
+        let value = self.value()?;
+        let (last, parents) = keys.split_last().unwrap();
+        let table = table_at(table, parents)?;
+        if table.insert(last.clone(), value).is_some() {
+            bail!("`{last}` is defined twice");
+        }
+        Ok(())
+    }
+
+    /// Parses a key, made of dot-separated bare or quoted keys.
+    fn keys(&mut self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_blank(false);
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if self.pos == start {
+                        bail!("expected a key");
+                    }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            keys.push(key);
+            self.skip_blank(false);
+            if !self.eat('.') {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Parses a value.
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                loop {
+                    self.skip_blank(true);
+                    if self.eat(']') {
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_blank(true);
+                    if !self.eat(',') {
+                        self.skip_blank(true);
+                        self.expect(']')?;
+                        return Ok(Value::Array(values));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Map::new();
+                self.skip_blank(false);
+                if self.eat('}') {
+                    return Ok(Value::Object(table));
+                }
+                loop {
+                    self.key_value(&mut table)?;
+                    self.skip_blank(false);
+                    if !self.eat(',') {
+                        self.expect('}')?;
+                        return Ok(Value::Object(table));
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || "+-._".contains(c))
+                {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                let number = word.replace('_', "");
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => {
+                        if let Ok(integer) = number.parse::<i64>() {
+                            Ok(integer.into())
+                        } else if let Ok(float) = number.parse::<f64>() {
+                            Ok(float.into())
+                        } else {
+                            bail!("invalid value `{word}`")
+                        }
+                    }
+                }
+            }
+            None => bail!("expected a value"),
+        }
+    }
+
+    /// Parses a string between double quotes, which may have escapes.
+    fn basic_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some(u @ ('u' | 'U')) => {
+                            let len = if u == 'u' { 4 } else { 8 };
+                            let end = (self.pos + len).min(self.chars.len());
+                            let hex: String = self.chars[self.pos..end].iter().collect();
+                            self.pos = end;
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| eyre!("invalid escape `\\{u}{hex}`"))?
+                        }
+                        _ => bail!("invalid escape in string"),
+                    };
+                    string.push(escaped);
+                }
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Parses a string between single quotes, which is taken literally.
+    fn literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(string),
+                Some('\n') | None => bail!("unterminated string"),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Skips the whitespace and the comments, along with the line breaks if
+    /// `lines` is set.
+    fn skip_blank(&mut self, lines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => self.pos += 1,
+                '\n' if lines => {
+                    self.pos += 1;
+                    self.line += 1;
+                }
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Expects the end of the line, or of the document.
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_blank(false);
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some(c) => bail!("unexpected `{c}`"),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// Skips the given character if it is the next one.
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_blank(false);
+        if !self.eat(c) {
+            bail!("expected `{c}`");
+        }
+        Ok(())
+    }
+}
+
+/// Returns the table at the given path of keys from the given table, creating
+/// the missing ones. Arrays of tables stand for their last table.
+fn table_at<'a>(
+    mut table: &'a mut Map<String, Value>,
+    keys: &[String],
+) -> Result<&'a mut Map<String, Value>> {
+    for key in keys {
+        let mut value = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Array(tables) = value {
+            value = tables
+                .last_mut()
+                .ok_or_else(|| eyre!("`{key}` isn't a table"))?;
+        }
+        table = match value {
+            Value::Object(table) => table,
+            _ => bail!("`{key}` isn't a table"),
+        };
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_tables_and_values() {
+        let document = r#"
+            # A comment.
+            timeout = 10
+            ratio = 0.5 # Another comment.
+            quiet = true
+            name = "a \"quoted\" \u00e9 string"
+            path = 'C:\titles'
+            hosts = [
+                "a.example",
+                "b.example", # A trailing comma.
+            ]
+
+            [push.linkding]
+            url = "https://links.example"
+            tags = { unread = false, names = ["a", "b"] }
+
+            [[override]]
+            host = "a.example"
+            "title prefix" = "A: "
+
+            [[override]]
+            host = "b.example"
+            site.name.short = 'B'
+        "#;
+        assert_eq!(
+            parse(document).unwrap(),
+            json!({
+                "timeout": 10,
+                "ratio": 0.5,
+                "quiet": true,
+                "name": "a \"quoted\" é string",
+                "path": "C:\\titles",
+                "hosts": ["a.example", "b.example"],
+                "push": {
+                    "linkding": {
+                        "url": "https://links.example",
+                        "tags": { "unread": false, "names": ["a", "b"] },
+                    },
+                },
+                "override": [
+                    { "host": "a.example", "title prefix": "A: " },
+                    { "host": "b.example", "site": { "name": { "short": "B" } } },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn reads_numbers_with_underscores_and_signs() {
+        assert_eq!(
+            parse("a = 1_000\nb = -2\nc = +1.5e3").unwrap(),
+            json!({ "a": 1000, "b": -2, "c": 1500.0 })
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        let error = |document| parse(document).unwrap_err().to_string();
+        assert_eq!(error("a = 1\na = 2"), "line 2: `a` is defined twice");
+        assert_eq!(error("a = 1\n\nb = \"open"), "line 3: unterminated string");
+        assert_eq!(error("a = yes"), "line 1: invalid value `yes`");
+        assert_eq!(error("a = 1 2"), "line 1: unexpected `2`");
+        assert_eq!(error("a = 1\n[a]"), "line 2: `a` isn't a table");
+        assert_eq!(error("[a]\n[[a]]"), "line 2: `a` isn't an array of tables");
+        assert_eq!(error("= 1"), "line 1: expected a key");
+    }
+}
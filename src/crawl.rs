@@ -0,0 +1,134 @@
+use std::{collections::HashSet, io::stdout};
+
+use eyre::Result;
+use futures::{stream, StreamExt};
+use tracing::{error, info};
+use url::Url;
+
+use crate::{
+    fetch::load_page,
+    input::{self, Entry},
+    output::{Output, OutputFormat},
+    urls,
+};
+
+/// Extensions of the links that aren't pages, so they aren't followed.
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "svg", "ico", "pdf", "zip", "gz", "tar", "mp3", "mp4",
+    "webm", "css", "js", "json", "xml", "woff", "woff2",
+];
+
+#[derive(clap::Args)]
+pub struct CrawlArgs {
+    /// URL of the page where the crawl starts.
+    url: Url,
+
+    /// How many links away from the start page the crawl goes.
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+
+    /// Only follows the links that have the same origin as the start page.
+    #[arg(long, default_value = "false")]
+    same_origin: bool,
+
+    /// Stops discovering pages once this many were found.
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    max_pages: usize,
+
+    /// Template. Use `%title` and `%url` as placeholders.
+    #[arg(short, long, default_value = "%title <%url>")]
+    template: String,
+
+    /// Format of the output. Each result has a `depth` field.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Template)]
+    output_format: OutputFormat,
+}
+
+/// Crawls the pages linked from the start page, breadth first, up to the given
+/// depth, printing the title of every page found. Each page is fetched once,
+/// and the pages that fail to load are reported and skipped.
+pub async fn crawl(args: CrawlArgs) -> Result<()> {
+    let origin = args.url.origin();
+    let start = args.url.to_string();
+    let mut seen = HashSet::from([urls::normalize(&start)]);
+    let mut frontier = vec![start];
+    let mut output = Output::new(stdout(), args.output_format, &args.template);
+
+    for depth in 0..=args.depth {
+        let pages: Vec<_> = stream::iter(frontier)
+            .map(|url| async move {
+                let page = load_page(&url).await;
+                (url, page)
+            })
+            .buffered(10)
+            .collect()
+            .await;
+
+        let mut next = Vec::new();
+        for (url, page) in pages {
+            let page = match page {
+                Ok(page) => page,
+                Err(error) => {
+                    error!("failed `{url}`: {error:#}");
+                    continue;
+                }
+            };
+            if page.title.is_none() {
+                info!("no title for `{url}`");
+            }
+            let mut entry = Entry::new(url);
+            entry.fields.push(("depth".into(), depth.to_string()));
+            output.write(&entry, page.title.as_deref())?;
+
+            if depth == args.depth {
+                continue;
+            }
+            let base = Url::parse(&page.url).ok();
+            for link in input::parse_html(&page.html, base.as_ref(), false)? {
+                let Ok(mut link) = Url::parse(&link.url) else {
+                    continue;
+                };
+                link.set_fragment(None);
+                if (args.same_origin && link.origin() != origin) || !is_page(&link) {
+                    continue;
+                }
+                if seen.len() < args.max_pages && seen.insert(urls::normalize(link.as_str())) {
+                    next.push(link.into());
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    output.finish()
+}
+
+/// Whether the given link may be a page, judging by its extension.
+fn is_page(url: &Url) -> bool {
+    let last = url.path().rsplit('/').next().unwrap_or_default();
+    match last.rsplit_once('.') {
+        Some((_, ext)) => !SKIPPED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the link to the given URL is followed.
+    fn followed(url: &str) -> bool {
+        is_page(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn skips_the_links_to_files_that_arent_pages() {
+        assert!(followed("https://example.com/"));
+        assert!(followed("https://example.com/blog/post"));
+        assert!(followed("https://example.com/index.html"));
+        assert!(followed("https://example.com/v1.2/docs"));
+        assert!(!followed("https://example.com/logo.PNG"));
+        assert!(!followed("https://example.com/paper.pdf?download=1"));
+        assert!(!followed("https://example.com/feed.xml"));
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{extract::State, routing::get, Router};
+use eyre::{Result, WrapErr};
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+    sync::Notify,
+    time,
+};
+use tracing::{error, info};
+
+use crate::{
+    metrics::Metrics,
+    output::process_template,
+    serve::{self, bind_unix_socket, ListenAddr},
+};
+
+/// How often the queue directory is scanned for URLs submitted by other
+/// processes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Extension of the queued files. Each one holds a single URL.
+const QUEUED_EXTENSION: &str = "url";
+
+#[derive(clap::Args)]
+pub struct DaemonArgs {
+    /// Directory of the on-disk queue. It is created if it doesn't exist.
+    #[arg(long)]
+    queue: PathBuf,
+
+    /// File to which the results are appended.
+    #[arg(long)]
+    sink: PathBuf,
+
+    /// Also accepts URLs, one per line, through this Unix domain socket.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Minimum time between two fetches.
+    #[arg(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    interval: Duration,
+
+    /// Template of the results. Use `%title` and `%url` as placeholders.
+    #[arg(short, long, default_value = "%title <%url>")]
+    template: String,
+
+    /// Exposes metrics in the Prometheus format at `/metrics` on this
+    /// address, like `serve` does. Either a TCP address, such as
+    /// `127.0.0.1:9090`, or the path of a Unix domain socket, prefixed by
+    /// `unix:`.
+    #[arg(long, value_name = "ADDRESS")]
+    metrics_listen: Option<ListenAddr>,
+}
+
+#[derive(clap::Args)]
+pub struct EnqueueArgs {
+    /// Directory of the on-disk queue of the daemon.
+    #[arg(long)]
+    queue: PathBuf,
+
+    /// URLs to enqueue. Unless given, they are read from the standard input,
+    /// one per line.
+    urls: Vec<String>,
+}
+
+/// Adds URLs to the queue of a daemon.
+pub fn enqueue(args: EnqueueArgs) -> Result<()> {
+    fs::create_dir_all(&args.queue)?;
+    if !args.urls.is_empty() {
+        for url in &args.urls {
+            enqueue_url(&args.queue, url)?;
+        }
+        return Ok(());
+    }
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            enqueue_url(&args.queue, line.trim())?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the daemon until it is killed. The URLs in the queue directory are
+/// fetched in the order they were enqueued, and each one is only removed from
+/// the queue once its result is appended to the sink, so that no URL is lost
+/// across restarts. URLs that fail, including those answered with an error
+/// status, are moved to the `failed` subdirectory.
+pub async fn run(args: DaemonArgs) -> Result<()> {
+    fs::create_dir_all(args.queue.join("failed"))
+        .wrap_err_with(|| format!("failed to create `{}`", args.queue.display()))?;
+    let notify = Arc::new(Notify::new());
+    let metrics = Arc::new(Metrics::new());
+
+    if let Some(listen) = args.metrics_listen.clone() {
+        let app = Router::new()
+            .route("/metrics", get(encode_metrics))
+            .with_state(Arc::clone(&metrics));
+        tokio::spawn(async move {
+            if let Err(error) = serve::listen(&listen, app).await {
+                error!("metrics server error: {error:#}");
+            }
+        });
+    }
+
+    if let Some(path) = &args.socket {
+        let listener = bind_unix_socket(path)?;
+        let queue = args.queue.clone();
+        let notify = Arc::clone(&notify);
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let queue = queue.clone();
+                        let notify = Arc::clone(&notify);
+                        let metrics = Arc::clone(&metrics);
+                        tokio::spawn(async move {
+                            let accepted = accept_urls(stream, &queue, &notify, &metrics).await;
+                            if let Err(error) = accepted {
+                                error!("socket error: {error:#}");
+                            }
+                        });
+                    }
+                    Err(error) => error!("socket error: {error}"),
+                }
+            }
+        });
+    }
+
+    info!("processing queue `{}`", args.queue.display());
+    loop {
+        let queued = queued_files(&args.queue)?;
+        if queued.is_empty() {
+            let _ = time::timeout(POLL_INTERVAL, notify.notified()).await;
+            continue;
+        }
+        for path in queued {
+            let started = time::Instant::now();
+            process_queued(&args, &metrics, &path).await?;
+            time::sleep_until(started + args.interval).await;
+        }
+    }
+}
+
+/// Encodes the metrics of the daemon.
+async fn encode_metrics(State(metrics): State<Arc<Metrics>>) -> String {
+    metrics.encode()
+}
+
+/// Reads URLs from a socket connection, one per line, enqueuing each of them.
+async fn accept_urls(
+    stream: UnixStream,
+    queue: &Path,
+    notify: &Notify,
+    metrics: &Metrics,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let url = line.trim();
+        if url.is_empty() {
+            continue;
+        }
+        enqueue_url(queue, url)?;
+        metrics.requests.with_label_values(&["socket"]).inc();
+        notify.notify_one();
+        writer
+            .write_all(format!("queued {url}\n").as_bytes())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Fetches the URL of a queued file, appends the result to the sink and
+/// removes the file from the queue.
+async fn process_queued(args: &DaemonArgs, metrics: &Metrics, path: &Path) -> Result<()> {
+    let url = fs::read_to_string(path)?;
+    let url = url.trim();
+
+    match metrics.fetch_title(url).await {
+        Ok(title) => {
+            let text = process_template(&args.template, title.as_deref().unwrap_or(url), url);
+            let mut sink = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&args.sink)
+                .await
+                .wrap_err_with(|| format!("failed to open `{}`", args.sink.display()))?;
+            sink.write_all(format!("{text}\n").as_bytes()).await?;
+            sink.sync_data().await?;
+            fs::remove_file(path)?;
+        }
+        Err(error) => {
+            error!("failed `{url}`: {error:#}");
+            fs::rename(
+                path,
+                args.queue.join("failed").join(path.file_name().unwrap()),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the queued files, oldest first.
+fn queued_files(queue: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<_> = fs::read_dir(queue)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext == QUEUED_EXTENSION));
+    files.sort();
+    Ok(files)
+}
+
+/// Adds a URL to the given queue directory. The file is written under a
+/// temporary name first, so that the daemon never sees a partial file. Names
+/// start with the current time, so that sorting them gives the queue order.
+fn enqueue_url(queue: &Path, url: &str) -> Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let name = format!("{now:020}-{}-{count}", process::id());
+
+    let tmp = queue.join(format!(".{name}.tmp"));
+    fs::write(&tmp, url)?;
+    fs::rename(&tmp, queue.join(name).with_extension(QUEUED_EXTENSION))
+        .wrap_err_with(|| format!("failed to enqueue `{url}`"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::Html};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn moves_the_urls_answered_with_an_error_status_to_failed() {
+        let app = Router::new()
+            .route("/ok", get(|| async { Html("<title>Fine</title>") }))
+            .route(
+                "/gone",
+                get(|| async { (StatusCode::NOT_FOUND, Html("<title>Not Found</title>")) }),
+            );
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        let dir = tempfile::tempdir().unwrap();
+        let args = DaemonArgs {
+            queue: dir.path().join("queue"),
+            sink: dir.path().join("sink.txt"),
+            socket: None,
+            interval: Duration::ZERO,
+            template: "%title <%url>".to_owned(),
+            metrics_listen: None,
+        };
+        fs::create_dir_all(args.queue.join("failed")).unwrap();
+        enqueue_url(&args.queue, &format!("http://{address}/ok")).unwrap();
+        enqueue_url(&args.queue, &format!("http://{address}/gone")).unwrap();
+        let metrics = Metrics::new();
+        for path in queued_files(&args.queue).unwrap() {
+            process_queued(&args, &metrics, &path).await.unwrap();
+        }
+
+        assert!(queued_files(&args.queue).unwrap().is_empty());
+        let sink = fs::read_to_string(&args.sink).unwrap();
+        assert_eq!(sink, format!("Fine <http://{address}/ok>\n"));
+        let failed = queued_files(&args.queue.join("failed")).unwrap();
+        assert_eq!(failed.len(), 1);
+        let url = fs::read_to_string(&failed[0]).unwrap();
+        assert_eq!(url, format!("http://{address}/gone"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result, WrapErr};
+use serde_json::Value;
+
+use crate::output::NO_TITLE;
+
+#[derive(clap::Args)]
+pub struct DiffArgs {
+    /// Output of the previous run, in the `json`, `ndjson` or `csv` format.
+    old: PathBuf,
+
+    /// Output of the new run, in the `json`, `ndjson` or `csv` format.
+    new: PathBuf,
+}
+
+/// A result of a run, as read back from its output.
+struct Record {
+    url: String,
+    title: Option<String>,
+    error: Option<String>,
+}
+
+impl Record {
+    /// Whether the URL failed, or resolved to no title.
+    fn failed(&self) -> bool {
+        self.error.is_some() || self.title.is_none()
+    }
+}
+
+/// Compares the outputs of two runs, printing the URLs whose title changed,
+/// that are new, that disappeared, that started failing, or that recovered.
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let old = read_records(&args.old)?;
+    let new = read_records(&args.new)?;
+    for change in changes(&old, &new) {
+        println!("{change}");
+    }
+    Ok(())
+}
+
+/// Returns the lines of the differences between the given records, in the
+/// order of the new ones, followed by those that disappeared.
+fn changes(old: &[Record], new: &[Record]) -> Vec<String> {
+    let old_by_url: HashMap<_, _> = old.iter().map(|r| (r.url.as_str(), r)).collect();
+    let new_by_url: HashMap<_, _> = new.iter().map(|r| (r.url.as_str(), r)).collect();
+
+    let mut changes = Vec::new();
+    for record in new {
+        let url = &record.url;
+        let Some(previous) = old_by_url.get(url.as_str()) else {
+            changes.push(format!("added {url}"));
+            continue;
+        };
+        match (previous.failed(), record.failed()) {
+            (false, true) => match &record.error {
+                Some(error) => changes.push(format!("failing {url}: {error}")),
+                None => changes.push(format!("failing {url}: no title")),
+            },
+            (true, false) => changes.push(format!("recovered {url}: {}", describe(record))),
+            (false, false) if previous.title != record.title => {
+                changes.push(format!(
+                    "changed {url}: {} -> {}",
+                    describe(previous),
+                    describe(record)
+                ));
+            }
+            _ => (),
+        }
+    }
+    for record in old {
+        if !new_by_url.contains_key(record.url.as_str()) {
+            changes.push(format!("removed {}", record.url));
+        }
+    }
+    changes
+}
+
+/// Describes the title of a record for the diff.
+fn describe(record: &Record) -> String {
+    match &record.title {
+        Some(title) => format!("{title:?}"),
+        None => "no title".into(),
+    }
+}
+
+/// Reads the records of the output file at the given path, detecting its
+/// format from its first character.
+fn read_records(path: &Path) -> Result<Vec<Record>> {
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+    let wrap = || format!("failed to parse `{}`", path.display());
+    match contents.trim_start().chars().next() {
+        Some('[') => {
+            let values: Vec<Value> = serde_json::from_str(&contents).wrap_err_with(wrap)?;
+            values.iter().map(json_record).collect()
+        }
+        Some('{') => contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let value: Value = serde_json::from_str(line).wrap_err_with(wrap)?;
+                json_record(&value)
+            })
+            .collect(),
+        _ => csv_records(&contents).wrap_err_with(wrap),
+    }
+}
+
+/// Reads a record from a JSON object of the `json` and `ndjson` formats.
+fn json_record(value: &Value) -> Result<Record> {
+    let field = |name| value[name].as_str().map(str::to_owned);
+    Ok(Record {
+        url: field("url").ok_or_else(|| eyre!("missing `url` field"))?,
+        title: field("title"),
+        error: field("error"),
+    })
+}
+
+/// Reads the records of the `csv` format.
+fn csv_records(contents: &str) -> Result<Vec<Record>> {
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name| headers.iter().position(|h| h == name);
+    let url_column = column("url").ok_or_else(|| eyre!("missing `url` column"))?;
+    let (title_column, error_column) = (column("title"), column("error"));
+
+    let mut records = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let field = |i: Option<usize>| {
+            i.and_then(|i| row.get(i))
+                .filter(|v| !v.is_empty() && *v != NO_TITLE)
+                .map(str::to_owned)
+        };
+        records.push(Record {
+            url: row[url_column].to_owned(),
+            title: field(title_column),
+            error: field(error_column),
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_csv_records() {
+        let contents = format!(
+            "title,url,error\nA,https://a.example/,\n{NO_TITLE},https://b.example/,\n,https://c.example/,timed out\n"
+        );
+        let records = csv_records(&contents).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].title.as_deref(), Some("A"));
+        assert!(!records[0].failed());
+        assert!(records[1].title.is_none() && records[1].failed());
+        assert_eq!(records[2].error.as_deref(), Some("timed out"));
+        assert!(csv_records("title\nA\n").is_err());
+    }
+
+    #[test]
+    fn reads_json_records() {
+        let value = serde_json::json!({"url": "https://a.example/", "title": "A", "extra": 1});
+        let record = json_record(&value).unwrap();
+        assert_eq!(record.url, "https://a.example/");
+        assert_eq!(record.title.as_deref(), Some("A"));
+        assert!(record.error.is_none());
+        assert!(json_record(&serde_json::json!({"title": "A"})).is_err());
+    }
+
+    #[test]
+    fn lists_the_changes_between_runs() {
+        let record = |url: &str, title: Option<&str>, error: Option<&str>| Record {
+            url: url.to_owned(),
+            title: title.map(str::to_owned),
+            error: error.map(str::to_owned),
+        };
+        let old = [
+            record("https://same.example/", Some("Same"), None),
+            record("https://changed.example/", Some("Old"), None),
+            record("https://failing.example/", Some("Up"), None),
+            record("https://recovered.example/", None, Some("down")),
+            record("https://removed.example/", Some("Gone"), None),
+        ];
+        let new = [
+            record("https://same.example/", Some("Same"), None),
+            record("https://changed.example/", Some("New"), None),
+            record("https://failing.example/", None, Some("timed out")),
+            record("https://recovered.example/", Some("Back"), None),
+            record("https://added.example/", None, None),
+        ];
+        assert_eq!(
+            changes(&old, &new),
+            [
+                r#"changed https://changed.example/: "Old" -> "New""#,
+                "failing https://failing.example/: timed out",
+                r#"recovered https://recovered.example/: "Back""#,
+                "added https://added.example/",
+                "removed https://removed.example/"
+            ]
+        );
+    }
+}
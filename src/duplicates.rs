@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+/// The distinct URLs of the results by their title, to find those with the
+/// same title, which are often soft 404 pages, consent walls or duplicated
+/// content.
+#[derive(Default)]
+pub struct TitleGroups {
+    /// The URLs with each title, in the order of the results.
+    urls: HashMap<String, Vec<String>>,
+    /// The titles, in the order they were first seen.
+    titles: Vec<String>,
+}
+
+impl TitleGroups {
+    /// Records that the given URL has the given title.
+    pub fn add(&mut self, title: &str, url: &str) {
+        let title = title.trim();
+        let urls = match self.urls.get_mut(title) {
+            Some(urls) => urls,
+            None => {
+                self.titles.push(title.to_owned());
+                self.urls.entry(title.to_owned()).or_default()
+            }
+        };
+        if !urls.iter().any(|seen| seen == url) {
+            urls.push(url.to_owned());
+        }
+    }
+
+    /// Whether other URLs have the given title.
+    pub fn is_duplicate(&self, title: &str) -> bool {
+        self.urls
+            .get(title.trim())
+            .is_some_and(|urls| urls.len() > 1)
+    }
+
+    /// Prints the titles that several URLs have, along with their URLs, to
+    /// the standard error.
+    pub fn print(&self) {
+        let groups: Vec<_> = self
+            .titles
+            .iter()
+            .map(|title| (title, &self.urls[title]))
+            .filter(|(_, urls)| urls.len() > 1)
+            .collect();
+        if groups.is_empty() {
+            eprintln!("no URLs have the same title");
+        }
+        for (title, urls) in groups {
+            eprintln!("{} URLs have the title `{title}`:", urls.len());
+            for url in urls {
+                eprintln!("  {url}");
+            }
+        }
+    }
+}
+
+/// Groups the titles that are near-duplicates, such as those of an article
+/// that is syndicated under many URLs with a different site name, by how many
+/// of their shingles they share: the sequences of three characters of their
+/// normalized text. A title joins the clusters of all the titles it is similar
+/// to, which are merged.
+pub struct Clusters {
+    similarity: f64,
+    /// The shingles of each title.
+    shingles: Vec<HashSet<String>>,
+    /// The titles that have each shingle.
+    index: HashMap<String, Vec<usize>>,
+    /// The title that each title was merged with, forming a tree per cluster.
+    parents: Vec<usize>,
+}
+
+impl Clusters {
+    /// Creates the clusters of the titles whose similarity, from 0 to 1, is at
+    /// least `similarity`.
+    pub fn new(similarity: f64) -> Self {
+        Clusters {
+            similarity,
+            shingles: Vec::new(),
+            index: HashMap::new(),
+            parents: Vec::new(),
+        }
+    }
+
+    /// Adds a title, merging it with the clusters of the titles it is similar
+    /// to.
+    pub fn add(&mut self, title: &str) {
+        let id = self.shingles.len();
+        let shingles = shingles(title);
+        // Only the titles that share some shingle with this one can be similar
+        // to it.
+        let mut shared: HashMap<usize, usize> = HashMap::new();
+        for shingle in &shingles {
+            for &other in self.index.get(shingle).into_iter().flatten() {
+                *shared.entry(other).or_default() += 1;
+            }
+            self.index.entry(shingle.clone()).or_default().push(id);
+        }
+        self.parents.push(id);
+        for (other, shared) in shared {
+            let total = shingles.len() + self.shingles[other].len();
+            if 2.0 * shared as f64 / total as f64 >= self.similarity {
+                let (root, other) = (self.root(id), self.root(other));
+                self.parents[root.max(other)] = root.min(other);
+            }
+        }
+        self.shingles.push(shingles);
+    }
+
+    /// Returns the cluster of each title, in the order they were added. The
+    /// clusters are numbered from 0 in the order of their first title.
+    pub fn finish(mut self) -> Vec<usize> {
+        let mut numbers = HashMap::new();
+        (0..self.parents.len())
+            .map(|id| {
+                let root = self.root(id);
+                let next = numbers.len();
+                *numbers.entry(root).or_insert(next)
+            })
+            .collect()
+    }
+
+    /// Returns the first title of the cluster of the given one.
+    fn root(&mut self, mut id: usize) -> usize {
+        while self.parents[id] != id {
+            self.parents[id] = self.parents[self.parents[id]];
+            id = self.parents[id];
+        }
+        id
+    }
+}
+
+/// Returns the shingles of the given title, once lowercased and with its
+/// punctuation and extra whitespace removed. Titles shorter than a shingle are
+/// their own.
+fn shingles(title: &str) -> HashSet<String> {
+    let normalized = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let chars: Vec<char> = normalized.chars().collect();
+    if chars.len() < 3 {
+        return HashSet::from([normalized]);
+    }
+    chars
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_the_distinct_urls_with_the_same_title() {
+        let mut groups = TitleGroups::default();
+        groups.add("Just a moment...", "https://a.example");
+        groups.add(" Just a moment... ", "https://b.example");
+        groups.add("Just a moment...", "https://a.example");
+        groups.add("A Title", "https://c.example");
+        groups.add("A Title", "https://c.example");
+        assert!(groups.is_duplicate("Just a moment..."));
+        assert!(!groups.is_duplicate("A Title"));
+        assert!(!groups.is_duplicate("Another Title"));
+        assert_eq!(
+            groups.urls["Just a moment..."],
+            ["https://a.example", "https://b.example"]
+        );
+        assert_eq!(groups.titles, ["Just a moment...", "A Title"]);
+    }
+
+    #[test]
+    fn clusters_titles_with_near_duplicate_text() {
+        let mut clusters = Clusters::new(0.8);
+        for title in [
+            "Rust 1.75 released with async fn in traits",
+            "Unrelated news about gardening",
+            "Rust 1.75 released with async fn in traits | Example News",
+            "rust 1.75 released, with async fn in traits!",
+            "Something else entirely",
+        ] {
+            clusters.add(title);
+        }
+        assert_eq!(clusters.finish(), [0, 1, 0, 0, 2]);
+    }
+
+    #[test]
+    fn merges_the_clusters_joined_by_a_title() {
+        let mut clusters = Clusters::new(0.5);
+        for title in ["aaaa bbbb", "cccc dddd", "aaaa bbbb cccc dddd"] {
+            clusters.add(title);
+        }
+        assert_eq!(clusters.finish(), [0, 0, 0]);
+    }
+
+    #[test]
+    fn takes_the_shingles_of_normalized_titles() {
+        assert_eq!(shingles("A-b"), HashSet::from(["a b".to_owned()]));
+        assert_eq!(shingles("Ab"), HashSet::from(["ab".to_owned()]));
+        assert_eq!(
+            shingles("Ab, CD"),
+            HashSet::from(["ab ".to_owned(), "b c".to_owned(), " cd".to_owned()])
+        );
+    }
+}
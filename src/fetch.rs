@@ -0,0 +1,632 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use clap::ValueEnum;
+use eyre::{bail, Report, Result, WrapErr};
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use reqwest::{
+    header::{COOKIE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    redirect, Client, ClientBuilder, Method, Proxy, RequestBuilder, Response, StatusCode,
+};
+use scraper::{element_ref::Text, Html, Selector};
+use tracing::{debug, info_span, Instrument};
+use url::Url;
+
+use crate::{
+    otlp::{self, TracedResolver},
+    overrides,
+    ssrf::{self, PublicResolver},
+    throttle,
+};
+
+/// The HTTP client shared by all requests, except those that go through the
+/// proxy of a rule of the configuration file.
+static CLIENT: Lazy<Client> = Lazy::new(|| build_client(None).unwrap());
+
+/// How long a request to the API of a service may take.
+const API_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The HTTP client of the requests to the APIs of services, such as those that
+/// the results are pushed to or the titles translated with. Unlike those of
+/// the pages, these requests aren't throttled, don't send the cookies and the
+/// headers of the rules, and may go to private addresses, as self-hosted
+/// services often are. Since the results are pushed one at a time, a request
+/// that hangs would stall the run, so they time out.
+static API_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .user_agent(concat!("titlelist/", env!("CARGO_PKG_VERSION")))
+        .timeout(API_TIMEOUT)
+        .build()
+        .unwrap()
+});
+
+/// Starts a request to the API of a service, through [`API_CLIENT`].
+pub fn api_request(method: Method, url: &str) -> RequestBuilder {
+    API_CLIENT.request(method, url)
+}
+
+/// Builds an HTTP client, going through the given proxy, if any.
+pub fn build_client(proxy: Option<&str>) -> Result<Client> {
+    let mut builder = ClientBuilder::new().user_agent("load title tags");
+    if let Some(proxy) = proxy {
+        if blocks_private() {
+            bail!("proxies can't be used with `--block-private`");
+        }
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+    let builder = if blocks_private() {
+        // Proxies would resolve the hosts themselves.
+        builder
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver))
+            .redirect(redirect::Policy::custom(|attempt| {
+                if let Err(error) = ssrf::check_url(attempt.url()) {
+                    attempt.error(error.to_string())
+                } else if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else {
+                    attempt.follow()
+                }
+            }))
+    } else if otlp::recorder().is_some() {
+        builder.dns_resolver(Arc::new(TracedResolver))
+    } else {
+        builder
+    };
+    Ok(builder.build()?)
+}
+
+/// Whether the requests to private addresses are refused.
+static BLOCK_PRIVATE: OnceCell<bool> = OnceCell::new();
+
+/// How many HTTP redirects are followed in a row, like the default policy of
+/// the client.
+const MAX_REDIRECTS: usize = 10;
+
+/// How many meta refresh and JavaScript redirects are followed in a row.
+const MAX_CLIENT_REDIRECTS: usize = 5;
+
+/// The longest meta refresh delay that is treated as a redirect, in seconds.
+/// Longer delays are usually pages that reload themselves.
+const MAX_REFRESH_DELAY: f64 = 10.0;
+
+/// Cookies that accept the cookie consent of the sites that otherwise serve a
+/// consent page instead of the requested one.
+const BUILTIN_CONSENT_COOKIES: &[(&str, &str)] = &[
+    ("google.com", "CONSENT=YES+; SOCS=CAI"),
+    ("youtube.com", "CONSENT=YES+; SOCS=CAI"),
+];
+
+/// Consent cookies given by the user, by domain.
+static CONSENT_COOKIES: OnceCell<Vec<(String, String)>> = OnceCell::new();
+
+/// Returns the HTTP client shared by all requests.
+pub fn client() -> &'static Client {
+    &CLIENT
+}
+
+/// Refuses the requests to loopback, private and link-local addresses, both
+/// those of the requested URLs and of their redirects. Must be called before
+/// any request is made, and may only be called once.
+pub fn block_private() {
+    let _ = BLOCK_PRIVATE.set(true);
+}
+
+/// Whether the requests to private addresses are refused.
+fn blocks_private() -> bool {
+    BLOCK_PRIVATE.get().copied().unwrap_or_default()
+}
+
+/// Sets the consent cookies sent to the given domains (and their subdomains),
+/// in addition to the built-in ones. May only be called once.
+pub fn set_consent_cookies(cookies: Vec<(String, String)>) {
+    let _ = CONSENT_COOKIES.set(cookies);
+}
+
+/// Builds a request to the given URL, with the consent cookie of its domain,
+/// if any, and the overrides of the rules that match it, once the delay
+/// between the requests to its host, if any, passed.
+pub async fn request(method: Method, url: &str) -> Result<RequestBuilder> {
+    throttle::wait(url).await;
+    debug!("requesting `{url}` with {method}");
+    let client = overrides::client(url).unwrap_or(&CLIENT);
+    let request = client.request(method, url);
+    let parsed = Url::parse(url).ok();
+    if let Some(parsed) = parsed.as_ref().filter(|_| blocks_private()) {
+        ssrf::check_url(parsed)?;
+    }
+    // The headers of the overrides replace the consent cookie, if they have
+    // a cookie.
+    let headers = overrides::headers(url);
+    let Some(host) = parsed.and_then(|url| url.host_str().map(str::to_owned)) else {
+        return Ok(request.headers(headers));
+    };
+    let matches = |domain: &str| {
+        host == domain
+            || host
+                .strip_suffix(domain)
+                .is_some_and(|sub| sub.ends_with('.'))
+    };
+    let user = CONSENT_COOKIES.get().into_iter().flatten();
+    let cookie = user
+        .map(|(domain, cookie)| (domain.as_str(), cookie.as_str()))
+        .chain(BUILTIN_CONSENT_COOKIES.iter().copied())
+        .find(|(domain, _)| matches(domain));
+    let request = match cookie {
+        Some((_, cookie)) => request.header(COOKIE, cookie),
+        None => request,
+    };
+    Ok(request.headers(headers))
+}
+
+/// Fetches the content of the given URL and retrieves its page title, if it
+/// is present. If there is no title, `None` is returned. Fails if the page was
+/// answered with an error status, rather than giving the title of the error
+/// page.
+pub async fn load_url_and_get_title(url: &str) -> Result<Option<String>> {
+    let page = load_page(url).await?;
+    check_status(page.status).wrap_err("failed to load the page")?;
+    Ok(page.title)
+}
+
+/// A fetched page.
+pub struct Page {
+    /// The final URL of the page, after redirects.
+    pub url: String,
+    /// The canonical URL that the requested one was unwrapped to, if any.
+    pub unwrapped_url: Option<String>,
+    pub status: StatusCode,
+    pub title: Option<String>,
+    pub html: String,
+    pub validators: Validators,
+    /// The fields given to the page by the API of its site, if it was
+    /// resolved through it.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Fetches the given URL, keeping the whole page so that it can be analyzed.
+pub async fn load_page(url: &str) -> Result<Page> {
+    match load_page_if_modified(url, &Validators::default()).await? {
+        Revalidated::Modified(page) => Ok(page),
+        Revalidated::NotModified => bail!("unexpected `304 Not Modified` for `{url}`"),
+    }
+}
+
+/// The validators of a response, which allow revalidating it later with a
+/// conditional request.
+#[derive(Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// The result of [`load_page_if_modified`].
+pub enum Revalidated {
+    /// The page didn't change since the validators were obtained.
+    NotModified,
+    /// The page was fetched again.
+    Modified(Page),
+}
+
+/// Fetches the given URL with a conditional request using the given
+/// validators, unless the server reports that the page wasn't modified.
+///
+/// Besides HTTP redirects, meta refresh and trivial JavaScript redirects are
+/// followed too, up to [`MAX_CLIENT_REDIRECTS`] of them. The validators are
+/// those of the given URL, so that it can be revalidated later.
+pub async fn load_page_if_modified(url: &str, validators: &Validators) -> Result<Revalidated> {
+    let Revalidated::Modified(mut page) = fetch_page(url, validators).await? else {
+        return Ok(Revalidated::NotModified);
+    };
+    for _ in 0..MAX_CLIENT_REDIRECTS {
+        let Some(target) = client_redirect(&page) else {
+            break;
+        };
+        debug!("`{}` redirects to `{target}` on the client side", page.url);
+        match fetch_page(target.as_str(), &Validators::default()).await? {
+            Revalidated::Modified(next) => {
+                page = Page {
+                    validators: page.validators,
+                    ..next
+                }
+            }
+            Revalidated::NotModified => bail!("unexpected `304 Not Modified` for `{target}`"),
+        }
+    }
+    Ok(Revalidated::Modified(page))
+}
+
+/// Fetches the given URL with a conditional request, following only HTTP
+/// redirects.
+async fn fetch_page(url: &str, validators: &Validators) -> Result<Revalidated> {
+    let mut request = request(Method::GET, url).await?;
+    if let Some(etag) = &validators.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request
+        .send()
+        .instrument(info_span!("request", url))
+        .await
+        .wrap_err_with(|| format!("failed to get: `{url}`"))?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Revalidated::NotModified);
+    }
+
+    let header = |name| {
+        let value = response.headers().get(name)?.to_str().ok()?;
+        Some(value.to_owned())
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    let status = response.status();
+    let final_url = response.url().to_string();
+    if final_url != url {
+        debug!("`{url}` redirected to `{final_url}`");
+    }
+    let html = response.text().instrument(info_span!("body")).await?;
+    let title = parse_html_and_get_title(&html)
+        .instrument(info_span!("parse"))
+        .await?;
+    Ok(Revalidated::Modified(Page {
+        url: final_url,
+        unwrapped_url: None,
+        status,
+        title,
+        html,
+        validators,
+        fields: Vec::new(),
+    }))
+}
+
+/// Returns the URL that the given page redirects to on the client side, if
+/// any: either with a `<meta http-equiv="refresh">` tag of a short delay or,
+/// for pages without a title, with a script assigning `location`. Redirects
+/// to the page itself are ignored.
+fn client_redirect(page: &Page) -> Option<Url> {
+    static REFRESH: Lazy<Selector> = Lazy::new(|| Selector::parse("meta[http-equiv]").unwrap());
+    static SCRIPT: Lazy<Selector> = Lazy::new(|| Selector::parse("script:not([src])").unwrap());
+    static LOCATION: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(concat!(
+            r"(?:^|[^A-Za-z0-9_$.]|(?:window|document|self|top)\.)location",
+            r"(?:\.href[ \t\r\n]*=|[ \t\r\n]*=|\.replace[ \t\r\n]*\(|\.assign[ \t\r\n]*\()",
+            r#"[ \t\r\n]*(?:"([^"]*)"|'([^']*)')"#,
+        ))
+        .unwrap()
+    });
+
+    let base = Url::parse(&page.url).ok()?;
+    let document = Html::parse_document(&page.html);
+    let refresh = document
+        .select(&REFRESH)
+        .filter(|el| {
+            let equiv = el.value().attr("http-equiv").unwrap_or_default();
+            equiv.trim().eq_ignore_ascii_case("refresh")
+        })
+        .find_map(|el| {
+            let (delay, target) = parse_refresh(el.value().attr("content")?)?;
+            (delay <= MAX_REFRESH_DELAY).then(|| target.to_owned())
+        });
+    let script = || {
+        if page.title.is_some() {
+            return None;
+        }
+        document.select(&SCRIPT).find_map(|el| {
+            let script = el.text().collect::<String>();
+            let caps = LOCATION.captures(&script)?;
+            let target = caps.get(1).or_else(|| caps.get(2))?.as_str();
+            Some(target.replace("\\/", "/"))
+        })
+    };
+
+    let target = base.join(refresh.or_else(script)?.trim()).ok()?;
+    let followed = matches!(target.scheme(), "http" | "https") && target != base;
+    followed.then_some(target)
+}
+
+/// Parses the content of a meta refresh tag, such as `0; url=/next`, into its
+/// delay and its URL. Tags without a URL only reload the page, so they are
+/// left out.
+fn parse_refresh(content: &str) -> Option<(f64, &str)> {
+    let (delay, rest) = content.split_once([';', ','])?;
+    let delay = delay.trim().parse().ok()?;
+    let rest = rest.trim_start();
+    let target = match rest.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url=") => &rest[4..],
+        _ => rest,
+    };
+    let target = target.trim().trim_matches(['"', '\'']);
+    (!target.is_empty()).then_some((delay, target))
+}
+
+/// Checks whether the given URL is reachable, without downloading its body
+/// when possible: a `HEAD` request is sent first, falling back to `GET` if it
+/// fails or is rejected, since some servers don't handle `HEAD` properly.
+/// Redirects are followed, so the returned response has the final URL.
+pub async fn probe(url: &str) -> Result<Response> {
+    match request(Method::HEAD, url).await?.send().await {
+        Ok(response)
+            if !response.status().is_client_error() && !response.status().is_server_error() =>
+        {
+            Ok(response)
+        }
+        _ => get(url).await,
+    }
+}
+
+/// Fetches the given URL, returning the full page HTML as a string.
+pub async fn load_html(url: &str) -> Result<String> {
+    get(url).await?.text().await.map_err(Into::into)
+}
+
+/// Fetches the given URL, returning the raw response body.
+pub async fn load_bytes(url: &str) -> Result<Vec<u8>> {
+    let bytes = get(url).await?.bytes().await?;
+    Ok(bytes.into())
+}
+
+/// Sends a `GET` request to the given URL.
+pub async fn get(url: &str) -> Result<Response> {
+    request(Method::GET, url)
+        .await?
+        .send()
+        .await
+        .wrap_err_with(|| format!("failed to get: `{url}`"))
+}
+
+/// Parses the given HTML string and retrieves the text of the `title` tag,
+/// if it is present.
+pub async fn parse_html_and_get_title(html: &str) -> Result<Option<String>> {
+    static SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+
+    /// Produces a string by iterating over all text nodes. A space character is
+    /// inserted between two text nodes.
+    fn join_text(text: Text<'_>) -> String {
+        let mut s = String::new();
+        for text_node in text {
+            s.push_str(text_node.trim());
+            s.push(' ');
+        }
+        s.pop();
+        s
+    }
+
+    let fragment = Html::parse_fragment(html);
+
+    let mut elements = fragment.select(&SELECTOR);
+    let fst = elements
+        .next() // Only get the first title tag.
+        .map(|el| join_text(el.text())) // Get full text from html text node.
+        .filter(|title| !title.is_empty()); // Map empty strings to none.
+
+    Ok(fst)
+}
+
+/// The error of a page that was answered with an error status, which the
+/// pages are otherwise read regardless of.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: StatusCode,
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "answered `{}`", self.status)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Fails with a [`StatusError`] if the given status of a page is an error
+/// one.
+pub fn check_status(status: StatusCode) -> Result<()> {
+    if status.is_client_error() || status.is_server_error() {
+        return Err(StatusError { status }.into());
+    }
+    Ok(())
+}
+
+/// Returns the status code of the response that the given error is about, if
+/// any.
+pub fn error_status(error: &Report) -> Option<u16> {
+    error.chain().find_map(|cause| {
+        let status = match cause.downcast_ref::<StatusError>() {
+            Some(error) => Some(error.status),
+            None => cause.downcast_ref::<reqwest::Error>()?.status(),
+        };
+        status.map(|status| status.as_u16())
+    })
+}
+
+/// The kinds of failures of fetches.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FailureKind {
+    /// The host name couldn't be resolved.
+    Dns,
+    /// The TLS handshake failed, such as with an invalid certificate.
+    Tls,
+    Timeout,
+    /// The connection couldn't be established for another reason.
+    Connect,
+    /// The server answered with an error status.
+    HttpStatus,
+    /// The URL is invalid.
+    Parse,
+    Other,
+}
+
+impl FailureKind {
+    /// Classifies the given error by its causes.
+    pub fn of(error: &Report) -> Self {
+        if error.chain().any(|cause| cause.is::<url::ParseError>()) {
+            return FailureKind::Parse;
+        }
+        if error
+            .chain()
+            .any(|cause| cause.is::<tokio::time::error::Elapsed>())
+        {
+            return FailureKind::Timeout;
+        }
+        if error.chain().any(|cause| cause.is::<StatusError>()) {
+            return FailureKind::HttpStatus;
+        }
+        let Some(error) = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        else {
+            return FailureKind::Other;
+        };
+        // The underlying errors of reqwest only tell their kind by their text.
+        let mut source = std::error::Error::source(error);
+        let mut causes = String::new();
+        while let Some(cause) = source {
+            causes.push_str(&cause.to_string().to_lowercase());
+            causes.push('\n');
+            source = cause.source();
+        }
+        if error.is_timeout() {
+            FailureKind::Timeout
+        } else if error.is_builder() {
+            FailureKind::Parse
+        } else if error.is_status() {
+            FailureKind::HttpStatus
+        } else if causes.contains("dns error") || causes.contains("failed to lookup address") {
+            FailureKind::Dns
+        } else if ["certificate", "tls", "ssl", "handshake"]
+            .iter()
+            .any(|word| causes.contains(word))
+        {
+            FailureKind::Tls
+        } else if error.is_connect() {
+            FailureKind::Connect
+        } else {
+            FailureKind::Other
+        }
+    }
+
+    /// Returns the name of the kind, as shown in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            FailureKind::Dns => "dns",
+            FailureKind::Tls => "tls",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Connect => "connect",
+            FailureKind::HttpStatus => "http-status",
+            FailureKind::Parse => "parse",
+            FailureKind::Other => "other",
+        }
+    }
+}
+
+/// The classes of failures that may be retried.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RetryClass {
+    /// The host name couldn't be resolved.
+    Dns,
+    /// The TLS handshake failed, such as with an invalid certificate.
+    Tls,
+    Timeout,
+    /// The connection couldn't be established for another reason.
+    Connect,
+    /// The server answered with `429 Too Many Requests`.
+    #[value(name = "429")]
+    TooManyRequests,
+    /// The server answered with another client error status.
+    #[value(name = "4xx")]
+    ClientError,
+    /// The server answered with a server error status.
+    #[value(name = "5xx")]
+    ServerError,
+    /// Any other failure, such as an invalid URL.
+    Other,
+}
+
+impl RetryClass {
+    /// The classes that are retried by default, since their failures are
+    /// usually temporary.
+    pub const TRANSIENT: [RetryClass; 4] = [
+        RetryClass::Timeout,
+        RetryClass::Connect,
+        RetryClass::TooManyRequests,
+        RetryClass::ServerError,
+    ];
+
+    /// Classifies the given error.
+    pub fn of(error: &Report) -> Self {
+        match FailureKind::of(error) {
+            FailureKind::Dns => RetryClass::Dns,
+            FailureKind::Tls => RetryClass::Tls,
+            FailureKind::Timeout => RetryClass::Timeout,
+            FailureKind::Connect => RetryClass::Connect,
+            FailureKind::HttpStatus => match error_status(error) {
+                Some(429) => RetryClass::TooManyRequests,
+                Some(500..) => RetryClass::ServerError,
+                Some(_) => RetryClass::ClientError,
+                None => RetryClass::Other,
+            },
+            FailureKind::Parse | FailureKind::Other => RetryClass::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error of a page answered with the given status.
+    fn status_error(status: u16) -> Report {
+        let status = StatusCode::from_u16(status).unwrap();
+        Report::new(StatusError { status }).wrap_err("failed to load the page")
+    }
+
+    #[test]
+    fn classifies_error_statuses() {
+        assert_eq!(error_status(&status_error(503)), Some(503));
+        assert!(FailureKind::of(&status_error(404)) == FailureKind::HttpStatus);
+        assert_eq!(FailureKind::of(&status_error(404)).name(), "http-status");
+    }
+
+    #[test]
+    fn fails_on_error_statuses() {
+        let check = |status| check_status(StatusCode::from_u16(status).unwrap());
+        assert!(check(200).is_ok());
+        assert!(check(304).is_ok());
+        assert_eq!(error_status(&check(404).unwrap_err()), Some(404));
+        assert!(FailureKind::of(&check(503).unwrap_err()) == FailureKind::HttpStatus);
+    }
+
+    #[test]
+    fn retries_transient_statuses() {
+        let class = |status| RetryClass::of(&status_error(status));
+        assert!(class(429) == RetryClass::TooManyRequests);
+        assert!(class(500) == RetryClass::ServerError);
+        assert!(class(503) == RetryClass::ServerError);
+        assert!(class(404) == RetryClass::ClientError);
+        assert!(RetryClass::TRANSIENT.contains(&class(502)));
+        assert!(!RetryClass::TRANSIENT.contains(&class(410)));
+    }
+
+    #[test]
+    fn leaves_other_errors_without_a_status() {
+        let error = Report::new(url::ParseError::EmptyHost);
+        assert_eq!(error_status(&error), None);
+        assert!(FailureKind::of(&error) == FailureKind::Parse);
+        assert!(RetryClass::of(&error) == RetryClass::Other);
+    }
+
+    #[test]
+    fn parses_refresh_tags() {
+        assert_eq!(parse_refresh("0; url=/next"), Some((0.0, "/next")));
+        assert_eq!(
+            parse_refresh("5;URL='https://example.com/'"),
+            Some((5.0, "https://example.com/"))
+        );
+        assert_eq!(parse_refresh("3, /other"), Some((3.0, "/other")));
+        assert_eq!(parse_refresh("30"), None);
+        assert_eq!(parse_refresh("0; url="), None);
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::markdown::BareUrl;
+
+/// Scans the given HTML document for the `http` and `https` anchors whose text
+/// is empty or just their URL, returning their URLs along with the span of
+/// their contents. Anchors with nested markup, such as images, are left out,
+/// as are those inside comments, scripts and styles.
+///
+/// The document is scanned as text, rather than parsed, since the parser
+/// doesn't keep the positions of the elements.
+pub fn unnamed_anchor_spans(contents: &str) -> Vec<BareUrl> {
+    static EXCLUDED: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(concat!(
+            r"<!--(?s:.)*?-->",
+            r"|<[sS][cC][rR][iI][pP][tT][ \t\r\n>](?s:.)*?</[sS][cC][rR][iI][pP][tT][ \t\r\n]*>",
+            r"|<[sS][tT][yY][lL][eE][ \t\r\n>](?s:.)*?</[sS][tT][yY][lL][eE][ \t\r\n]*>",
+        ))
+        .unwrap()
+    });
+    static ANCHOR: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"<[aA][ \t\r\n]([^>]*)>([^<]*)</[aA][ \t\r\n]*>").unwrap());
+    static HREF: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(concat!(
+            r"(?:^|[ \t\r\n])[hH][rR][eE][fF][ \t\r\n]*=[ \t\r\n]*",
+            r#"(?:"([^"]*)"|'([^']*)'|([^ \t\r\n"'>]+))"#,
+        ))
+        .unwrap()
+    });
+
+    let excluded: Vec<Range<usize>> = EXCLUDED.find_iter(contents).map(|m| m.range()).collect();
+    let mut anchors = Vec::new();
+    for caps in ANCHOR.captures_iter(contents) {
+        let whole = caps.get(0).unwrap().range();
+        if excluded
+            .iter()
+            .any(|ex| ex.start < whole.end && whole.start < ex.end)
+        {
+            continue;
+        }
+        let Some(href) = HREF.captures(&caps[1]) else {
+            continue;
+        };
+        let href = href
+            .get(1)
+            .or_else(|| href.get(2))
+            .or_else(|| href.get(3))
+            .map_or("", |m| m.as_str());
+        let url = unescape_html(href.trim());
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            continue;
+        }
+
+        let text = caps.get(2).unwrap();
+        let trimmed = unescape_html(text.as_str().trim());
+        if trimmed.is_empty() || trimmed == url {
+            anchors.push(BareUrl {
+                url,
+                span: text.range(),
+            });
+        }
+    }
+    anchors
+}
+
+/// Unescapes the most common HTML character references.
+fn unescape_html(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the URLs of the unnamed anchors of the given document, along
+    /// with their contents.
+    fn anchors(contents: &str) -> Vec<(String, &str)> {
+        unnamed_anchor_spans(contents)
+            .into_iter()
+            .map(|anchor| (anchor.url, &contents[anchor.span]))
+            .collect()
+    }
+
+    #[test]
+    fn finds_empty_anchors_and_those_named_by_their_url() {
+        let contents = r#"<p><a href="https://a.example/"></a>
+<A class="x" HREF='https://b.example/?a=1&amp;b=2'> https://b.example/?a=1&amp;b=2 </A>
+<a href=https://c.example/>A name</a>
+<a href="/relative"></a>
+<a href="https://d.example/"><img src="i.png"></a></p>"#;
+        assert_eq!(
+            anchors(contents),
+            [
+                ("https://a.example/".to_owned(), ""),
+                (
+                    "https://b.example/?a=1&b=2".to_owned(),
+                    " https://b.example/?a=1&amp;b=2 "
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_anchors_in_comments_scripts_and_styles() {
+        let contents = r#"<!-- <a href="https://a.example/"></a> -->
+<script>document.write('<a href="https://b.example/"></a>')</script>
+<style>/* <a href="https://c.example/"></a> */</style>
+<a href="https://d.example/"></a>"#;
+        assert_eq!(anchors(contents), [("https://d.example/".to_owned(), "")]);
+    }
+}
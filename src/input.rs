@@ -0,0 +1,896 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io::Read,
+    path::Path,
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use eyre::{bail, Result, WrapErr};
+use flate2::read::GzDecoder;
+use futures::{stream, Stream};
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Selector};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use url::Url;
+
+use crate::{browser, fetch::load_bytes, markdown};
+
+/// A single input entry: the URL to be fetched and the fields that
+/// accompanied it in the input, which are passed through to the output.
+pub struct Entry {
+    pub url: String,
+    pub fields: Vec<(String, String)>,
+}
+
+impl Entry {
+    /// Creates an entry with no passthrough fields.
+    pub fn new(url: impl Into<String>) -> Self {
+        Entry {
+            url: url.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the given passthrough field, if present.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the folder names of the `folder` passthrough field.
+    pub fn folders(&self) -> Vec<&str> {
+        self.field("folder")
+            .map(|f| {
+                f.split(FOLDER_SEPARATOR)
+                    .filter(|f| !f.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// One URL per line.
+    Lines,
+    /// CSV with a header row. The URL is taken from the `--url-column` column
+    /// and the remaining columns are passed through to the output.
+    Csv,
+    /// A Markdown document. Bare URLs and autolinks are extracted, while
+    /// links that already have a text are left out.
+    Markdown,
+    /// An HTML document. The targets of its `<a href>` links are extracted.
+    Html,
+    /// A Netscape bookmarks file, as exported by browsers. The folder of each
+    /// bookmark is passed through as the `folder` field, as well as its
+    /// `add_date`, `last_modified` and `tags` attributes, which are empty
+    /// when absent.
+    Bookmarks,
+    /// An OPML outline, such as a feed list. The `htmlUrl` of each outline is
+    /// used as the URL or, if absent, its `xmlUrl`. The folder and the feed
+    /// URL are passed through as the `folder` and `xml_url` fields.
+    Opml,
+    /// An RSS or Atom feed. The link of each entry is extracted, and its title
+    /// and publication date are passed through as the `entry_title` and
+    /// `published` fields.
+    Feed,
+    /// A sitemap, a sitemap index or a gzipped sitemap. The `lastmod` of each
+    /// URL is passed through.
+    Sitemap,
+    /// A Pocket export, either HTML or CSV. The saved title, the time it was
+    /// added, the tags and the list (unread or archive) are passed through as
+    /// the `saved_title`, `add_date`, `tags` and `folder` fields.
+    Pocket,
+    /// An Instapaper export, either HTML or CSV. The saved title, the time it
+    /// was added and the folder are passed through as the `saved_title`,
+    /// `add_date` and `folder` fields, as well as the tags and the selection,
+    /// if present.
+    Instapaper,
+    /// The history of a Firefox profile (`places.sqlite`). Requires `--file`.
+    FirefoxHistory,
+    /// The bookmarks of a Firefox profile (`places.sqlite`). Requires
+    /// `--file`.
+    FirefoxBookmarks,
+    /// The history of a Chrome profile (`History`). Requires `--file`.
+    ChromeHistory,
+    /// The bookmarks of a Chrome profile (`Bookmarks`).
+    ChromeBookmarks,
+}
+
+impl InputFormat {
+    /// Whether the input is a database that must be read from `--file`
+    /// instead of being parsed as text.
+    pub fn is_database(self) -> bool {
+        matches!(
+            self,
+            InputFormat::FirefoxHistory
+                | InputFormat::FirefoxBookmarks
+                | InputFormat::ChromeHistory
+        )
+    }
+}
+
+/// Options that affect how the input is parsed.
+pub struct InputOptions<'a> {
+    pub format: InputFormat,
+    pub url_column: &'a str,
+    pub base_url: Option<&'a Url>,
+    pub same_origin: bool,
+    /// Only include browser history and bookmarks visited or added after this
+    /// time.
+    pub since: Option<SystemTime>,
+    /// Whether the `lines` input is separated by NUL bytes instead of new lines.
+    pub read0: bool,
+}
+
+/// Parses the input contents into a list of entries, according to the given
+/// options.
+pub fn parse(contents: &str, opts: &InputOptions<'_>) -> Result<Vec<Entry>> {
+    match opts.format {
+        InputFormat::Lines if opts.read0 => {
+            Ok(non_empty_records(contents, '\0').map(Entry::new).collect())
+        }
+        InputFormat::Lines => Ok(non_empty_lines(contents).map(Entry::new).collect()),
+        InputFormat::Csv => parse_csv(contents, opts.url_column),
+        InputFormat::Markdown => Ok(markdown::bare_urls(contents)
+            .into_iter()
+            .map(Entry::new)
+            .collect()),
+        InputFormat::Html => parse_html(contents, opts.base_url, opts.same_origin),
+        InputFormat::Bookmarks => Ok(parse_bookmarks(contents)),
+        InputFormat::Opml => parse_opml(contents),
+        InputFormat::Pocket => parse_pocket(contents),
+        InputFormat::Instapaper => parse_instapaper(contents),
+        InputFormat::Feed => parse_feed(contents, opts.base_url),
+        InputFormat::ChromeBookmarks => browser::chrome_bookmarks(contents, opts.since),
+        InputFormat::Sitemap => bail!("sitemaps must be read with `read_sitemap`"),
+        InputFormat::FirefoxHistory
+        | InputFormat::FirefoxBookmarks
+        | InputFormat::ChromeHistory => {
+            bail!("database input formats must be read with `read_database`")
+        }
+    }
+}
+
+/// Gives every entry the passthrough fields of all the others, in the order
+/// they were first seen, with an empty value where it lacks them, so that the
+/// results have the same fields, as the columns of the CSV output.
+pub fn align_fields(entries: &mut [Entry]) {
+    let mut names: Vec<String> = Vec::new();
+    for entry in entries.iter() {
+        for (name, _) in &entry.fields {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    for entry in entries {
+        let fields = names
+            .iter()
+            .map(|name| {
+                (
+                    name.clone(),
+                    entry.field(name).unwrap_or_default().to_owned(),
+                )
+            })
+            .collect();
+        entry.fields = fields;
+    }
+}
+
+/// Reads the entries from the database at the given path. The input format
+/// must be one for which [`InputFormat::is_database`] holds.
+pub fn read_database(path: &Path, opts: &InputOptions<'_>) -> Result<Vec<Entry>> {
+    match opts.format {
+        InputFormat::FirefoxHistory => browser::firefox_history(path, opts.since),
+        InputFormat::FirefoxBookmarks => browser::firefox_bookmarks(path, opts.since),
+        InputFormat::ChromeHistory => browser::chrome_history(path, opts.since),
+        _ => bail!("not a database input format"),
+    }
+}
+
+/// Returns a stream of the entries of a line-based input, separated by the
+/// given byte. Each entry is yielded as soon as its line is read, so that the
+/// input may be an endless pipe.
+pub fn stream_lines(
+    reader: impl AsyncBufRead + Unpin + Send + 'static,
+    sep: u8,
+) -> impl Stream<Item = Result<Entry>> + Send {
+    stream::try_unfold(reader, move |mut reader| async move {
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            if reader.read_until(sep, &mut buf).await? == 0 {
+                return Ok(None);
+            }
+            let line = std::str::from_utf8(&buf).wrap_err("input is not valid UTF-8")?;
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok(Some((Entry::new(line), reader)));
+            }
+        }
+    })
+}
+
+/// Returns an iterator over the non-empty lines of the provided string slice.
+fn non_empty_lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+}
+
+/// Separator between the folder names in the `folder` field of bookmarks.
+pub const FOLDER_SEPARATOR: &str = " / ";
+
+/// Returns an iterator over the non-empty records of the provided string slice,
+/// separated by the given character.
+fn non_empty_records(contents: &str, sep: char) -> impl Iterator<Item = &str> {
+    contents
+        .split(sep)
+        .map(|record| record.trim())
+        .filter(|record| !record.is_empty())
+}
+
+/// Parses a Netscape bookmarks file. Folders are `<h3>` headings followed by
+/// a `<dl>` list of bookmarks, both inside a `<dt>` item of the parent list.
+fn parse_bookmarks(contents: &str) -> Vec<Entry> {
+    static ANCHOR: Lazy<Selector> = Lazy::new(|| Selector::parse("dt > a[href]").unwrap());
+
+    let document = Html::parse_document(contents);
+
+    document
+        .select(&ANCHOR)
+        .filter_map(|el| {
+            let url = el.value().attr("href")?.trim();
+            if url.is_empty() {
+                return None;
+            }
+            let mut folders: Vec<_> = el
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .filter(|el| el.value().name() == "dt")
+                .filter_map(|dt| {
+                    dt.children()
+                        .filter_map(ElementRef::wrap)
+                        .find(|el| el.value().name() == "h3")
+                })
+                .map(|h3| h3.text().collect::<String>().trim().to_owned())
+                .collect();
+            folders.reverse();
+
+            let mut entry = Entry::new(url);
+            entry
+                .fields
+                .push(("folder".into(), folders.join(FOLDER_SEPARATOR)));
+            // The attributes are always passed through, so that every entry
+            // has the same fields.
+            for attr in ["add_date", "last_modified", "tags"] {
+                let value = el.value().attr(attr).unwrap_or_default();
+                entry.fields.push((attr.into(), value.to_owned()));
+            }
+            Some(entry)
+        })
+        .collect()
+}
+
+/// Parses an OPML document. Outlines without a URL of their own are treated
+/// as folders.
+fn parse_opml(contents: &str) -> Result<Vec<Entry>> {
+    fn walk(node: roxmltree::Node<'_, '_>, folders: &mut Vec<String>, entries: &mut Vec<Entry>) {
+        for outline in node.children().filter(|n| n.has_tag_name("outline")) {
+            let text = outline
+                .attribute("text")
+                .or_else(|| outline.attribute("title"))
+                .unwrap_or_default();
+            let xml_url = outline.attribute("xmlUrl").filter(|u| !u.is_empty());
+            let url = ["htmlUrl", "url"]
+                .into_iter()
+                .find_map(|attr| outline.attribute(attr))
+                .filter(|u| !u.is_empty())
+                .or(xml_url);
+            match url {
+                Some(url) => {
+                    let mut entry = Entry::new(url.trim());
+                    entry
+                        .fields
+                        .push(("folder".into(), folders.join(FOLDER_SEPARATOR)));
+                    if let Some(xml_url) = xml_url {
+                        entry.fields.push(("xml_url".into(), xml_url.to_owned()));
+                    }
+                    entries.push(entry);
+                }
+                None => {
+                    folders.push(text.to_owned());
+                    walk(outline, folders, entries);
+                    folders.pop();
+                }
+            }
+        }
+    }
+
+    let document = roxmltree::Document::parse(contents).wrap_err("invalid OPML document")?;
+    let mut entries = Vec::new();
+    if let Some(body) = document.descendants().find(|n| n.has_tag_name("body")) {
+        walk(body, &mut Vec::new(), &mut entries);
+    }
+    Ok(entries)
+}
+
+/// Parses an RSS (0.9x, 1.0 or 2.0) or Atom feed. Relative entry links are
+/// resolved against the given base URL.
+fn parse_feed(contents: &str, base_url: Option<&Url>) -> Result<Vec<Entry>> {
+    /// Returns the link of the given RSS item or Atom entry.
+    fn entry_link<'a>(entry: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+        entry
+            .children()
+            .filter(|n| n.has_tag_name("link"))
+            .find_map(|link| match link.attribute("href") {
+                // Atom links. Only the alternate (default) relation is the
+                // entry page itself.
+                Some(href) => {
+                    matches!(link.attribute("rel"), None | Some("alternate")).then_some(href)
+                }
+                None => link.text(),
+            })
+            .map(str::trim)
+            .filter(|link| !link.is_empty())
+    }
+
+    let document = roxmltree::Document::parse(contents).wrap_err("invalid feed document")?;
+
+    let entries = document
+        .descendants()
+        .filter(|n| n.has_tag_name("item") || n.has_tag_name("entry"))
+        .filter_map(|item| {
+            let link = entry_link(item)?;
+            let url = match base_url {
+                Some(base) => base.join(link).ok()?.into(),
+                None => link.to_owned(),
+            };
+            let mut entry = Entry::new(url);
+            if let Some(title) = xml_child_text(item, &["title"]) {
+                entry.fields.push(("entry_title".into(), title.to_owned()));
+            }
+            if let Some(date) = xml_child_text(item, &["pubDate", "published", "updated", "date"]) {
+                entry.fields.push(("published".into(), date.to_owned()));
+            }
+            Some(entry)
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Reads a sitemap, given its raw (possibly gzipped) contents. The sitemaps
+/// referenced by sitemap indexes are fetched as well. Stops once `limit` URLs
+/// have been found. The `lastmod` of each URL is passed through.
+pub async fn read_sitemap(data: Vec<u8>, limit: Option<usize>) -> Result<Vec<Entry>> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let mut entries = Vec::new();
+    let mut pending = VecDeque::<String>::new();
+    let mut visited = HashSet::new();
+
+    let mut data = Some(data);
+    loop {
+        let data = match data.take() {
+            Some(data) => data,
+            None => match pending.pop_front() {
+                Some(url) => load_bytes(&url).await?,
+                None => break,
+            },
+        };
+        let text = decode_sitemap(data)?;
+        let document = roxmltree::Document::parse(&text).wrap_err("invalid sitemap document")?;
+
+        for node in document.root_element().children() {
+            let Some(loc) = xml_child_text(node, &["loc"]) else {
+                continue;
+            };
+            if node.has_tag_name("sitemap") {
+                if visited.insert(loc.to_owned()) {
+                    pending.push_back(loc.to_owned());
+                }
+            } else if node.has_tag_name("url") {
+                if entries.len() >= limit {
+                    return Ok(entries);
+                }
+                let mut entry = Entry::new(loc);
+                if let Some(lastmod) = xml_child_text(node, &["lastmod"]) {
+                    entry.fields.push(("lastmod".into(), lastmod.to_owned()));
+                }
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Decodes the contents of a sitemap, decompressing it first if gzipped.
+fn decode_sitemap(data: Vec<u8>) -> Result<String> {
+    let data = if data.starts_with(&[0x1f, 0x8b]) {
+        let mut out = Vec::new();
+        GzDecoder::new(&data[..])
+            .read_to_end(&mut out)
+            .wrap_err("invalid gzipped sitemap")?;
+        out
+    } else {
+        data
+    };
+    String::from_utf8(data).wrap_err("sitemap is not valid UTF-8")
+}
+
+/// Returns the trimmed text of the first child element of `node` with one of
+/// the given names.
+fn xml_child_text<'a>(node: roxmltree::Node<'a, '_>, names: &[&str]) -> Option<&'a str> {
+    node.children()
+        .find(|n| names.iter().any(|name| n.has_tag_name(*name)))
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// Parses a Pocket export. The HTML export is a list of links per section,
+/// whereas the CSV export has `title`, `url`, `time_added`, `tags` (separated by
+/// `|`) and `status` columns.
+fn parse_pocket(contents: &str) -> Result<Vec<Entry>> {
+    if is_html(contents) {
+        return Ok(parse_saved_html(contents));
+    }
+    let mut entries = parse_csv(contents, "url")?;
+    for entry in &mut entries {
+        rename_fields(
+            entry,
+            &[
+                ("title", "saved_title"),
+                ("time_added", "add_date"),
+                ("status", "folder"),
+            ],
+        );
+        if let Some((_, tags)) = entry.fields.iter_mut().find(|(k, _)| k == "tags") {
+            *tags = tags.replace('|', ",");
+        }
+    }
+    Ok(entries)
+}
+
+/// Parses an Instapaper export. The HTML export is a list of links per folder,
+/// whereas the CSV export has `URL`, `Title`, `Selection`, `Folder` and
+/// `Timestamp` columns (and `Tags`, in newer exports).
+fn parse_instapaper(contents: &str) -> Result<Vec<Entry>> {
+    if is_html(contents) {
+        return Ok(parse_saved_html(contents));
+    }
+    let mut entries = parse_csv(contents, "URL")?;
+    for entry in &mut entries {
+        rename_fields(
+            entry,
+            &[
+                ("Title", "saved_title"),
+                ("Selection", "selection"),
+                ("Folder", "folder"),
+                ("Timestamp", "add_date"),
+                ("Tags", "tags"),
+            ],
+        );
+    }
+    Ok(entries)
+}
+
+/// Parses the HTML export of a read-it-later service, in which each `<h1>`
+/// heading (the folder) is followed by a list of links. The `time_added` and
+/// `tags` attributes of the links are passed through, if present.
+fn parse_saved_html(contents: &str) -> Vec<Entry> {
+    let document = Html::parse_document(contents);
+
+    let mut folder = String::new();
+    let mut entries = Vec::new();
+    for el in document
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+    {
+        match el.value().name() {
+            "h1" => folder = el.text().collect::<String>().trim().to_owned(),
+            "a" => {
+                let Some(url) = el.value().attr("href").map(str::trim) else {
+                    continue;
+                };
+                let mut entry = Entry::new(url);
+                let title = el.text().collect::<String>();
+                entry
+                    .fields
+                    .push(("saved_title".into(), title.trim().to_owned()));
+                entry.fields.push(("folder".into(), folder.clone()));
+                for (attr, field) in [("time_added", "add_date"), ("tags", "tags")] {
+                    let value = el.value().attr(attr).unwrap_or_default();
+                    entry.fields.push((field.into(), value.to_owned()));
+                }
+                entries.push(entry);
+            }
+            _ => (),
+        }
+    }
+    entries
+}
+
+/// Whether the given contents look like an HTML document rather than CSV.
+fn is_html(contents: &str) -> bool {
+    contents.trim_start().starts_with('<')
+}
+
+/// Renames the passthrough fields of the given entry, according to the given
+/// `(from, to)` pairs.
+fn rename_fields(entry: &mut Entry, renames: &[(&str, &str)]) {
+    for (key, _) in &mut entry.fields {
+        if let Some((_, to)) = renames.iter().find(|(from, _)| key == from) {
+            *key = (*to).to_owned();
+        }
+    }
+}
+
+/// Parses a CSV document with a header row. The URL column is selected either
+/// by its header name or by its 1-based position. Rows with an empty URL are
+/// skipped.
+fn parse_csv(contents: &str, url_column: &str) -> Result<Vec<Entry>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let headers = reader
+        .headers()
+        .wrap_err("failed to read CSV header")?
+        .clone();
+
+    let url_index = match headers.iter().position(|h| h.trim() == url_column) {
+        Some(i) => i,
+        None => match url_column.parse::<usize>() {
+            Ok(n) if (1..=headers.len()).contains(&n) => n - 1,
+            _ => bail!("CSV input has no `{url_column}` column"),
+        },
+    };
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.wrap_err("failed to read CSV record")?;
+        let url = record.get(url_index).unwrap_or_default().trim();
+        if url.is_empty() {
+            continue;
+        }
+        let fields = headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .filter(|(i, _)| *i != url_index)
+            .map(|(_, (h, v))| (h.to_owned(), v.to_owned()))
+            .collect();
+        entries.push(Entry {
+            url: url.to_owned(),
+            fields,
+        });
+    }
+    Ok(entries)
+}
+
+/// Extracts the `href` targets of the anchors in the given HTML document.
+/// Relative links are resolved against the `<base>` tag of the document or,
+/// if there is none, against the given base URL. Only `http` and `https` links
+/// are kept.
+pub fn parse_html(contents: &str, base_url: Option<&Url>, same_origin: bool) -> Result<Vec<Entry>> {
+    static BASE: Lazy<Selector> = Lazy::new(|| Selector::parse("base[href]").unwrap());
+    static ANCHOR: Lazy<Selector> = Lazy::new(|| Selector::parse("a[href]").unwrap());
+
+    let document = Html::parse_document(contents);
+
+    let document_base = document
+        .select(&BASE)
+        .next()
+        .and_then(|el| el.value().attr("href"))
+        .and_then(|href| match base_url {
+            Some(base) => base.join(href).ok(),
+            None => Url::parse(href).ok(),
+        });
+    let base = document_base.as_ref().or(base_url);
+
+    if same_origin && base.is_none() {
+        bail!("`--same-origin` requires a base URL (see `--base-url`)");
+    }
+
+    let entries = document
+        .select(&ANCHOR)
+        .filter_map(|el| el.value().attr("href"))
+        .map(str::trim)
+        .filter(|href| !href.is_empty() && !href.starts_with('#'))
+        .filter_map(|href| match base {
+            Some(base) => base.join(href).ok(),
+            None => Url::parse(href).ok(),
+        })
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|url| !same_origin || base.map(Url::origin) == Some(url.origin()))
+        .map(Entry::new)
+        .collect();
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the passthrough fields of the given entry.
+    fn fields(entry: &Entry) -> Vec<(&str, &str)> {
+        entry
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn aligns_fields() {
+        let mut entries = vec![
+            Entry {
+                url: "https://a.example/".into(),
+                fields: vec![("add_date".into(), "1".into())],
+            },
+            Entry {
+                url: "https://b.example/".into(),
+                fields: vec![("tags".into(), "x".into()), ("add_date".into(), "2".into())],
+            },
+            Entry::new("https://c.example/"),
+        ];
+        align_fields(&mut entries);
+        assert_eq!(fields(&entries[0]), [("add_date", "1"), ("tags", "")]);
+        assert_eq!(fields(&entries[1]), [("add_date", "2"), ("tags", "x")]);
+        assert_eq!(fields(&entries[2]), [("add_date", ""), ("tags", "")]);
+    }
+
+    #[test]
+    fn parses_bookmarks_with_every_field() {
+        let contents = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><H3>Reading</H3>
+    <DL><p>
+        <DT><A HREF="https://a.example/" ADD_DATE="1700000000" TAGS="rust,web">A</A>
+        <DT><A HREF="https://b.example/">B</A>
+    </DL><p>
+    <DT><A HREF="https://c.example/" LAST_MODIFIED="1700000001">C</A>
+</DL><p>"#;
+        let entries = parse_bookmarks(contents);
+        let urls: Vec<_> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://a.example/",
+                "https://b.example/",
+                "https://c.example/"
+            ]
+        );
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("folder", "Reading"),
+                ("add_date", "1700000000"),
+                ("last_modified", ""),
+                ("tags", "rust,web")
+            ]
+        );
+        assert_eq!(
+            fields(&entries[1]),
+            [
+                ("folder", "Reading"),
+                ("add_date", ""),
+                ("last_modified", ""),
+                ("tags", "")
+            ]
+        );
+        assert_eq!(
+            fields(&entries[2]),
+            [
+                ("folder", ""),
+                ("add_date", ""),
+                ("last_modified", "1700000001"),
+                ("tags", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_saved_html_with_every_field() {
+        let contents = r#"<html><body>
+<h1>Unread</h1>
+<ul>
+<li><a href="https://a.example/" time_added="1700000000" tags="news">A story</a></li>
+<li><a href="https://b.example/">B</a></li>
+</ul>
+<h1>Read Archive</h1>
+<ul><li><a href="https://c.example/" time_added="1600000000">C</a></li></ul>
+</body></html>"#;
+        let entries = parse_saved_html(contents);
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("saved_title", "A story"),
+                ("folder", "Unread"),
+                ("add_date", "1700000000"),
+                ("tags", "news")
+            ]
+        );
+        assert_eq!(
+            fields(&entries[1]),
+            [
+                ("saved_title", "B"),
+                ("folder", "Unread"),
+                ("add_date", ""),
+                ("tags", "")
+            ]
+        );
+        assert_eq!(
+            fields(&entries[2]),
+            [
+                ("saved_title", "C"),
+                ("folder", "Read Archive"),
+                ("add_date", "1600000000"),
+                ("tags", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_csv_by_column_name_or_number() {
+        let contents =
+            "title,url,tags\nA,https://a.example/,x\n\"B, quoted\",https://b.example/\n,,\n";
+        let entries = parse_csv(contents, "url").unwrap();
+        let urls: Vec<_> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(urls, ["https://a.example/", "https://b.example/"]);
+        assert_eq!(fields(&entries[0]), [("title", "A"), ("tags", "x")]);
+        assert_eq!(fields(&entries[1]), [("title", "B, quoted")]);
+
+        let entries = parse_csv(contents, "2").unwrap();
+        assert_eq!(entries[0].url, "https://a.example/");
+    }
+
+    #[test]
+    fn rejects_csv_without_the_url_column() {
+        let result = parse_csv("title,link\nA,https://a.example/\n", "url");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "CSV input has no `url` column");
+        assert!(parse_csv("title,link\n", "3").is_err());
+    }
+
+    #[test]
+    fn parses_opml_outlines_with_their_folders() {
+        let contents = r#"<?xml version="1.0"?>
+<opml version="2.0">
+  <head><title>Feeds</title></head>
+  <body>
+    <outline text="Tech">
+      <outline text="A" htmlUrl="https://a.example/" xmlUrl="https://a.example/feed"/>
+      <outline text="B" xmlUrl="https://b.example/feed"/>
+    </outline>
+    <outline text="C" url="https://c.example/"/>
+    <outline text="Empty" htmlUrl=""/>
+  </body>
+</opml>"#;
+        let entries = parse_opml(contents).unwrap();
+        let urls: Vec<_> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://a.example/",
+                "https://b.example/feed",
+                "https://c.example/"
+            ]
+        );
+        assert_eq!(
+            fields(&entries[0]),
+            [("folder", "Tech"), ("xml_url", "https://a.example/feed")]
+        );
+        assert_eq!(fields(&entries[2]), [("folder", "")]);
+        assert!(parse_opml("<opml>").is_err());
+    }
+
+    #[test]
+    fn parses_rss_feeds() {
+        let contents = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Blog</title>
+  <link>https://blog.example/</link>
+  <item>
+    <title>First</title>
+    <link> https://blog.example/first </link>
+    <pubDate>Mon, 01 Jan 2024 00:00:00 GMT</pubDate>
+  </item>
+  <item><title>No link</title></item>
+</channel></rss>"#;
+        let entries = parse_feed(contents, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].url, "https://blog.example/first");
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("entry_title", "First"),
+                ("published", "Mon, 01 Jan 2024 00:00:00 GMT")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_atom_feeds_with_relative_links() {
+        let contents = r#"<?xml version="1.0"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Blog</title>
+  <entry>
+    <title>Post</title>
+    <link rel="edit" href="/edit/1"/>
+    <link href="/posts/1"/>
+    <updated>2024-01-01T00:00:00Z</updated>
+  </entry>
+</feed>"#;
+        let base = Url::parse("https://blog.example/feed.xml").unwrap();
+        let entries = parse_feed(contents, Some(&base)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].url, "https://blog.example/posts/1");
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("entry_title", "Post"),
+                ("published", "2024-01-01T00:00:00Z")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_sitemaps_up_to_the_limit() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://a.example/</loc><lastmod>2024-01-01</lastmod></url>
+  <url><loc>https://a.example/b</loc></url>
+  <url><loc>https://a.example/c</loc></url>
+</urlset>"#;
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        std::io::Write::write_all(&mut gzipped, contents.as_bytes()).unwrap();
+        let gzipped = gzipped.finish().unwrap();
+
+        let entries = read_sitemap(gzipped, Some(2)).await.unwrap();
+        let urls: Vec<_> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        assert_eq!(urls, ["https://a.example/", "https://a.example/b"]);
+        assert_eq!(fields(&entries[0]), [("lastmod", "2024-01-01")]);
+        assert!(fields(&entries[1]).is_empty());
+
+        let entries = read_sitemap(contents.into(), None).await.unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn parses_pocket_and_instapaper_csv_exports() {
+        let pocket =
+            "title,url,time_added,tags,status\nA,https://a.example/,1700000000,rust|web,unread\n";
+        let entries = parse_pocket(pocket).unwrap();
+        assert_eq!(entries[0].url, "https://a.example/");
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("saved_title", "A"),
+                ("add_date", "1700000000"),
+                ("tags", "rust,web"),
+                ("folder", "unread")
+            ]
+        );
+
+        let instapaper =
+            "URL,Title,Selection,Folder,Timestamp\nhttps://b.example/,B,,Archive,1700000001\n";
+        let entries = parse_instapaper(instapaper).unwrap();
+        assert_eq!(entries[0].url, "https://b.example/");
+        assert_eq!(
+            fields(&entries[0]),
+            [
+                ("saved_title", "B"),
+                ("selection", ""),
+                ("folder", "Archive"),
+                ("add_date", "1700000001")
+            ]
+        );
+    }
+}
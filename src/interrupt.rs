@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::{signal, sync::watch, time};
+use tracing::warn;
+
+/// How long the requests in flight may take to finish once interrupted.
+const GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// How many times the run was interrupted.
+static INTERRUPTS: Lazy<watch::Sender<u32>> = Lazy::new(|| watch::channel(0).0);
+
+/// Listens for Ctrl-C. The first one asks the run to stop, and the second one
+/// to stop without waiting for the requests in flight.
+pub fn listen() {
+    tokio::spawn(async {
+        while signal::ctrl_c().await.is_ok() {
+            INTERRUPTS.send_modify(|count| *count += 1);
+            if *INTERRUPTS.borrow() == 1 {
+                warn!("interrupted, finishing the requests in flight (press Ctrl-C again to stop now)");
+            }
+        }
+    });
+}
+
+/// Whether the run was interrupted.
+pub fn interrupted() -> bool {
+    *INTERRUPTS.borrow() > 0
+}
+
+/// Completes once the run is interrupted, after which no new URL should be
+/// fetched.
+pub async fn requested() {
+    interrupts(1).await;
+}
+
+/// Completes once the requests in flight should be abandoned: when the grace
+/// period since the run was interrupted is over, or when it is interrupted
+/// again.
+pub async fn abandoned() {
+    interrupts(1).await;
+    tokio::select! {
+        _ = time::sleep(GRACE_PERIOD) => (),
+        _ = interrupts(2) => (),
+    }
+}
+
+/// Completes once the run was interrupted the given number of times.
+async fn interrupts(count: u32) {
+    let mut interrupts = INTERRUPTS.subscribe();
+    while *interrupts.borrow_and_update() < count {
+        // The sender is never dropped.
+        let _ = interrupts.changed().await;
+    }
+}
@@ -0,0 +1,217 @@
+/// Common words of the languages written in the Latin script, which are told
+/// apart by how many of them a text has.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "of", "and", "to", "in", "for", "is", "on", "with", "how", "what", "why",
+            "your", "you", "an", "from", "by", "at", "this", "are", "it", "that", "my", "new",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "des", "du", "et", "une", "un", "est", "pour", "dans", "sur",
+            "avec", "qui", "que", "au", "aux", "pas", "comment", "ce", "sont", "nous", "vous",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "mit", "von", "den", "dem", "ein", "eine", "nicht",
+            "zu", "für", "auf", "sich", "auch", "wie", "im", "bei", "oder", "wird", "sie",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "del", "y", "una", "por", "para", "con", "es", "en", "que", "como",
+            "sobre", "más", "su", "sus", "al", "lo", "qué", "cómo", "se", "son",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "do", "da", "dos", "das", "e", "uma", "um", "para", "com", "em",
+            "que", "não", "por", "como", "mais", "no", "na", "ao", "sobre", "são", "você",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "della", "di", "e", "che", "per", "una", "con", "non", "del",
+            "sono", "come", "nel", "alla", "dei", "delle", "anche", "più", "questo", "si",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "op", "voor", "met", "niet", "zijn", "dat",
+            "te", "hoe", "wat", "ook", "naar", "bij", "om", "je", "wij", "worden",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "en", "är", "av", "för", "med", "till", "den", "på",
+            "inte", "om", "har", "hur", "vad", "ett", "från", "jag", "du", "vi",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "i", "w", "na", "z", "jest", "się", "do", "nie", "że", "to", "jak", "dla", "od", "po",
+            "co", "czy", "są", "przez", "oraz", "jego", "ich", "tak",
+        ],
+    ),
+    (
+        "tr",
+        &[
+            "ve", "bir", "bu", "için", "ile", "da", "de", "ne", "nasıl", "çok", "daha", "gibi",
+            "olan", "mi", "ben", "sen", "var", "yok", "her", "ama", "neden", "olarak",
+        ],
+    ),
+];
+
+/// Letters that are only, or mostly, used by some of the languages written in
+/// the Latin script.
+const LETTERS: &[(&str, &str)] = &[
+    ("de", "äöüß"),
+    ("fr", "çèêëîïôœùûÿ"),
+    ("es", "ñ¿¡"),
+    ("pt", "ãõ"),
+    ("sv", "å"),
+    ("pl", "ąćęłńśźż"),
+    ("tr", "ğış"),
+];
+
+/// Detects the language of the given text, such as a title, returning its
+/// ISO 639-1 code. Texts in a script used by one language are told by it,
+/// whereas those in the Latin script are told by their common words and
+/// letters. Returns `None` if the language can't be told, as for many short
+/// titles.
+pub fn detect(text: &str) -> Option<&'static str> {
+    if let Some(language) = detect_script(text) {
+        return Some(language);
+    }
+    let text = text.to_lowercase();
+    let words: Vec<_> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words.iter().filter(|word| stopwords.contains(word)).count();
+            (*language, hits * 2)
+        })
+        .collect();
+    for (language, letters) in LETTERS {
+        let hits = text.chars().filter(|c| letters.contains(*c)).count();
+        if let Some((_, score)) = scores.iter_mut().find(|(l, _)| l == language) {
+            *score += hits;
+        }
+    }
+    // A tie between languages, such as on a word they share, tells nothing.
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    match scores[..] {
+        [(language, best), (_, second), ..] if best > 0 && best > second => Some(language),
+        _ => None,
+    }
+}
+
+/// Detects the language of the given text by the script of most of its
+/// letters, if it is one that tells the language.
+fn detect_script(text: &str) -> Option<&'static str> {
+    let mut latin = 0;
+    let mut others: Vec<(&str, usize)> = Vec::new();
+    let has = |chars: &str| text.chars().any(|c| chars.contains(c));
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let language = match c {
+            'a'..='z' | 'A'..='Z' | '\u{c0}'..='\u{24f}' => {
+                latin += 1;
+                continue;
+            }
+            '\u{370}'..='\u{3ff}' => "el",
+            // Ukrainian and Belarusian have letters of their own.
+            '\u{400}'..='\u{4ff}' if has("іїєґІЇЄҐ") => "uk",
+            '\u{400}'..='\u{4ff}' if has("ўЎ") => "be",
+            '\u{400}'..='\u{4ff}' => "ru",
+            '\u{590}'..='\u{5ff}' => "he",
+            // Persian has letters that Arabic doesn't.
+            '\u{600}'..='\u{6ff}' if has("پچژگ") => "fa",
+            '\u{600}'..='\u{6ff}' => "ar",
+            '\u{900}'..='\u{97f}' => "hi",
+            '\u{e00}'..='\u{e7f}' => "th",
+            '\u{10a0}'..='\u{10ff}' => "ka",
+            '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => "ko",
+            // Japanese is mostly written with both kana and kanji.
+            '\u{3040}'..='\u{30ff}' => "ja",
+            '\u{4e00}'..='\u{9fff}' if has_kana(text) => "ja",
+            '\u{4e00}'..='\u{9fff}' => "zh",
+            _ => continue,
+        };
+        match others.iter_mut().find(|(l, _)| *l == language) {
+            Some((_, count)) => *count += 1,
+            None => others.push((language, 1)),
+        }
+    }
+    let (language, count) = others.into_iter().max_by_key(|(_, count)| *count)?;
+    (count >= latin).then_some(language)
+}
+
+/// Whether the given text has hiragana or katakana.
+fn has_kana(text: &str) -> bool {
+    text.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_languages_by_their_scripts() {
+        assert_eq!(detect("Война и мир"), Some("ru"));
+        assert_eq!(detect("Їжак і його друзі"), Some("uk"));
+        assert_eq!(detect("東京の天気"), Some("ja"));
+        assert_eq!(detect("北京天气"), Some("zh"));
+        assert_eq!(detect("서울 날씨"), Some("ko"));
+        assert_eq!(detect("Ελληνικά νέα"), Some("el"));
+        assert_eq!(detect("چگونه یک کتاب بنویسیم"), Some("fa"));
+        assert_eq!(detect("أخبار اليوم"), Some("ar"));
+    }
+
+    #[test]
+    fn tells_latin_languages_by_their_words_and_letters() {
+        assert_eq!(detect("How to write a parser in Rust"), Some("en"));
+        assert_eq!(
+            detect("Comment écrire un analyseur pour le web"),
+            Some("fr")
+        );
+        assert_eq!(
+            detect("Wie man einen Parser schreibt, für die Welt"),
+            Some("de")
+        );
+        assert_eq!(detect("Cómo escribir un analizador con Rust"), Some("es"));
+        assert_eq!(
+            detect("Como escrever um analisador não trivial"),
+            Some("pt")
+        );
+        assert_eq!(detect("Jak napisać parser dla przeglądarki"), Some("pl"));
+    }
+
+    #[test]
+    fn gives_up_on_titles_that_tell_nothing() {
+        assert_eq!(detect("Rust"), None);
+        assert_eq!(detect("GitHub - tokio-rs/tokio"), None);
+        assert_eq!(detect("1234"), None);
+        // A word shared by several languages.
+        assert_eq!(detect("Rust en 2024"), None);
+    }
+
+    #[test]
+    fn weighs_the_script_against_the_latin_letters() {
+        assert_eq!(detect("Rust のドキュメント"), Some("ja"));
+        assert_eq!(detect("The Rust Programming Language — Книга"), Some("en"));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+
+use crate::{otlp, progress};
+
+/// The most verbose level of the logged events.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    /// Only the URLs that failed and other errors.
+    Error,
+    /// Errors, and the problems that titlelist worked around.
+    Warn,
+    /// Warnings, and notes such as the pages without a title.
+    Info,
+    /// Notes, and the requests, redirects and retries.
+    Debug,
+    /// Everything.
+    Trace,
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    }
+}
+
+/// How the logged events are written.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// As text, for people.
+    Text,
+    /// As JSON objects, one per line, with the `timestamp`, the `level`, the
+    /// `target` and the `message` of the events, along with their fields.
+    Json,
+}
+
+/// Logs the events of titlelist up to the given level to the standard error,
+/// in the given format. Must be called before any event is logged, and may
+/// only be called once.
+pub fn init(level: LogLevel, format: LogFormat) {
+    let logger = Logger {
+        level: level.into(),
+        format,
+        next_span: AtomicU64::new(1),
+    };
+    let _ = tracing::subscriber::set_global_default(logger);
+}
+
+/// Writes the events to the standard error, one per line. As text, those of
+/// the `info` level and above are written between parentheses like the other
+/// notes of titlelist, and the others are prefixed by their level. The events
+/// of the dependencies are ignored, and so are spans, which only get an ID.
+///
+/// The spans are recorded by [`otlp`] when they are exported. A subscriber of
+/// `tracing-subscriber` would do, but this one is simple enough to not be
+/// worth the dependencies.
+struct Logger {
+    level: Level,
+    format: LogFormat,
+    next_span: AtomicU64,
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let enabled = match metadata.is_span() {
+            true => otlp::recorder().is_some(),
+            false => *metadata.level() <= self.level,
+        };
+        enabled && metadata.target().starts_with("titlelist")
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        match otlp::recorder() {
+            Some(_) => Some(LevelFilter::TRACE),
+            None => Some(LevelFilter::from_level(self.level)),
+        }
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+        let id = span::Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed));
+        if let Some(recorder) = otlp::recorder() {
+            recorder.new_span(&id, attrs);
+        }
+        id
+    }
+
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        if let Some(recorder) = otlp::recorder() {
+            recorder.record(id, values);
+        }
+    }
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut line = Line::default();
+        event.record(&mut line);
+        let metadata = event.metadata();
+        let level = *metadata.level();
+        let text = match self.format {
+            LogFormat::Text => {
+                let mut fields = String::new();
+                for (name, value) in &line.fields {
+                    match value {
+                        Value::String(value) => fields.push_str(&format!(" {name}={value}")),
+                        value => fields.push_str(&format!(" {name}={value}")),
+                    }
+                }
+                if level <= Level::INFO {
+                    format!("({}{fields})", line.message)
+                } else {
+                    format!("{level:>5} {}{fields}", line.message)
+                }
+            }
+            LogFormat::Json => {
+                let mut object = Map::new();
+                let timestamp = humantime::format_rfc3339_millis(SystemTime::now());
+                object.insert("timestamp".into(), timestamp.to_string().into());
+                object.insert("level".into(), level.as_str().to_lowercase().into());
+                object.insert("target".into(), metadata.target().into());
+                object.insert("message".into(), line.message.into());
+                for (name, value) in line.fields {
+                    object.entry(name).or_insert(value);
+                }
+                Value::Object(object).to_string()
+            }
+        };
+        progress::suspend(|| eprintln!("{text}"));
+    }
+
+    fn enter(&self, id: &span::Id) {
+        if let Some(recorder) = otlp::recorder() {
+            recorder.enter(id);
+        }
+    }
+
+    fn exit(&self, id: &span::Id) {
+        if let Some(recorder) = otlp::recorder() {
+            recorder.exit(id);
+        }
+    }
+
+    fn clone_span(&self, id: &span::Id) -> span::Id {
+        if let Some(recorder) = otlp::recorder() {
+            recorder.clone_span(id);
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: span::Id) -> bool {
+        otlp::recorder().is_some_and(|recorder| recorder.try_close(&id))
+    }
+}
+
+/// The message and the other fields of an event or a span.
+#[derive(Default)]
+pub struct Line {
+    pub message: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Line {
+    /// Records the given field, which may be the message.
+    fn push(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = message,
+            (name, value) => self.fields.push((name.to_owned(), value)),
+        }
+    }
+}
+
+impl Visit for Line {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{value:?}").into());
+    }
+}
@@ -1,159 +1,1947 @@
 use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, stdout, IsTerminal, Write},
+    mem,
     path::{Path, PathBuf},
-    pin::pin,
+    pin::{pin, Pin},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use clap::Parser;
-use eyre::{Report, Result, WrapErr};
-use futures::{stream, StreamExt};
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
-use reqwest::{Client, ClientBuilder};
-use scraper::{element_ref::Text, Html, Selector};
+use clap::{builder::RangedU64ValueParser, Parser, Subcommand, ValueEnum};
+use eyre::{bail, eyre, Report, Result, WrapErr};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    FutureExt, StreamExt,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
 use tokio::{
     fs::File,
-    io::{stdin, AsyncReadExt},
+    io::{stdin, AsyncRead, AsyncReadExt, BufReader},
+    time,
+};
+use tracing::{debug, error, field::Empty, info, info_span, warn, Instrument};
+use url::Url;
+
+use crate::{
+    analyze::{Analyzer, Resolution},
+    archive::ArchiveBackend,
+    breaker::Breaker,
+    cache::Cache,
+    concurrency::{Concurrency, Limiter},
+    config::Config,
+    duplicates::{Clusters, TitleGroups},
+    fetch::{check_status, load_bytes, load_html, probe, FailureKind, RetryClass},
+    input::{Entry, InputFormat, InputOptions},
+    log::{LogFormat, LogLevel},
+    obsidian::Vault,
+    output::{Output, OutputFormat, SharedBuffer},
+    push::{Link, PushBackend, Pusher},
+    render::{RenderMode, Renderer},
+    robots::Robots,
+    schedule::Scheduler,
+    state::State,
+    stats::Stats,
+    throttle::Jitter,
+    translate::Translator,
+    urls::{HostForm, UrlCleaner, UrlFilter, UrlRewrites},
 };
 
+mod amp;
+mod analyze;
+mod archive;
+mod breaker;
+mod browser;
+mod cache;
+mod check;
+mod clipboard;
+mod concurrency;
+mod config;
+mod crawl;
+mod daemon;
+mod diff;
+mod duplicates;
+mod fetch;
+mod html;
+mod input;
+mod interrupt;
+mod lang;
+mod log;
+mod markdown;
+mod metrics;
+mod obsidian;
+mod org;
+mod otlp;
+mod output;
+mod overrides;
+mod progress;
+mod push;
+mod render;
+mod resolver;
+mod rewrite;
+mod robots;
+mod rpc;
+mod schedule;
+mod serve;
+mod sites;
+mod ssrf;
+mod state;
+mod stats;
+mod throttle;
+mod translate;
+mod urls;
+mod watch;
+
+/// How many of the failed URLs are retried concurrently by `--retry-failed`.
+const RETRY_CONCURRENCY: usize = 2;
+
+/// How much longer than `--timeout` the URLs retried by `--retry-failed` may
+/// take.
+const RETRY_TIMEOUT_FACTOR: u32 = 2;
+
 #[derive(Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path of the file that contains the URLs, one per line. Unless this
-    /// option is set, reads from the standard input.
+    /// option is set, reads from the standard input. An `http` or `https` URL
+    /// may also be given, in which case the input is fetched.
     #[arg(short, long)]
     file: Option<PathBuf>,
 
-    /// Template. Use `%title` and `%url` as placeholders.
+    /// Template. Use `%title` and `%url` as placeholders, along with `%` and
+    /// the name of a field of the results, such as `%archive_url` with
+    /// `--archive` or `%author` with `--site-apis`.
     ///
-    /// Default is `%title <%url>`.
+    /// Default is `%title <%url>`, or `%url` with `--expand-only`.
     #[arg(short, long)]
     template: Option<String>,
 
+    /// Template of the lines written to the output for the URLs that fail to
+    /// load, instead of reporting them on the standard error. Use `%url` and
+    /// `%error` as placeholders. Only supported by the `template` output
+    /// format.
+    #[arg(long, value_name = "TEMPLATE")]
+    error_template: Option<String>,
+
+    /// How the URLs that fail to load are reported. The JSON records have the
+    /// `url`, the `error`, its `kind` (`dns`, `tls`, `timeout`, `connect`,
+    /// `http-status`, `parse` or `other`), the `status` code, if any, and the
+    /// number of `retries`.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ErrorFormat::Text,
+        conflicts_with = "error_template"
+    )]
+    error_format: ErrorFormat,
+
     /// Doesn't emit links if the page doesn't have a title. By default, this
     /// is set to `false` and if a page doesn't have a title, `@@@ NO TITLE @@@`
     /// will be used.
     #[arg(long, default_value = "false")]
     skip_when_no_title: bool,
+
+    /// Logs more: `-v` logs the requests, redirects and retries along with
+    /// their timing, and `-vv` logs everything.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only logs the errors, such as the URLs that fail to load, without the
+    /// notes and warnings, and doesn't show the progress, for pipelines whose
+    /// logs only need what went wrong. The same as `--log-level error` and
+    /// `--no-progress`.
+    #[arg(short, long, global = true, conflicts_with_all = ["verbose", "log_level"])]
+    quiet: bool,
+
+    /// The most verbose level of the logs written to the standard error,
+    /// which overrides `--verbose`. Default is `info`.
+    #[arg(long, global = true, value_enum, value_name = "LEVEL")]
+    log_level: Option<LogLevel>,
+
+    /// The format of the logs, such as JSON for log pipelines.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Path of the configuration file, whose rules apply to every run, such
+    /// as `selector."example.com" = "h1.title"` to read the titles of the
+    /// pages of a domain from a CSS selector, or `[[rule]]` tables that
+    /// attach `headers`, a `cookie`, a `user_agent`, a `proxy` or a `render`
+    /// mode to the URLs of a `domain` (or a glob, as in `*.example.com`) or
+    /// of a `pattern`, or `[[resolver]]` tables that resolve the URLs of a
+    /// `pattern` through an `endpoint` given its capture groups (as in
+    /// `https://api.example.com/items/$1`), reading the `title` and the
+    /// `fields` from their JSON pointers. Defaults to
+    /// `titlelist/config.toml` in `$XDG_CONFIG_HOME` or `~/.config`, if it
+    /// exists.
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Refuses to fetch the URLs whose host is or resolves to a loopback,
+    /// private or link-local address, including those of redirects, for
+    /// services that fetch URLs given by their users. Not supported by
+    /// `--render`.
+    #[arg(long, global = true, default_value = "false")]
+    block_private: bool,
+
+    /// Minimum gap between consecutive requests to the same host (e.g.,
+    /// `500ms`), regardless of how many requests are in flight.
+    #[arg(long, global = true, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    delay_per_host: Option<Duration>,
+
+    /// Waits for a random duration of the given range (e.g., `100ms..2s`, or
+    /// just `2s` for up to two seconds) between consecutive requests, so that
+    /// they are less bursty.
+    #[arg(long, global = true, value_name = "RANGE", value_parser = Jitter::parse)]
+    jitter: Option<Jitter>,
+
+    /// Only waits for the `--jitter` between consecutive requests to the same
+    /// host, rather than between all the requests.
+    #[arg(long, global = true, default_value = "false", requires = "jitter")]
+    jitter_per_host: bool,
+
+    /// Format of the input.
+    #[arg(long, value_enum, default_value_t = InputFormat::Lines)]
+    input_format: InputFormat,
+
+    /// Column that contains the URLs when the input is CSV. Either a header
+    /// name or a 1-based column number.
+    #[arg(long, default_value = "url")]
+    url_column: String,
+
+    /// Format of the output. The template is only used by the `template`
+    /// format.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Template)]
+    output_format: OutputFormat,
+
+    /// Base URL used to resolve relative links when the input is HTML.
+    /// Defaults to the `<base>` tag of the document or, if the input was
+    /// fetched, to its URL.
+    #[arg(long)]
+    base_url: Option<Url>,
+
+    /// Only keeps links that have the same origin as the base URL when the
+    /// input is HTML.
+    #[arg(long, default_value = "false")]
+    same_origin: bool,
+
+    /// Only includes browser history entries visited, or bookmarks added,
+    /// within the given duration (e.g., `7d` or `12h`).
+    #[arg(long, value_parser = humantime::parse_duration)]
+    since: Option<Duration>,
+
+    /// Separates the URLs of the input by NUL bytes instead of new lines, as
+    /// produced by `find -print0`. Only affects the `lines` input format.
+    #[arg(short = '0', long, default_value = "false")]
+    read0: bool,
+
+    /// Keeps following `--file` after its contents are read, like `tail -F`,
+    /// processing the lines as they are appended. Only supported by the
+    /// `lines` input format.
+    #[arg(long, default_value = "false", requires = "file")]
+    watch: bool,
+
+    /// Watches the system clipboard instead of reading the input, processing
+    /// each URL as it is copied.
+    #[arg(long, default_value = "false", conflicts_with_all = ["file", "watch"])]
+    clipboard_watch: bool,
+
+    /// Reads the input from the system clipboard.
+    #[arg(long, default_value = "false", conflicts_with_all = ["file", "watch", "clipboard_watch"])]
+    from_clipboard: bool,
+
+    /// Copies the output to the system clipboard instead of printing it. With
+    /// `--clipboard-watch`, each result is copied as soon as it is ready. On
+    /// Linux, the copied text only outlives titlelist if a clipboard manager
+    /// is running.
+    #[arg(long, default_value = "false")]
+    to_clipboard: bool,
+
+    /// Speaks JSON-RPC over the standard input and output instead of
+    /// processing an input, so that editor plugins can keep a single process
+    /// alive. Each line is a request such as
+    /// `{"jsonrpc": "2.0", "id": 1, "method": "title", "params": {"url": "..."}}`.
+    #[arg(long, default_value = "false")]
+    stdio: bool,
+
+    /// Prints the URLs that would be fetched, once normalized, deduplicated and
+    /// filtered, along with the settings they would be fetched with, without
+    /// fetching them. Remote inputs are still fetched.
+    #[arg(long, default_value = "false")]
+    dry_run: bool,
+
+    /// Sorts the results, so that the output is stable across runs. The
+    /// results are only written once the whole input is processed.
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<SortKey>,
+
+    /// Groups the results, writing them under a heading per group. The groups
+    /// are sorted by name, and `--sort` sorts the results within them. The
+    /// results are only written once the whole input is processed.
+    #[arg(long, value_enum, value_name = "KEY", conflicts_with = "resume")]
+    group_by: Option<GroupBy>,
+
+    /// Prints a summary of the run to the standard error once it ends: how
+    /// many URLs succeeded, had no title or failed (by kind of failure), how
+    /// much was downloaded, how long it took, and the slowest hosts.
+    #[arg(long, default_value = "false")]
+    stats: bool,
+
+    /// Reports the distinct URLs that have the same title to the standard
+    /// error once the run ends, which are often soft 404 pages, consent walls
+    /// or duplicated content.
+    #[arg(long, default_value = "false")]
+    duplicate_titles: bool,
+
+    /// Gives the results a `duplicate_title` field, which tells whether other
+    /// URLs have the same title. The results are held until the run ends.
+    #[arg(long, default_value = "false")]
+    mark_duplicate_titles: bool,
+
+    /// Groups the results whose titles are near-duplicates, such as the same
+    /// article under many URLs, writing them one after the other and giving
+    /// them a `cluster` field, numbered from 1. The similarity of the titles,
+    /// from 0 to 1, may be given, and defaults to 0.6. The results are held
+    /// until the run ends.
+    #[arg(
+        long,
+        value_name = "SIMILARITY",
+        num_args = 0..=1,
+        default_missing_value = "0.6",
+        value_parser = parse_similarity
+    )]
+    cluster_similar: Option<f64>,
+
+    /// Only fails the run if more than this percentage of the URLs failed to
+    /// load, which includes the pages answered with an error status (`4xx` or
+    /// `5xx`). Otherwise, any failure does, and the run exits with the code 2,
+    /// or 3 if all the URLs failed.
+    #[arg(long, value_name = "PERCENT")]
+    fail_threshold: Option<f64>,
+
+    /// Aborts the run on the first URL that fails to load, rather than
+    /// reporting it and going on with the others. The results so far are
+    /// still written. Failures that only fall back to the fetched page, such
+    /// as those of rendering, don't abort it.
+    #[arg(long, default_value = "false", conflicts_with = "fail_threshold")]
+    fail_fast: bool,
+
+    /// How long each URL may take to load, including its redirects. By
+    /// default, there is no limit.
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Retries the URLs that failed once the others are processed, with
+    /// twice the `--timeout` and fewer of them at once, since failures are
+    /// often temporary.
+    #[arg(long, default_value = "false", conflicts_with = "fail_fast")]
+    retry_failed: bool,
+
+    /// The failures that `--retry-failed` retries, separated by commas. By
+    /// default, only the usually temporary ones: timeouts, failures to
+    /// connect, `429 Too Many Requests` and server errors.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CLASSES",
+        value_delimiter = ',',
+        default_values_t = RetryClass::TRANSIENT,
+        requires = "retry_failed"
+    )]
+    retry_on: Vec<RetryClass>,
+
+    /// Writes the URLs that failed to load to the given file, one per line, so
+    /// that they can be given to titlelist again later. With
+    /// `--retry-failed`, only those that failed again are written.
+    #[arg(long, value_name = "FILE")]
+    failed_output: Option<PathBuf>,
+
+    /// Doesn't show the progress of the run on the standard error. It is
+    /// otherwise shown when the standard error is a terminal: how many URLs
+    /// were completed and failed, how fast, and, if the input can be read as a
+    /// whole first, how long the rest should take.
+    #[arg(long, default_value = "false")]
+    no_progress: bool,
+
+    /// Exports a trace per URL to the OpenTelemetry collector at the given
+    /// URL (e.g., `http://localhost:4318`) over OTLP/HTTP once the run ends.
+    /// The traces have spans for the name resolution, the request until the
+    /// response headers arrive (which includes connecting), the body and its
+    /// parsing.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<Url>,
+
+    /// Writes the results for people when the standard output is a terminal:
+    /// with a coloured marker of whether they have a title, their titles and
+    /// URLs aligned in columns, and the URLs that fail to load among them with
+    /// their error dimmed. The output is written with the template otherwise,
+    /// such as when it's piped. Only applies to the `template` output format.
+    #[arg(long, default_value = "false", conflicts_with_all = ["template", "error_template"])]
+    pretty: bool,
+
+    /// How many URLs are loaded at once: a number, or `auto` to adjust it to
+    /// how the hosts respond, sending more requests at once while they keep
+    /// up and fewer when they slow down, time out or answer with
+    /// `429 Too Many Requests` or `503 Service Unavailable`.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = Concurrency::parse,
+        default_value = "10"
+    )]
+    concurrency: Concurrency,
+
+    /// How many URLs of the same host are loaded at once at most. The URLs
+    /// are always loaded taking turns between their hosts, so that the many
+    /// URLs of a slow host don't hold back those of the others.
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    max_per_host: Option<usize>,
+
+    /// Skips the URLs of the hosts that failed to load this many times in a
+    /// row, such as dead domains, rather than waiting for each of them to
+    /// fail. Only the failures of the host count (DNS, connection, timeout
+    /// and 5xx errors), not the pages it doesn't find, and a URL of the host
+    /// that answers resets the count. The skipped URLs are reported as
+    /// failures.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    break_after: Option<u32>,
+
+    /// Writes the results as soon as they are ready, rather than in the order
+    /// of the input, so that a slow page doesn't hold back the ones after it.
+    #[arg(long, default_value = "false")]
+    unordered: bool,
+
+    /// Only processes the first N URLs of the input.
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Skips the first N URLs of the input, so that, along with `--limit`, it
+    /// can be processed in chunks.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    skip: usize,
+
+    /// Flags the pages that say they don't exist, even though they were served
+    /// successfully, with a `soft_404` field. Their title and main heading are
+    /// matched against common "not found" phrases.
+    #[arg(long, default_value = "false")]
+    detect_soft_404: bool,
+
+    /// Additional pattern (a regular expression) of the titles of soft 404
+    /// pages, matched against the lowercased title and main heading. May be
+    /// given several times.
+    #[arg(long, value_name = "REGEX", requires = "detect_soft_404")]
+    soft_404_pattern: Vec<String>,
+
+    /// Flags the pages behind a paywall or a login wall with a `paywalled`
+    /// field, based on their structured data (`isAccessibleForFree`), their
+    /// meta tags and their title.
+    #[arg(long, default_value = "false")]
+    detect_paywalls: bool,
+
+    /// Detects the language of the titles, giving the results a `lang` field
+    /// with its ISO 639-1 code (e.g., `en`), which is empty when it can't be
+    /// told, as for many short titles.
+    #[arg(long, default_value = "false")]
+    detect_language: bool,
+
+    /// Only outputs the results whose title is in one of the given languages,
+    /// by ISO 639-1 code (e.g., `en,fr`). Implies `--detect-language`.
+    #[arg(long, value_name = "LANGS", value_delimiter = ',')]
+    only_lang: Vec<String>,
+
+    /// Translates the titles to the given language (e.g., `en`), giving the
+    /// results a `title_translated` field, through the translation service
+    /// of the `[translation]` table of the configuration file: its `backend`
+    /// (`libretranslate` or `deepl`), its `endpoint` and its `api_key`.
+    #[arg(long, value_name = "LANG")]
+    translate_to: Option<String>,
+
+    /// Renders the pages with a headless Chromium before reading their titles,
+    /// so that the titles set by JavaScript, as in single-page applications,
+    /// are seen. Much slower than fetching the pages. With `auto`, pages are
+    /// only rendered if their static title looks like a placeholder.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "always"
+    )]
+    render: Option<RenderMode>,
+
+    /// Browser used by `--render`. Defaults to the first Chromium or Chrome
+    /// found in the `PATH`.
+    #[arg(long, value_name = "PATH")]
+    browser: Option<PathBuf>,
+
+    /// Passes an argument to the browser of `--render`, which may be given
+    /// many times, as in `--browser-arg=--no-sandbox`. Chromium refuses to
+    /// run as root, such as in containers, unless its sandbox is disabled
+    /// like this, which leaves the system exposed to the pages it loads.
+    #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+    browser_arg: Vec<String>,
+
+    /// How long the scripts of the pages may run when rendering them.
+    #[arg(long, default_value = "5s", value_parser = humantime::parse_duration)]
+    render_budget: Duration,
+
+    /// Retries the `https` URLs over `http` when they fail to connect, such
+    /// as because of TLS errors. URLs without a scheme, such as
+    /// `example.com/foo`, are assumed to use `https`.
+    #[arg(long, default_value = "false")]
+    http_fallback: bool,
+
+    /// Resolves the URLs of some sites through their APIs rather than their
+    /// pages, whose titles are often generic or set by scripts, or which often
+    /// serve interstitials:
+    ///
+    /// - YouTube and Vimeo, through oEmbed;
+    /// - Twitter, whose tweets are titled with their author and text;
+    /// - Reddit;
+    /// - GitHub, whose repositories are titled with their description (its API
+    ///   is used with the token in `GITHUB_TOKEN`, if set, which raises its
+    ///   rate limit);
+    /// - Wikipedia;
+    /// - DOIs (as in `doi:10.1000/182`), through CrossRef;
+    /// - arXiv, whose paper IDs may also be given (as in `arXiv:2401.12345`);
+    /// - Hacker News, whose stories give the URL of the article they link to;
+    /// - Stack Overflow and the other sites of Stack Exchange;
+    /// - Mastodon, whose statuses are titled with their author and text.
+    ///
+    /// The results get `article_url`, `author`, `description`, `sitename` and
+    /// `year` fields: the subreddit of Reddit posts, the extract of Wikipedia
+    /// articles, and the year in which papers were published, for instance.
+    #[arg(long, default_value = "false")]
+    site_apis: bool,
+
+    /// Resolves the tweets through the given Nitter instance (e.g.,
+    /// `https://nitter.net`) with `--site-apis`, rather than the oEmbed
+    /// endpoint of Twitter.
+    #[arg(long, value_name = "URL", requires = "site_apis")]
+    nitter: Option<Url>,
+
+    /// Looks up the pages that are gone (`404 Not Found` or `410 Gone`) or
+    /// whose host can't be reached in the `--archive-backend`, reading the
+    /// title of their latest snapshot instead. The results get an `archive_url`
+    /// field with the snapshot, which is empty for the other pages.
+    #[arg(long, default_value = "false", conflicts_with = "expand_only")]
+    archive_fallback: bool,
+
+    /// Saves the pages that load to the `--archive-backend`, giving the
+    /// results an `archive_url` field with their snapshot, which the template
+    /// can write with `%archive_url`. The pages are saved one at a time, at
+    /// most every 5 seconds, since the archives limit how often they can be
+    /// saved.
+    #[arg(long, default_value = "false", conflicts_with = "expand_only")]
+    archive: bool,
+
+    /// The web archive used by `--archive` and `--archive-fallback`.
+    #[arg(long, value_enum, value_name = "ARCHIVE", default_value_t = ArchiveBackend::Wayback)]
+    archive_backend: ArchiveBackend,
+
+    /// Pushes the results with a title to a service, such as a bookmark
+    /// manager, along with their `description` field and the tags of their
+    /// `tags` field, which are separated by commas, where the service keeps
+    /// them. The settings of the service are read from its
+    /// table in the configuration file, as in `[push.pinboard]`:
+    ///
+    /// - `linkding`: the `url` of the instance and its `token`, which
+    ///   `LINKDING_TOKEN` overrides.
+    /// - `notion`: the `token` of an integration, which `NOTION_TOKEN`
+    ///   overrides, and the ID of the `database` it is shared with, whose
+    ///   `Title`, `URL`, `Domain` and `Fetched At` properties are filled.
+    /// - `pinboard`: its `token`, as in `user:TOKEN`, which `PINBOARD_TOKEN`
+    ///   overrides.
+    /// - `raindrop`: its `token`, which `RAINDROP_TOKEN` overrides, and the ID
+    ///   of the `collection`, which defaults to Unsorted.
+    /// - `shaarli`: the `url` of the instance and its API `secret`, which
+    ///   `SHAARLI_SECRET` overrides.
+    /// - `wallabag`: the `url` of the instance, the `client_id` and the
+    ///   `client_secret` of an API client, and the `username` and the
+    ///   `password` of the account, which `WALLABAG_PASSWORD` overrides.
+    ///
+    /// The links that are already bookmarked are kept as is.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SERVICE",
+        conflicts_with = "expand_only"
+    )]
+    push: Option<PushBackend>,
+
+    /// Writes a Markdown note per result with a title to the given Obsidian
+    /// vault, or a folder of it. The notes are named after the titles, and
+    /// have the `url`, `fetched_at` and `tags` of the results as properties.
+    /// The results that already have a note are skipped.
+    #[arg(long, value_name = "VAULT", conflicts_with = "expand_only")]
+    obsidian: Option<PathBuf>,
+
+    /// Appends the results to the daily note of `--obsidian` instead, named
+    /// after the local day as in `2024-01-31.md` (the day in UTC on non-Unix
+    /// platforms), as the items of a list.
+    #[arg(long, default_value = "false", requires = "obsidian")]
+    obsidian_daily: bool,
+
+    /// Normalizes the URLs before fetching and outputting them: the host is
+    /// lowercased, default ports, fragments and dot segments are removed, and
+    /// scheme-relative URLs (`//example.com`) get the `https` scheme.
+    #[arg(long, default_value = "false")]
+    normalize: bool,
+
+    /// Only processes the URLs of the given host and its subdomains. May be
+    /// given several times.
+    #[arg(long, value_name = "HOST")]
+    only_host: Vec<String>,
+
+    /// Skips the URLs of the given host and its subdomains. May be given
+    /// several times.
+    #[arg(long, value_name = "HOST")]
+    exclude_host: Vec<String>,
+
+    /// Only processes the URLs that match the given regular expression. May
+    /// be given several times, in which case a URL must match any of them.
+    #[arg(long, value_name = "REGEX")]
+    include: Vec<String>,
+
+    /// Skips the URLs that match the given regular expression. May be given
+    /// several times.
+    #[arg(long, value_name = "REGEX")]
+    exclude: Vec<String>,
+
+    /// Skips the URLs that the robots.txt file of their host disallows,
+    /// reporting them. The robots.txt files are fetched once per host.
+    #[arg(long, default_value = "false")]
+    respect_robots: bool,
+
+    /// Skips the URLs of the input that were already seen, comparing them once
+    /// normalized (as with `--normalize`).
+    #[arg(long, default_value = "false")]
+    dedup: bool,
+
+    /// Outputs a single result for the URLs that land on the same page, once
+    /// their redirects are followed, with its final URL (as with `--expand`).
+    #[arg(long, default_value = "false")]
+    dedup_final: bool,
+
+    /// Lists the input URLs that landed on each result of `--dedup-final` in
+    /// an `aliases` field, separated by spaces. The results are only written
+    /// once the whole input is processed.
+    #[arg(long, default_value = "false", requires = "dedup_final")]
+    list_aliases: bool,
+
+    /// Writes the internationalized domain names of the output URLs in the
+    /// given form. By default, they are written as they were given or served.
+    #[arg(long, value_enum, value_name = "FORM")]
+    idn: Option<HostForm>,
+
+    /// Removes the tracking parameters, such as `utm_source` or `fbclid`, from
+    /// the URLs before fetching and outputting them.
+    #[arg(long, default_value = "false")]
+    clean_urls: bool,
+
+    /// Additional query parameter removed by `--clean-urls`. A trailing `*`
+    /// matches any suffix, as in `ref_*`. May be given several times.
+    #[arg(long, value_name = "PARAM", requires = "clean_urls")]
+    strip_param: Vec<String>,
+
+    /// Query parameter kept by `--clean-urls`, even if it is a known tracking
+    /// parameter. A trailing `*` matches any suffix. May be given several
+    /// times.
+    #[arg(long, value_name = "PARAM", requires = "clean_urls")]
+    keep_param: Vec<String>,
+
+    /// Replaces AMP pages, including those served by the AMP caches of Google
+    /// and Cloudflare, by their canonical page, outputting its URL and title.
+    #[arg(long, default_value = "false")]
+    unwrap_amp: bool,
+
+    /// Outputs the final URL of each link, after its redirects, instead of the
+    /// requested one, which is kept in a `requested_url` field. Useful for
+    /// links from URL shorteners.
+    #[arg(long, default_value = "false")]
+    expand: bool,
+
+    /// Like `--expand`, but only follows the HTTP redirects of the links,
+    /// without fetching their titles.
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["skip_when_no_title", "render", "cache"]
+    )]
+    expand_only: bool,
+
+    /// Sends the given cookie to a domain and its subdomains, so that they
+    /// serve the requested pages instead of a cookie consent page. Given as
+    /// `DOMAIN=COOKIE`, such as `example.com=consent=yes`. May be given
+    /// several times. Cookies for some well-known sites are built in.
+    #[arg(long, value_name = "DOMAIN=COOKIE", value_parser = parse_consent_cookie)]
+    consent_cookie: Vec<(String, String)>,
+
+    /// Caches the resolved titles in the given directory, so that later runs
+    /// only fetch the URLs that are new or expired.
+    #[arg(long, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Appends the results to the given output file, skipping the URLs it
+    /// already has, so that an interrupted run can be resumed. The file must
+    /// have been written with the same output format and template. Not
+    /// supported by the `json`, `bookmarks` and `opml` output formats.
+    #[arg(long, value_name = "FILE", conflicts_with = "to_clipboard")]
+    resume: Option<PathBuf>,
+
+    /// Records the URLs completed by the run in the given file as it
+    /// progresses, so that it can be continued with `--continue` if it is
+    /// interrupted.
+    #[arg(long, value_name = "FILE")]
+    state: Option<PathBuf>,
+
+    /// Continues the run recorded by `--state`, skipping the URLs it already
+    /// completed. Without this flag, the state file is started anew.
+    #[arg(long = "continue", default_value = "false", requires = "state")]
+    continue_run: bool,
+
+    /// How long the titles in the `--cache` directory are used without asking
+    /// the server. Expired titles are revalidated with a conditional request
+    /// when possible, so that unchanged pages aren't downloaded again.
+    #[arg(long, default_value = "1d", value_parser = humantime::parse_duration)]
+    cache_ttl: Duration,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs an HTTP server that resolves titles on request.
+    Serve(serve::ServeArgs),
+    /// Runs a daemon that fetches the URLs of an on-disk queue in the
+    /// background, appending the results to a file.
+    Daemon(daemon::DaemonArgs),
+    /// Adds URLs to the queue of a daemon.
+    Enqueue(daemon::EnqueueArgs),
+    /// Inspects or cleans up a `--cache` directory.
+    Cache(cache::CacheArgs),
+    /// Compares the outputs of two runs, reporting the URLs whose title
+    /// changed, that disappeared or that started failing.
+    Diff(diff::DiffArgs),
+    /// Checks the links of Markdown files, reporting those that fail to load
+    /// or whose text doesn't match the title of the page.
+    Check(check::CheckArgs),
+    /// Only checks whether URLs are reachable, reporting their status codes,
+    /// redirects and timing, without fetching their titles.
+    CheckLinks(check::CheckLinksArgs),
+    /// Rewrites the bare URLs of Markdown or Org files in place as links
+    /// titled with the page titles, or fills in the empty anchors of HTML
+    /// files.
+    Rewrite(rewrite::RewriteArgs),
+    /// Follows the links of a page up to a depth, printing the title of every
+    /// page found.
+    Crawl(crawl::CrawlArgs),
 }
 
+/// Exits with 0 if the run succeeded, 2 if some of its URLs failed to load, 3
+/// if all of them did, or 1 for any other error.
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            if error.downcast_ref::<Interrupted>().is_some() {
+                // Exits right away, since the runtime would otherwise wait for
+                // the standard input to be read to its end.
+                std::process::exit(Interrupted::EXIT_CODE.into());
+            }
+            match error.downcast_ref::<FailedUrls>() {
+                Some(failed) => ExitCode::from(failed.exit_code()),
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+/// Runs the command selected by the arguments.
+async fn run() -> Result<()> {
     let args = Args::parse();
+    if let Some(endpoint) = &args.otlp_endpoint {
+        otlp::enable(endpoint.clone());
+    }
+    let log_level = args.log_level.unwrap_or(match args.verbose {
+        _ if args.quiet => LogLevel::Error,
+        0 => LogLevel::Info,
+        1 => LogLevel::Debug,
+        _ => LogLevel::Trace,
+    });
+    log::init(log_level, args.log_format);
+    let config = Config::load(args.config.as_deref())?;
+    if args.block_private {
+        fetch::block_private();
+    }
+    if let Some(delay) = args.delay_per_host {
+        throttle::set_delay(delay);
+    }
+    if let Some(jitter) = args.jitter {
+        throttle::set_jitter(jitter, args.jitter_per_host);
+    }
+    overrides::set(&config.rule).wrap_err("invalid rule in the configuration")?;
+    sites::set_custom_resolvers(&config.resolver)
+        .wrap_err("invalid resolver in the configuration")?;
+
+    match args.command {
+        Some(Command::Serve(serve_args)) => return serve::serve(serve_args).await,
+        Some(Command::Daemon(daemon_args)) => return daemon::run(daemon_args).await,
+        Some(Command::Enqueue(enqueue_args)) => return daemon::enqueue(enqueue_args),
+        Some(Command::Cache(cache_args)) => return cache::manage(cache_args),
+        Some(Command::Diff(diff_args)) => return diff::diff(diff_args),
+        Some(Command::Check(check_args)) => return check::check(check_args).await,
+        Some(Command::CheckLinks(check_args)) => return check::check_links(check_args).await,
+        Some(Command::Rewrite(rewrite_args)) => return rewrite::rewrite(rewrite_args).await,
+        Some(Command::Crawl(crawl_args)) => return crawl::crawl(crawl_args).await,
+        None => (),
+    }
+    if args.stdio {
+        return rpc::run().await;
+    }
 
-    let template = args.template.as_deref().unwrap_or("%title <%url>");
+    let default_template = if args.expand_only {
+        "%url"
+    } else {
+        "%title <%url>"
+    };
+    let template = args.template.as_deref().unwrap_or(default_template);
+    if args.error_template.is_some() && args.output_format != OutputFormat::Template {
+        bail!("`--error-template` is only supported by the `template` output format");
+    }
+    if args.error_format == ErrorFormat::Output && args.output_format != OutputFormat::Ndjson {
+        bail!("`--error-format output` is only supported by the `ndjson` output format");
+    }
+    fetch::set_consent_cookies(args.consent_cookie.clone());
 
-    let contents = read_file_string(args.file.as_deref()).await?;
+    let mut entries = read_entries(&args).await?;
+    let rewrites = UrlRewrites {
+        normalize: args.normalize,
+        unwrap_amp: args.unwrap_amp,
+        cleaner: args
+            .clean_urls
+            .then(|| UrlCleaner::new(&args.strip_param, &args.keep_param)),
+    };
+    entries = {
+        let rewrites = rewrites.clone();
+        entries
+            .map(move |entry| {
+                let mut entry = entry?;
+                entry.url = rewrites.apply(&entry.url);
+                if urls::has_mixed_scripts(&entry.url) {
+                    warn!(
+                        "the domain of `{}` mixes scripts, as lookalikes of other domains do",
+                        entry.url
+                    );
+                }
+                Ok(entry)
+            })
+            .boxed()
+    };
+    let filter = UrlFilter::new(
+        &args.only_host,
+        &args.exclude_host,
+        &args.include,
+        &args.exclude,
+    )?;
+    if !filter.is_empty() {
+        entries = entries
+            .filter(move |entry| {
+                let kept = entry
+                    .as_ref()
+                    .map_or(true, |entry| filter.matches(&entry.url));
+                future::ready(kept)
+            })
+            .boxed();
+    }
+    let duplicates = Arc::new(AtomicUsize::new(0));
+    if args.dedup {
+        let duplicates = Arc::clone(&duplicates);
+        let mut seen = HashSet::new();
+        entries = entries
+            .filter(move |entry| {
+                let unique = match entry {
+                    Ok(entry) => seen.insert(urls::normalize(&entry.url)),
+                    Err(_) => true,
+                };
+                if !unique {
+                    duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+                future::ready(unique)
+            })
+            .boxed();
+    }
+    // URLs that a previous run already completed.
+    let mut done = HashSet::new();
+    let mut resumed = None;
+    if let Some(path) = &args.resume {
+        let contents = match tokio::fs::read_to_string(path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error).wrap_err_with(|| format!("failed to read `{}`", path.display()))
+            }
+        };
+        let urls = output::parse_urls(&contents, args.output_format, template)
+            .wrap_err_with(|| format!("failed to resume `{}`", path.display()))?;
+        info!(
+            "skipping {} URLs already in `{}`",
+            urls.len(),
+            path.display()
+        );
+        done.extend(urls);
+        if !args.dry_run {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            resumed = Some((file, !contents.is_empty()));
+        }
+    }
+    let mut state = None;
+    if let (Some(path), true) = (&args.state, args.dry_run) {
+        if args.continue_run {
+            done.extend(state::completed(path)?);
+        }
+    } else if let Some(path) = &args.state {
+        let (opened, completed) = State::open(path, args.continue_run)?;
+        if args.continue_run {
+            info!(
+                "skipping {} URLs completed by the previous run",
+                completed.len()
+            );
+        }
+        done.extend(completed);
+        state = Some(opened);
+    }
+    if !done.is_empty() {
+        entries = entries
+            .filter(move |entry| {
+                let skip = matches!(entry, Ok(entry) if done.contains(&entry.url));
+                future::ready(!skip)
+            })
+            .boxed();
+    }
+    if args.skip > 0 {
+        entries = entries.skip(args.skip).boxed();
+    }
+    let entries = match args.limit {
+        Some(limit) => entries.take(limit).boxed(),
+        None => entries,
+    };
+    if args.dry_run {
+        return dry_run(&args, entries).await;
+    }
 
-    // Creates an iterator of futures.
-    let titles_iter = non_empty_lines(&contents).map(|url| async move {
-        let maybe_title = load_url_and_get_title(url).await?;
-        Ok::<_, Report>((maybe_title, url))
-    });
+    let cache = match &args.cache {
+        Some(dir) => Some(Cache::open(dir, args.cache_ttl)?),
+        None => None,
+    };
+    let cache = cache.as_ref();
 
-    // Processes 10 futures concurrently.
-    let mut urls_stream = stream::iter(titles_iter).buffered(10);
+    let mut analyzer = Analyzer::default().select_titles(&config.selector)?;
+    if args.detect_soft_404 {
+        analyzer = analyzer.detect_soft_404(&args.soft_404_pattern)?;
+    }
+    if args.detect_paywalls {
+        analyzer = analyzer.detect_paywalls();
+    }
+    if args.unwrap_amp {
+        analyzer = analyzer.unwrap_amp();
+    }
+    if args.site_apis {
+        analyzer = analyzer.use_site_apis();
+    }
+    if let Some(instance) = &args.nitter {
+        sites::set_nitter(instance.clone());
+    }
+    if args.expand || args.dedup_final {
+        analyzer = analyzer.expand();
+    }
+    if args.render.is_some() || overrides::renders() {
+        if args.block_private {
+            bail!("`--render` can't be used with `--block-private`");
+        }
+        let renderer = Renderer::new(
+            args.browser.clone(),
+            args.browser_arg.clone(),
+            args.render_budget,
+        )?;
+        let mode = args.render.unwrap_or(RenderMode::Never);
+        analyzer = analyzer.render_with(renderer, mode);
+    }
+    let analyzer = &analyzer;
+    let (expand, expand_only) = (args.expand || args.expand_only, args.expand_only);
+    let rewrites = &rewrites;
+    let http_fallback = args.http_fallback;
+    let (archive, archive_fallback) = (args.archive, args.archive_fallback);
+    let detect_language = args.detect_language || !args.only_lang.is_empty();
+    let translator = match (&args.translate_to, &config.translation) {
+        (Some(target), Some(translation)) => Some(Translator::new(translation, target)?),
+        (Some(_), None) => {
+            bail!("`--translate-to` needs a `[translation]` table in the configuration")
+        }
+        (None, _) => None,
+    };
+    let translator = translator.as_ref();
+    let archive_backend = args.archive_backend;
+    let robots = args.respect_robots.then(Robots::default);
+    let robots = robots.as_ref();
+    let limiter = matches!(args.concurrency, Concurrency::Auto).then(Limiter::new);
+    let limiter = limiter.as_ref();
+    let breaker = args.break_after.map(Breaker::new);
+    let breaker = breaker.as_ref();
+
+    // Processes an entry, which may only take the given time to resolve.
+    let process = move |index, mut entry: Entry, timeout| {
+        let span = info_span!("url", url = %entry.url, status = Empty, error = Empty);
+        let traced = span.clone();
+        async move {
+            if let Some(robots) = robots {
+                if !robots.allows(&entry.url).await {
+                    info!("skipped `{}`, disallowed by its robots.txt", entry.url);
+                    return Ok(Outcome::Skipped);
+                }
+            }
+            // The URLs skipped by the breaker are reported as failures, so
+            // that they reach the error output and `--failed-output`.
+            if breaker.is_some_and(|breaker| breaker.is_open(&entry.url)) {
+                let error = eyre!("skipped, as its host failed too many times in a row");
+                span.record("error", format!("{error:#}"));
+                return Ok(Outcome::Failed {
+                    index,
+                    entry,
+                    error,
+                });
+            }
+            let permit = match limiter {
+                Some(limiter) => Some(limiter.acquire().await),
+                None => None,
+            };
+            let started = Instant::now();
+            let result = match resolve(&entry.url, cache, analyzer, expand_only, timeout).await {
+                Err(error)
+                    if http_fallback
+                        && entry.url.starts_with("https:")
+                        && is_connect_error(&error) =>
+                {
+                    entry.url.replace_range(..6, "http:");
+                    info!("retrying over `http`: `{}`", entry.url);
+                    resolve(&entry.url, cache, analyzer, expand_only, timeout).await
+                }
+                result => result,
+            };
+            if let Some(permit) = permit {
+                permit.finish(is_overloaded(&result));
+            }
+            if breaker.is_some_and(|breaker| breaker.record(&entry.url, is_host_failure(&result))) {
+                let host = Url::parse(&entry.url).map(|url| url.origin().ascii_serialization());
+                let host = host.unwrap_or_default();
+                warn!("skipping the other URLs of `{host}`, which failed too many times in a row");
+            }
+            let mut archive_url = None;
+            let result = match result {
+                result if archive_fallback && is_gone(&result) => {
+                    match archive_backend.find_snapshot(&entry.url).await {
+                        Ok(Some(snapshot)) => {
+                            info!("using the archived `{}`", snapshot.url);
+                            archive_url = Some(snapshot.url);
+                            Ok(Resolution {
+                                title: snapshot.title,
+                                status: result.err().and_then(|error| fetch::error_status(&error)),
+                                ..Default::default()
+                            })
+                        }
+                        Ok(None) => result,
+                        Err(error) => {
+                            warn!(
+                                "failed to look up `{}` in the archive: {error:#}",
+                                entry.url
+                            );
+                            result
+                        }
+                    }
+                }
+                result => result,
+            };
+            let saved = matches!(&result, Ok(resolution)
+                if resolution.status.is_some_and(|status| (200..300).contains(&status)));
+            if archive && archive_url.is_none() && saved {
+                match archive_backend.save(&entry.url).await {
+                    Ok(snapshot) => archive_url = Some(snapshot),
+                    Err(error) => warn!("failed to archive `{}`: {error:#}", entry.url),
+                }
+            }
+            let resolution = match result {
+                Ok(resolution) => resolution,
+                Err(error) => {
+                    span.record("error", format!("{error:#}"));
+                    return Ok(Outcome::Failed {
+                        index,
+                        entry,
+                        error,
+                    });
+                }
+            };
+            if let Some(status) = resolution.status {
+                span.record("status", status);
+            }
+            debug!("resolved `{}` in {:?}", entry.url, started.elapsed());
+            entry.fields.extend(resolution.fields);
+            if archive || archive_fallback {
+                let archive_url = archive_url.unwrap_or_default();
+                entry.fields.push(("archive_url".into(), archive_url));
+            }
+            if detect_language {
+                let title = resolution.title.as_deref();
+                let lang = title.and_then(lang::detect).unwrap_or_default();
+                entry.fields.push(("lang".into(), lang.into()));
+            }
+            if let Some(translator) = translator {
+                let translated = match &resolution.title {
+                    Some(title) => translator
+                        .translate(title, entry.field("lang"))
+                        .await
+                        .unwrap_or_else(|error| {
+                            warn!("{error:#}");
+                            String::new()
+                        }),
+                    None => String::new(),
+                };
+                entry.fields.push(("title_translated".into(), translated));
+            }
+            let requested = match resolution.url {
+                // The final URLs may have tracking parameters of their own.
+                Some(url) => std::mem::replace(&mut entry.url, rewrites.apply(&url)),
+                None => entry.url.clone(),
+            };
+            if expand {
+                entry
+                    .fields
+                    .push(("requested_url".into(), requested.clone()));
+            }
+            Ok::<_, Report>(Outcome::Done(Processed {
+                index,
+                title: resolution.title,
+                entry,
+                requested,
+                status: resolution.status,
+                bytes: resolution.bytes,
+                elapsed: started.elapsed(),
+            }))
+        }
+        .instrument(traced)
+    };
+
+    // Once interrupted, no new entries are read, and those in flight are given
+    // a moment to finish.
+    interrupt::listen();
+    let mut entries = entries.take_until(interrupt::requested()).boxed();
 
-    while let Some(tup) = urls_stream.next().await {
-        let (maybe_title, url) = tup?;
-        let maybe_title = maybe_title.as_deref().or_else(|| {
-            eprintln!("(no title for `{url}`)");
-            (!args.skip_when_no_title).then_some("@@@ NO TITLE @@@")
-        });
-        if let Some(title) = maybe_title {
-            let text = process_template(template, title, url);
-            println!("{text}");
+    // The progress is only shown with the number of URLs if the whole input
+    // can be read first.
+    if !args.no_progress && !args.quiet {
+        let reading_whole = !args.watch
+            && !args.clipboard_watch
+            && (args.file.is_some() || !io::stdin().is_terminal());
+        let total = if reading_whole {
+            let all: Vec<_> = entries.collect().await;
+            let total = all.len();
+            entries = stream::iter(all).boxed();
+            Some(total)
+        } else {
+            None
+        };
+        progress::start(total);
+    }
+
+    // Creates a stream of futures.
+    let timeout = args.timeout;
+    let mut urls_stream = Scheduler::new(
+        entries,
+        args.concurrency.max(),
+        args.max_per_host,
+        !args.unordered,
+        move |index, entry| process(index, entry, timeout).boxed(),
+    )
+    .boxed();
+
+    let clipboard_buffer = args.to_clipboard.then(SharedBuffer::default);
+    let (writer, continuing, terminal): (Box<dyn Write>, _, _) = match (&clipboard_buffer, resumed)
+    {
+        (Some(buffer), _) => (Box::new(buffer.clone()), false, false),
+        (None, Some((file, continuing))) => (Box::new(file), continuing, false),
+        (None, None) => (Box::new(stdout()), false, stdout().is_terminal()),
+    };
+    let mut output = Output::new(writer, args.output_format, template);
+    if continuing {
+        output = output.continuing();
+    }
+    if args.pretty && terminal && std::env::var_os("NO_COLOR").is_none() {
+        output = output.pretty();
+    }
+
+    // The results are held until the whole input is processed when they are
+    // sorted, grouped or their aliases are listed, along with the requested
+    // URLs that landed on them.
+    let hold = args.sort.is_some()
+        || args.group_by.is_some()
+        || args.list_aliases
+        || args.mark_duplicate_titles
+        || args.cluster_similar.is_some();
+    let pusher = match args.push {
+        Some(backend) => Some(Pusher::new(backend, &config.push)?),
+        None => None,
+    };
+    let vault = match args.obsidian.clone() {
+        Some(dir) => Some(Vault::new(dir, args.obsidian_daily)?),
+        None => None,
+    };
+    let mut title_groups =
+        (args.duplicate_titles || args.mark_duplicate_titles).then(TitleGroups::default);
+    let mut held: Vec<(Processed, Vec<String>)> = Vec::new();
+    // The normalized final URLs seen so far, with the index of their result in
+    // `held`, if held.
+    let mut finals: HashMap<String, usize> = HashMap::new();
+    let mut collapsed = 0;
+    let mut stats = args.stats.then(Stats::new);
+    let mut failed_output = match &args.failed_output {
+        Some(path) => Some(
+            std::fs::File::create(path)
+                .wrap_err_with(|| format!("failed to create `{}`", path.display()))?,
+        ),
+        None => None,
+    };
+    let (mut attempted, mut failed) = (0, 0);
+    // The failure that aborted the run, with `--fail-fast`. The results so far
+    // are still written.
+    let mut aborted = None;
+    // The entries that failed, to retry them once the others are processed.
+    let mut retries = Vec::new();
+    let mut retrying = false;
+    let abandoned = interrupt::abandoned();
+    tokio::pin!(abandoned);
+    loop {
+        while let Some(outcome) = tokio::select! {
+            outcome = urls_stream.next() => outcome,
+            () = &mut abandoned => None,
+        } {
+            let mut processed = match outcome? {
+                Outcome::Done(processed) => processed,
+                Outcome::Skipped => {
+                    progress::advance(false);
+                    if let Some(stats) = &mut stats {
+                        stats.skip();
+                    }
+                    continue;
+                }
+                Outcome::Failed {
+                    index,
+                    entry,
+                    error,
+                } => {
+                    if args.retry_failed
+                        && !retrying
+                        && args.retry_on.contains(&RetryClass::of(&error))
+                    {
+                        retries.push((index, entry));
+                        continue;
+                    }
+                    let url = entry.url;
+                    progress::advance(true);
+                    if let Some(stats) = &mut stats {
+                        stats.failure(FailureKind::of(&error));
+                    }
+                    attempted += 1;
+                    failed += 1;
+                    if let Some(file) = &mut failed_output {
+                        writeln!(file, "{url}")?;
+                    }
+                    if args.fail_fast {
+                        aborted = Some(error.wrap_err(format!("failed `{url}`")));
+                        break;
+                    }
+                    let retries = u32::from(retrying);
+                    match (&args.error_template, args.error_format) {
+                        (Some(template), _) => {
+                            let cause = error.root_cause().to_string();
+                            progress::suspend(|| output.write_failure(template, &url, &cause))?;
+                        }
+                        (None, ErrorFormat::Text) if output.is_pretty() => {
+                            let cause = error.root_cause().to_string();
+                            progress::suspend(|| output.write_pretty_failure(&url, &cause))?;
+                        }
+                        (None, ErrorFormat::Text) => error!("failed `{url}`: {error:#}"),
+                        (None, ErrorFormat::Json) => {
+                            let record = failure_record(&url, &error, retries);
+                            progress::suspend(|| eprintln!("{record}"));
+                        }
+                        (None, ErrorFormat::Output) => {
+                            let record = failure_record(&url, &error, retries);
+                            progress::suspend(|| output.write_record(&record))?;
+                        }
+                    }
+                    continue;
+                }
+            };
+            attempted += 1;
+            progress::advance(false);
+            if let Some(stats) = &mut stats {
+                stats.success(
+                    &processed.entry.url,
+                    processed.title.is_some(),
+                    processed.bytes,
+                    processed.elapsed,
+                );
+            }
+            let entry = &mut processed.entry;
+            if let Some(form) = args.idn {
+                entry.url = urls::display_host(&entry.url, form);
+            }
+            if processed.title.is_none() && !args.expand_only {
+                info!("no title for `{}`", entry.url);
+            }
+            if entry.field("soft_404") == Some("true") {
+                info!("soft 404 for `{}`", entry.url);
+            }
+            let lang = entry.field("lang");
+            if !args.only_lang.is_empty()
+                && !args.only_lang.iter().any(|l| Some(l.as_str()) == lang)
+            {
+                debug!(
+                    "skipped `{}`, whose title is in another language",
+                    entry.url
+                );
+                if let Some(state) = &mut state {
+                    state.record(&processed.requested)?;
+                }
+                continue;
+            }
+            if args.dedup_final {
+                let key = urls::normalize(&entry.url);
+                if let Some(&i) = finals.get(&key) {
+                    collapsed += 1;
+                    match held.get_mut(i) {
+                        Some((_, aliases)) => aliases.push(processed.requested),
+                        None => {
+                            if let Some(state) = &mut state {
+                                state.record(&processed.requested)?;
+                            }
+                        }
+                    }
+                    continue;
+                }
+                finals.insert(key, held.len());
+            }
+            if let (Some(groups), Some(title)) = (&mut title_groups, &processed.title) {
+                groups.add(title, &processed.entry.url);
+            }
+            if let (Some(pusher), Some(title)) = (&pusher, &processed.title) {
+                let link = Link::new(&processed.entry, title);
+                match pusher.push(&link).await {
+                    Ok(()) => debug!("pushed `{}`", link.url),
+                    Err(error) => warn!("failed to push `{}`: {error:#}", link.url),
+                }
+            }
+            if let (Some(vault), Some(title)) = (&vault, &processed.title) {
+                if let Err(error) = vault.add(&Link::new(&processed.entry, title)) {
+                    warn!("{error:#}");
+                }
+            }
+            if hold {
+                let aliases = vec![processed.requested.clone()];
+                held.push((processed, aliases));
+                continue;
+            }
+            if processed.title.is_some() || !args.skip_when_no_title {
+                progress::suspend(|| output.write(&processed.entry, processed.title.as_deref()))?;
+                if let (Some(buffer), true) = (&clipboard_buffer, args.clipboard_watch) {
+                    clipboard::set_text(buffer.take())?;
+                }
+            }
+            if let Some(state) = &mut state {
+                state.record(&processed.requested)?;
+            }
+        }
+        if interrupt::interrupted() {
+            // The URLs left to retry still failed.
+            for (_, entry) in mem::take(&mut retries) {
+                if let Some(file) = &mut failed_output {
+                    writeln!(file, "{}", entry.url)?;
+                }
+                attempted += 1;
+                failed += 1;
+            }
+        }
+        if retrying || retries.is_empty() || aborted.is_some() {
+            break;
+        }
+        // The URLs that failed are often only temporarily unavailable, so they
+        // are retried with more time and less load on their hosts, still
+        // taking turns between them.
+        info!("retrying {} failed URLs", retries.len());
+        retrying = true;
+        let timeout = args.timeout.map(|timeout| timeout * RETRY_TIMEOUT_FACTOR);
+        let (indexes, entries): (Vec<_>, Vec<_>) = mem::take(&mut retries).into_iter().unzip();
+        urls_stream = Scheduler::new(
+            stream::iter(entries).map(Ok).boxed(),
+            RETRY_CONCURRENCY,
+            args.max_per_host,
+            !args.unordered,
+            move |retry, entry| process(indexes[retry], entry, timeout).boxed(),
+        )
+        .boxed();
+    }
+    progress::finish();
+    if let Err(error) = otlp::export().await {
+        warn!("{error:#}");
+    }
+    if let Some(key) = args.sort {
+        sort_results(&mut held, key);
+    }
+    if let Some(similarity) = args.cluster_similar {
+        cluster_results(&mut held, similarity);
+    }
+    // The groups are sorted by name, and the results within them keep their
+    // order.
+    let group_of = |processed: &Processed| match args.group_by? {
+        GroupBy::Domain => Some(urls::domain(&processed.entry.url)),
+    };
+    held.sort_by_cached_key(|(processed, _)| group_of(processed));
+    let mut current_group = None;
+    for (processed, aliases) in held {
+        let group = group_of(&processed);
+        let Processed {
+            title, mut entry, ..
+        } = processed;
+        if args.list_aliases {
+            entry.fields.push(("aliases".into(), aliases.join(" ")));
+        }
+        if let Some(groups) = title_groups.as_ref().filter(|_| args.mark_duplicate_titles) {
+            let duplicate = title
+                .as_deref()
+                .is_some_and(|title| groups.is_duplicate(title));
+            entry
+                .fields
+                .push(("duplicate_title".into(), duplicate.to_string()));
+        }
+        if title.is_some() || !args.skip_when_no_title {
+            if group.is_some() && group != current_group {
+                output.group(group.as_deref().unwrap_or_default())?;
+                current_group = group;
+            }
+            output.write(&entry, title.as_deref())?;
+        }
+        if let Some(state) = &mut state {
+            for requested in &aliases {
+                state.record(requested)?;
+            }
         }
     }
 
+    output.finish()?;
+    if let Some(stats) = &stats {
+        stats.print();
+    }
+    if let Some(groups) = title_groups.as_ref().filter(|_| args.duplicate_titles) {
+        groups.print();
+    }
+    if collapsed > 0 {
+        info!("collapsed {collapsed} results with the same final URL");
+    }
+    let duplicates = duplicates.load(Ordering::Relaxed);
+    if duplicates > 0 {
+        info!("skipped {duplicates} duplicate URLs");
+    }
+    if let Some(buffer) = clipboard_buffer.filter(|_| !args.clipboard_watch) {
+        clipboard::set_text(buffer.take())?;
+    }
+    if interrupt::interrupted() {
+        return Err(Interrupted {
+            completed: attempted,
+        }
+        .into());
+    }
+    if let Some(error) = aborted {
+        return Err(error.wrap_err(FailedUrls {
+            failed,
+            total: attempted,
+            aborted: true,
+        }));
+    }
+    let percent = failed as f64 * 100.0 / attempted.max(1) as f64;
+    if failed > 0
+        && args
+            .fail_threshold
+            .is_none_or(|threshold| percent > threshold)
+    {
+        return Err(FailedUrls {
+            failed,
+            total: attempted,
+            aborted: false,
+        }
+        .into());
+    }
     Ok(())
 }
 
-/// Given a template, processes it by interpolating the given `title` and `url`
-/// strings. Expects to substitute `%title` and `%url` in the given template.
-fn process_template(template: &str, title: &str, url: &str) -> String {
-    static RE: Lazy<Regex> = Lazy::new(|| Regex::new("%(title|url)").unwrap());
+/// The outcome of an entry of the input.
+enum Outcome {
+    Done(Processed),
+    /// The entry was skipped, such as because robots.txt disallowed it.
+    Skipped,
+    Failed {
+        /// The position of the entry in the input.
+        index: usize,
+        entry: Entry,
+        error: Report,
+    },
+}
 
-    let text = RE.replace_all(template, |cap: &Captures| match &cap[0] {
-        "%title" => title,
-        "%url" => url,
-        _ => unreachable!(),
-    });
+/// How the URLs that fail to load are reported.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// As text, on the standard error.
+    Text,
+    /// As JSON records, one per line, on the standard error.
+    Json,
+    /// As JSON records in the output, among the results. Only supported by
+    /// the `ndjson` output format.
+    Output,
+}
 
-    text.into_owned()
+/// Builds the JSON record that reports the failure of the given URL, which
+/// was retried the given number of times. The message of the error only has
+/// its outermost and innermost causes, since the others tend to repeat them.
+fn failure_record(url: &str, error: &Report, retries: u32) -> Value {
+    let cause = error.root_cause().to_string();
+    let message = match error.to_string() {
+        message if message == cause => message,
+        message => format!("{message}: {cause}"),
+    };
+    json!({
+        "url": url,
+        "error": message,
+        "kind": FailureKind::of(error).name(),
+        "status": fetch::error_status(error),
+        "retries": retries,
+    })
 }
 
-/// Reads the contents of the given path, if it exists. Otherwise, reads from
-/// the standard input.
-async fn read_file_string(path: Option<&Path>) -> Result<String> {
-    async fn read(reader: impl AsyncReadExt) -> Result<String> {
-        let mut buf = String::new();
-        pin!(reader).read_to_string(&mut buf).await?;
-        Ok(buf)
+/// The error of a run that was interrupted, after the results so far were
+/// written.
+#[derive(Debug)]
+struct Interrupted {
+    /// How many URLs were completed, successfully or not.
+    completed: usize,
+}
+
+impl Interrupted {
+    /// The exit code of an interrupted run, like that of processes killed by
+    /// `SIGINT`.
+    const EXIT_CODE: u8 = 130;
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "interrupted after {} URLs", self.completed)
     }
-    match path {
-        Some(path) => read(File::open(path).await?).await,
-        None => read(stdin()).await,
+}
+
+impl std::error::Error for Interrupted {}
+
+/// The error of a run in which some URLs failed to load.
+#[derive(Debug)]
+struct FailedUrls {
+    failed: usize,
+    total: usize,
+    /// Whether the run was aborted by the failure, with `--fail-fast`.
+    aborted: bool,
+}
+
+impl FailedUrls {
+    /// Returns the exit code of the run: 3 if all the URLs failed, or 2 if
+    /// only some of them did.
+    fn exit_code(&self) -> u8 {
+        if self.failed == self.total {
+            3
+        } else {
+            2
+        }
     }
 }
 
-/// Returns an iterator over the non-empty lines of the provided string slice.
-fn non_empty_lines(contents: &str) -> impl Iterator<Item = &str> {
-    contents
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
+impl fmt::Display for FailedUrls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.aborted {
+            write!(f, "aborted after {} URLs", self.total)
+        } else {
+            write!(f, "{} of {} URLs failed", self.failed, self.total)
+        }
+    }
 }
 
-/// Fetches the content of the given URL and retrieves its page title, if it
-/// is present. If there is no title, `None` is returned.
-async fn load_url_and_get_title(url: &str) -> Result<Option<String>> {
-    let html = load_html(url).await?;
-    parse_html_and_get_title(&html).await
+impl std::error::Error for FailedUrls {}
+
+/// An entry of the input, once processed.
+struct Processed {
+    /// The position of the entry in the input.
+    index: usize,
+    title: Option<String>,
+    entry: Entry,
+    /// The requested URL, which the final URL of the entry may have replaced.
+    requested: String,
+    status: Option<u16>,
+    bytes: usize,
+    /// How long the entry took to resolve.
+    elapsed: Duration,
 }
 
-/// Fetches the given URL, returning the full page HTML as a string.
-async fn load_html(url: &str) -> Result<String> {
-    // One doesn't really need this since one's only using the client once.
-    static CLIENT: Lazy<Client> = Lazy::new(|| {
-        ClientBuilder::new()
-            .user_agent("load title tags")
-            .build()
-            .unwrap()
-    });
+/// How the results are sorted.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    /// In the order of the input, even with `--unordered`.
+    Input,
+    /// By title, ignoring case. Results without a title come last.
+    Title,
+    /// By URL.
+    Url,
+    /// By host, ignoring a leading `www.`, then by URL.
+    Domain,
+    /// By status code.
+    Status,
+}
+
+/// How the results are grouped.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// By host, ignoring a leading `www.`.
+    Domain,
+}
+
+/// Sorts the given results, which are first put in the order of the input, so
+/// that ties keep that order.
+fn sort_results(results: &mut [(Processed, Vec<String>)], key: SortKey) {
+    results.sort_by_key(|(processed, _)| processed.index);
+    match key {
+        SortKey::Input => (),
+        SortKey::Title => results.sort_by_cached_key(|(processed, _)| {
+            let title = processed.title.as_deref().map(str::to_lowercase);
+            (title.is_none(), title)
+        }),
+        SortKey::Url => results.sort_by(|(a, _), (b, _)| a.entry.url.cmp(&b.entry.url)),
+        SortKey::Domain => results.sort_by_cached_key(|(processed, _)| {
+            let url = &processed.entry.url;
+            (urls::domain(url), url.clone())
+        }),
+        SortKey::Status => results.sort_by_key(|(processed, _)| processed.status),
+    }
+}
+
+/// Clusters the results whose titles are similar, giving them a `cluster`
+/// field, which is empty for those without a title. The clusters are ordered
+/// by their first result, and the results within them keep their order.
+fn cluster_results(results: &mut Vec<(Processed, Vec<String>)>, similarity: f64) {
+    let mut clusters = Clusters::new(similarity);
+    for title in results
+        .iter()
+        .filter_map(|(processed, _)| processed.title.as_deref())
+    {
+        clusters.add(title);
+    }
+    let mut clusters = clusters.finish().into_iter();
+    // The position of the first result of each cluster, by which they are
+    // ordered, while the results without a title stay where they are.
+    let mut firsts = HashMap::new();
+    let mut keyed: Vec<_> = mem::take(results)
+        .into_iter()
+        .enumerate()
+        .map(|(position, (mut processed, aliases))| {
+            let cluster = processed.title.as_ref().and_then(|_| clusters.next());
+            let key = cluster.map_or(position, |cluster| {
+                *firsts.entry(cluster).or_insert(position)
+            });
+            let field = cluster.map(|cluster| (cluster + 1).to_string());
+            processed
+                .entry
+                .fields
+                .push(("cluster".into(), field.unwrap_or_default()));
+            (key, (processed, aliases))
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    *results = keyed.into_iter().map(|(_, result)| result).collect();
+}
+
+/// Prints the URLs that would be fetched, one per line, preceded by the
+/// settings they would be fetched with.
+async fn dry_run(args: &Args, mut entries: BoxStream<'static, Result<Entry>>) -> Result<()> {
+    let fetch = match args.render {
+        _ if args.expand_only => "following redirects only".to_owned(),
+        Some(RenderMode::Always) => "rendering every page".to_owned(),
+        Some(RenderMode::Auto) => "rendering the pages with placeholder titles".to_owned(),
+        Some(RenderMode::Never) | None => "fetching".to_owned(),
+    };
+    let mut settings = vec![fetch];
+    if let Some(dir) = &args.cache {
+        settings.push(format!(
+            "cache `{}` (TTL {})",
+            dir.display(),
+            humantime::format_duration(args.cache_ttl)
+        ));
+    }
+    if let Some(delay) = args.delay_per_host {
+        settings.push(format!(
+            "delay per host {}",
+            humantime::format_duration(delay)
+        ));
+    }
+    if args.jitter.is_some() {
+        let scope = if args.jitter_per_host {
+            "per host"
+        } else {
+            "global"
+        };
+        settings.push(format!("{scope} jitter"));
+    }
+    let flags = [
+        (args.block_private, "private addresses blocked"),
+        (args.http_fallback, "http fallback"),
+        (args.respect_robots, "robots.txt respected (not checked)"),
+        (args.detect_soft_404, "soft 404 detection"),
+        (args.detect_paywalls, "paywall detection"),
+        (
+            args.detect_language || !args.only_lang.is_empty(),
+            "language detection",
+        ),
+        (args.unwrap_amp, "AMP unwrapping"),
+        (args.expand || args.dedup_final, "expanded URLs"),
+        (args.dedup_final, "deduplicated final URLs"),
+    ];
+    settings.extend(
+        flags
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, setting)| setting.to_owned()),
+    );
+    eprintln!("(dry run: {})", settings.join(", "));
+
+    let mut count = 0;
+    while let Some(entry) = entries.next().await {
+        println!("{}", entry?.url);
+        count += 1;
+    }
+    eprintln!("(dry run: {count} URLs would be fetched)");
+    Ok(())
+}
+
+/// Resolves the given URL with the cache, if any, or the analyzer, failing if
+/// it takes longer than the given timeout. With `expand_only`, only its
+/// redirects are followed.
+async fn resolve(
+    url: &str,
+    cache: Option<&Cache>,
+    analyzer: &Analyzer,
+    expand_only: bool,
+    timeout: Option<Duration>,
+) -> Result<Resolution> {
+    let resolution = resolve_without_timeout(url, cache, analyzer, expand_only);
+    let resolution = match timeout {
+        Some(timeout) => time::timeout(timeout, resolution).await.wrap_err_with(|| {
+            let timeout = humantime::format_duration(timeout);
+            format!("`{url}` timed out after {timeout}")
+        })?,
+        None => resolution.await,
+    }?;
+    // The pages answered with an error status are failures, even if they
+    // have a title, such as that of an error page.
+    if let Some(status) = resolution
+        .status
+        .and_then(|status| StatusCode::from_u16(status).ok())
+    {
+        check_status(status)?;
+    }
+    Ok(resolution)
+}
+
+/// Resolves the given URL like [`resolve`], without a timeout.
+async fn resolve_without_timeout(
+    url: &str,
+    cache: Option<&Cache>,
+    analyzer: &Analyzer,
+    expand_only: bool,
+) -> Result<Resolution> {
+    match cache {
+        _ if expand_only => {
+            let response = probe(url).await?;
+            Ok(Resolution {
+                url: Some(response.url().to_string()),
+                status: Some(response.status().as_u16()),
+                ..Default::default()
+            })
+        }
+        Some(cache) => cache.resolve(url, analyzer).await,
+        None => analyzer.resolve(url).await,
+    }
+}
 
-    CLIENT
-        .get(url)
-        .send()
-        .await
-        .wrap_err_with(|| format!("failed to get: `{url}`"))?
-        .text()
-        .await
-        .map_err(Into::into)
+/// Whether the given result of a URL suggests that its host is overloaded: it
+/// timed out, the connection failed, or the host asked to slow down.
+fn is_overloaded(result: &Result<Resolution>) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(error) => match FailureKind::of(error) {
+            FailureKind::Timeout | FailureKind::Connect => true,
+            _ => matches!(fetch::error_status(error), Some(429 | 503)),
+        },
+    }
+}
+
+/// Whether the given result of a URL is a failure of its host rather than of
+/// the page, such as a 404, so that it counts towards `--break-after`.
+fn is_host_failure(result: &Result<Resolution>) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(error) => match FailureKind::of(error) {
+            FailureKind::Dns | FailureKind::Connect | FailureKind::Timeout => true,
+            _ => fetch::error_status(error).is_some_and(|status| status >= 500),
+        },
+    }
+}
+
+/// Whether the given result of a URL suggests that its page is gone for good:
+/// its host can't be reached, or the page isn't found anymore.
+fn is_gone(result: &Result<Resolution>) -> bool {
+    match result {
+        Ok(_) => false,
+        Err(error) => match FailureKind::of(error) {
+            FailureKind::Dns | FailureKind::Connect => true,
+            _ => matches!(fetch::error_status(error), Some(404 | 410)),
+        },
+    }
+}
+
+/// Whether the given error is a failure to connect to the server, including
+/// TLS errors.
+fn is_connect_error(error: &Report) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(reqwest::Error::is_connect)
 }
 
-/// Parses the given HTML string and retrieves the text of the `title` tag,
-/// if it is present.
-async fn parse_html_and_get_title(html: &str) -> Result<Option<String>> {
-    static SELECTOR: Lazy<Selector> = Lazy::new(|| Selector::parse("title").unwrap());
+/// Reads the input selected by the arguments. Line-based input from a local
+/// file or the standard input is streamed, so that each URL can be processed
+/// as soon as its line is read. Other inputs are read as a whole.
+async fn read_entries(args: &Args) -> Result<BoxStream<'static, Result<Entry>>> {
+    let source_url = args.file.as_deref().and_then(remote_input_url);
 
-    /// Produces a string by iterating over all text nodes. A space character is
-    /// inserted between two text nodes.
-    fn join_text(text: Text<'_>) -> String {
-        let mut s = String::new();
-        for text_node in text {
-            s.push_str(text_node.trim());
-            s.push(' ');
+    if args.clipboard_watch {
+        return Ok(clipboard::watch().boxed());
+    }
+
+    if args.watch {
+        if args.input_format != InputFormat::Lines || source_url.is_some() {
+            bail!("`--watch` requires a local `--file` in the `lines` input format");
         }
-        s.pop();
-        s
+        let path = args.file.clone().unwrap();
+        let sep = if args.read0 { b'\0' } else { b'\n' };
+        return Ok(watch::follow(path, sep).boxed());
+    }
+
+    if args.input_format == InputFormat::Lines && source_url.is_none() && !args.from_clipboard {
+        let reader: Pin<Box<dyn AsyncRead + Send>> = match &args.file {
+            Some(path) => Box::pin(File::open(path).await?),
+            None => Box::pin(stdin()),
+        };
+        let sep = if args.read0 { b'\0' } else { b'\n' };
+        return Ok(input::stream_lines(BufReader::new(reader), sep).boxed());
     }
 
-    let fragment = Html::parse_fragment(html);
+    let input_opts = InputOptions {
+        format: args.input_format,
+        url_column: &args.url_column,
+        base_url: args.base_url.as_ref().or(source_url.as_ref()),
+        same_origin: args.same_origin,
+        since: args.since.map(|since| SystemTime::now() - since),
+        read0: args.read0,
+    };
+    let mut entries = if args.input_format.is_database() {
+        let path = args
+            .file
+            .as_deref()
+            .filter(|_| source_url.is_none())
+            .ok_or_else(|| eyre!("this input format requires a local `--file`"))?;
+        input::read_database(path, &input_opts)?
+    } else if args.input_format == InputFormat::Sitemap {
+        let data = match &source_url {
+            Some(url) => load_bytes(url.as_str()).await?,
+            None => read_file_bytes(args.file.as_deref()).await?,
+        };
+        // The skipped URLs are read too.
+        input::read_sitemap(data, args.limit.map(|limit| limit + args.skip)).await?
+    } else {
+        let contents = match &source_url {
+            _ if args.from_clipboard => clipboard::get_text()?,
+            Some(url) => load_html(url.as_str()).await?,
+            None => read_file_string(args.file.as_deref()).await?,
+        };
+        input::parse(&contents, &input_opts)?
+    };
+    input::align_fields(&mut entries);
+    Ok(stream::iter(entries.into_iter().map(Ok)).boxed())
+}
 
-    let mut elements = fragment.select(&SELECTOR);
-    let fst = elements
-        .next() // Only get the first title tag.
-        .map(|el| join_text(el.text())) // Get full text from html text node.
-        .filter(|title| !title.is_empty()); // Map empty strings to none.
+/// Parses a `--consent-cookie` value.
+fn parse_consent_cookie(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((domain, cookie)) if !domain.is_empty() && !cookie.is_empty() => {
+            Ok((domain.to_ascii_lowercase(), cookie.to_owned()))
+        }
+        _ => Err("expected `DOMAIN=COOKIE`".into()),
+    }
+}
 
-    Ok(fst)
+/// Parses a `--cluster-similar` value.
+fn parse_similarity(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(similarity) if (0.0..=1.0).contains(&similarity) => Ok(similarity),
+        _ => Err("expected a number from 0 to 1".into()),
+    }
+}
+
+/// Returns the URL of the input if the given `--file` path is actually an
+/// `http` or `https` URL.
+fn remote_input_url(path: &Path) -> Option<Url> {
+    let url = Url::parse(path.to_str()?).ok()?;
+    matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+/// Reads the contents of the given path, if it exists. Otherwise, reads from
+/// the standard input.
+async fn read_file_string(path: Option<&Path>) -> Result<String> {
+    let bytes = read_file_bytes(path).await?;
+    String::from_utf8(bytes).wrap_err("input is not valid UTF-8")
+}
+
+/// Reads the raw contents of the given path, if it exists. Otherwise, reads
+/// from the standard input.
+async fn read_file_bytes(path: Option<&Path>) -> Result<Vec<u8>> {
+    async fn read(reader: impl AsyncReadExt) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        pin!(reader).read_to_end(&mut buf).await?;
+        Ok(buf)
+    }
+    match path {
+        Some(path) => read(File::open(path).await?).await,
+        None => read(stdin()).await,
+    }
 }
@@ -0,0 +1,250 @@
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use pulldown_cmark::{Event, LinkType, Parser, Tag, TagEnd};
+use regex::Regex;
+
+/// A bare URL or an autolink of a Markdown document.
+pub struct BareUrl {
+    pub url: String,
+    /// The byte range of the URL in the document, including the angle
+    /// brackets of autolinks.
+    pub span: Range<usize>,
+}
+
+/// Scans the given Markdown document for bare URLs and autolinks
+/// (`<https://...>`). URLs inside code spans, code blocks, HTML, and existing
+/// links are ignored.
+pub fn bare_urls(contents: &str) -> Vec<String> {
+    bare_url_spans(contents)
+        .into_iter()
+        .map(|bare| bare.url)
+        .collect()
+}
+
+/// Like [`bare_urls`], but also returns where each URL is in the document.
+pub fn bare_url_spans(contents: &str) -> Vec<BareUrl> {
+    let mut links = Vec::new();
+    // Nesting depth of the elements whose contents must be left alone.
+    let mut skip_depth = 0_usize;
+    // The parser may split contiguous text into several events, so their
+    // spans are merged before being scanned for URLs.
+    let mut text: Option<Range<usize>> = None;
+
+    let scan = |links: &mut Vec<BareUrl>, text: Range<usize>| {
+        let start = text.start;
+        let text = &contents[text];
+        links.extend(find_urls(text).map(|range| BareUrl {
+            url: text[range.clone()].to_owned(),
+            span: start + range.start..start + range.end,
+        }));
+    };
+
+    for (event, span) in Parser::new(contents).into_offset_iter() {
+        if let Event::Text(_) = event {
+            if skip_depth == 0 {
+                text = match text {
+                    Some(prev) if prev.end == span.start => Some(prev.start..span.end),
+                    Some(prev) => {
+                        scan(&mut links, prev);
+                        Some(span)
+                    }
+                    None => Some(span),
+                };
+            }
+            continue;
+        }
+        if let Some(prev) = text.take() {
+            scan(&mut links, prev);
+        }
+        match event {
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink,
+                dest_url,
+                ..
+            }) if skip_depth == 0 => {
+                links.push(BareUrl {
+                    url: dest_url.into_string(),
+                    span,
+                });
+                skip_depth += 1;
+            }
+            Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => {
+                skip_depth += 1;
+            }
+            Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => {
+                skip_depth = skip_depth.saturating_sub(1);
+            }
+            _ => (),
+        }
+    }
+    if let Some(prev) = text {
+        scan(&mut links, prev);
+    }
+
+    links
+}
+
+/// A link of a Markdown document, as written with `[text](url)` or a reference.
+pub struct Link {
+    pub text: String,
+    pub url: String,
+    /// The 1-based line where the link starts.
+    pub line: usize,
+}
+
+/// Returns the inline and reference links of the given Markdown document.
+/// Autolinks and images are left out, since they have no text of their own.
+pub fn links(contents: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    // The link being read, if any.
+    let mut current: Option<Link> = None;
+
+    for (event, span) in Parser::new(contents).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) if link_type != LinkType::Autolink && link_type != LinkType::Email => {
+                current = Some(Link {
+                    text: String::new(),
+                    url: dest_url.into_string(),
+                    line: contents[..span.start].matches('\n').count() + 1,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(link) = &mut current {
+                    link.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(link) = &mut current {
+                    link.text.push(' ');
+                }
+            }
+            Event::End(TagEnd::Link) => links.extend(current.take()),
+            _ => (),
+        }
+    }
+
+    links
+}
+
+/// Returns the byte ranges of the `http` and `https` URLs in the given text.
+pub fn find_urls(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"https?://[^ \t\r\n<>"'`]+"#).unwrap());
+
+    RE.find_iter(text).filter_map(|m| {
+        let len = trimmed_url_len(m.as_str());
+        (len > 0).then(|| m.start()..m.start() + len)
+    })
+}
+
+/// Returns the length of the given URL candidate after removing trailing
+/// punctuation that is most likely part of the surrounding prose, such as a
+/// final period or an unbalanced closing parenthesis.
+fn trimmed_url_len(url: &str) -> usize {
+    let mut end = url.len();
+    loop {
+        let s = &url[..end];
+        let Some(last) = s.chars().next_back() else {
+            return 0;
+        };
+        let unbalanced =
+            |open, close| last == close && s.matches(close).count() > s.matches(open).count();
+        if ".,;:!?*_~".contains(last) || unbalanced('(', ')') || unbalanced('[', ']') {
+            end -= last.len_utf8();
+        } else {
+            return end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the URLs that [`find_urls`] finds in the given text.
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text).map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn finds_urls_without_the_surrounding_punctuation() {
+        assert_eq!(
+            urls("See https://example.com/a, or (https://example.com/b)."),
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(
+            urls("https://en.wikipedia.org/wiki/Rust_(programming_language)!"),
+            ["https://en.wikipedia.org/wiki/Rust_(programming_language)"]
+        );
+        assert_eq!(
+            urls("\"http://example.com/?q=1\""),
+            ["http://example.com/?q=1"]
+        );
+        assert!(urls("ftp://example.com and https://").is_empty());
+    }
+
+    #[test]
+    fn extracts_bare_urls_and_autolinks() {
+        let contents = "\
+Bare https://a.example/ and <https://b.example/>.
+
+[Linked](https://c.example/) and ![image](https://d.example/i.png)
+`https://e.example/` in code
+
+```
+https://f.example/
+```
+";
+        assert_eq!(
+            bare_urls(contents),
+            ["https://a.example/", "https://b.example/"]
+        );
+    }
+
+    #[test]
+    fn reads_links_with_their_text_and_line() {
+        let contents = "\
+# Title
+
+An [inline `code` link](https://a.example/) and <https://b.example/>,
+a [reference][ref] over
+[two
+lines](https://c.example/) and ![an image](https://d.example/i.png).
+
+[ref]: https://e.example/
+";
+        let links = links(contents);
+        let found: Vec<_> = links
+            .iter()
+            .map(|link| (link.text.as_str(), link.url.as_str(), link.line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("inline code link", "https://a.example/", 3),
+                ("reference", "https://e.example/", 4),
+                ("two lines", "https://c.example/", 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_the_spans_of_bare_urls() {
+        let contents = "- See https://a.example/x. Or <https://b.example/>\n";
+        let spans: Vec<_> = bare_url_spans(contents)
+            .into_iter()
+            .map(|bare| (bare.url, &contents[bare.span]))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("https://a.example/x".to_owned(), "https://a.example/x"),
+                ("https://b.example/".to_owned(), "<https://b.example/>")
+            ]
+        );
+    }
+}
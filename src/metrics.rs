@@ -0,0 +1,91 @@
+use eyre::Result;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use url::Url;
+
+use crate::fetch::load_url_and_get_title;
+
+/// Metrics of the long-lived modes, in the Prometheus format.
+pub struct Metrics {
+    registry: Registry,
+    /// API requests, by endpoint.
+    pub requests: IntCounterVec,
+    pub cache_hits: IntCounter,
+    pub cache_misses: IntCounter,
+    /// Time taken to fetch and parse pages, by host.
+    pub fetch_duration: HistogramVec,
+    /// Failed fetches, by host.
+    pub fetch_errors: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("titlelist".into()), None).unwrap();
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "API requests, by endpoint"),
+            &["endpoint"],
+        )
+        .unwrap();
+        let cache_hits =
+            IntCounter::new("cache_hits_total", "Titles served from the cache").unwrap();
+        let cache_misses =
+            IntCounter::new("cache_misses_total", "Titles not found in the cache").unwrap();
+        let fetch_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "fetch_duration_seconds",
+                "Time taken to fetch pages, by host",
+            ),
+            &["host"],
+        )
+        .unwrap();
+        let fetch_errors = IntCounterVec::new(
+            Opts::new("fetch_errors_total", "Failed fetches, by host"),
+            &["host"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(cache_hits.clone())).unwrap();
+        registry.register(Box::new(cache_misses.clone())).unwrap();
+        registry.register(Box::new(fetch_duration.clone())).unwrap();
+        registry.register(Box::new(fetch_errors.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            cache_hits,
+            cache_misses,
+            fetch_duration,
+            fetch_errors,
+        }
+    }
+
+    /// Fetches the title of the given URL, recording how long it took and
+    /// whether it failed under its host.
+    pub async fn fetch_title(&self, url: &str) -> Result<Option<String>> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        let timer = self
+            .fetch_duration
+            .with_label_values(&[&host])
+            .start_timer();
+        let result = load_url_and_get_title(url).await;
+        timer.observe_duration();
+        if result.is_err() {
+            self.fetch_errors.with_label_values(&[&host]).inc();
+        }
+        result
+    }
+
+    /// Encodes all metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use eyre::{bail, Result, WrapErr};
+
+use crate::{push::Link, rewrite::escape_markdown};
+
+/// The longest file name of a note, in characters, before its extension.
+const NAME_LENGTH: usize = 80;
+
+/// Writes the results to an Obsidian vault, either as a note per result or as
+/// the items of a list in the daily note.
+pub struct Vault {
+    dir: PathBuf,
+    daily: bool,
+}
+
+impl Vault {
+    /// Writes to the vault (or a folder of it) at the given directory, which
+    /// must exist.
+    pub fn new(dir: PathBuf, daily: bool) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("the Obsidian vault `{}` isn't a directory", dir.display());
+        }
+        Ok(Vault { dir, daily })
+    }
+
+    /// Writes the given link to the vault, unless it is already there.
+    pub fn add(&self, link: &Link<'_>) -> Result<()> {
+        let now = SystemTime::now();
+        match self.daily {
+            true => self.append_to_daily_note(link, &local_day(now)),
+            false => self.write_note(link, &humantime::format_rfc3339_seconds(now).to_string()),
+        }
+        .wrap_err_with(|| format!("failed to write `{}` to the Obsidian vault", link.url))
+    }
+
+    /// Writes a note for the given link, named after its title, with its URL,
+    /// when it was fetched and its tags as properties.
+    fn write_note(&self, link: &Link<'_>, fetched: &str) -> Result<()> {
+        // The URL is written as a JSON string, which is also a YAML one.
+        let url = format!("url: {}", serde_json::to_string(link.url)?);
+        let mut note = format!("---\n{url}\nfetched_at: {fetched}\ntags:");
+        match link.tags.as_slice() {
+            [] => note.push_str(" []\n"),
+            tags => {
+                for tag in tags {
+                    // Obsidian tags can't have spaces.
+                    let tag = serde_json::to_string(&tag.replace(' ', "-"))?;
+                    note.push_str(&format!("\n  - {tag}"));
+                }
+                note.push('\n');
+            }
+        }
+        note.push_str(&format!("---\n\n# {}\n", link.title));
+        if let Some(description) = link.description {
+            note.push_str(&format!("\n{description}\n"));
+        }
+        // The notes of other pages with the same title get a number.
+        let slug = slugify(link.title);
+        for n in 1.. {
+            let name = match n {
+                1 => format!("{slug}.md"),
+                n => format!("{slug}-{n}.md"),
+            };
+            let path = self.dir.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => return Ok(file.write_all(note.as_bytes())?),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    if fs::read_to_string(&path)?.lines().any(|line| line == url) {
+                        return Ok(());
+                    }
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+        unreachable!()
+    }
+
+    /// Appends the given link to the list of the daily note of the given day,
+    /// named as in `2024-01-31.md`.
+    fn append_to_daily_note(&self, link: &Link<'_>, day: &str) -> Result<()> {
+        let path = self.dir.join(format!("{day}.md"));
+        // The parentheses would end the link early.
+        let url = link.url.replace('(', "%28").replace(')', "%29");
+        let target = format!("]({url})");
+        match fs::read_to_string(&path) {
+            Ok(note) if note.lines().any(|line| line.ends_with(&target)) => return Ok(()),
+            Ok(_) => (),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error.into()),
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "- [{}]{}", escape_markdown(link.title), &target[1..])?;
+        Ok(())
+    }
+}
+
+/// Returns the day of the given time in the local time zone, as in
+/// `2024-01-31`, which is the one of the daily notes of Obsidian.
+#[cfg(unix)]
+fn local_day(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs()) as libc::time_t;
+    // SAFETY: `localtime_r` only writes to the given `tm`, unlike `localtime`,
+    // which shares it between the calls of all threads.
+    let tm = unsafe {
+        let mut tm = std::mem::zeroed::<libc::tm>();
+        if libc::localtime_r(&secs, &mut tm).is_null() {
+            return utc_day(time);
+        }
+        tm
+    };
+    format!(
+        "{}-{:02}-{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday
+    )
+}
+
+/// Returns the day of the given time, as in `2024-01-31`. The local time zone
+/// isn't known on this platform, so it is the day in UTC.
+#[cfg(not(unix))]
+fn local_day(time: SystemTime) -> String {
+    utc_day(time)
+}
+
+/// Returns the day of the given time in UTC, as in `2024-01-31`.
+fn utc_day(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()[..10].to_owned()
+}
+
+/// Returns the file name of the note of the given title, in lowercase, with
+/// its words separated by dashes.
+fn slugify(title: &str) -> String {
+    let words = title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug: String = words.chars().take(NAME_LENGTH).collect();
+    match slug.trim_end_matches('-') {
+        "" => "untitled".to_owned(),
+        slug => slug.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn slugifies_titles() {
+        assert_eq!(slugify("Hello, World! (2024)"), "hello-world-2024");
+        assert_eq!(slugify("Écoute ça"), "écoute-ça");
+        assert_eq!(slugify("???"), "untitled");
+        assert_eq!(slugify(&"word ".repeat(40)).chars().count(), 79);
+    }
+
+    #[test]
+    fn formats_days() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_706_745_600);
+        assert_eq!(utc_day(time), "2024-02-01");
+    }
+}
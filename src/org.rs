@@ -0,0 +1,187 @@
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::markdown::{find_urls, BareUrl};
+
+/// Scans the given Org document for bare URLs and angle links
+/// (`<https://...>`). URLs inside blocks (such as `#+BEGIN_SRC`), drawers,
+/// keywords, comments, fixed-width lines, verbatim and code markup, and
+/// existing links are ignored.
+pub fn bare_url_spans(contents: &str) -> Vec<BareUrl> {
+    static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"\[\[[^\]]*\](\[[^\]]*\])?\]").unwrap());
+    static DRAWER: Lazy<Regex> = Lazy::new(|| Regex::new(r"^:[A-Za-z0-9_-]+:$").unwrap());
+
+    let mut urls = Vec::new();
+    // The line that ends the block or drawer being skipped, if any.
+    let mut skip_until: Option<String> = None;
+    let mut offset = 0;
+
+    for line in contents.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        let lower = trimmed.to_ascii_lowercase();
+
+        if let Some(end) = &skip_until {
+            if lower.starts_with(end.as_str()) {
+                skip_until = None;
+            }
+            continue;
+        }
+        if let Some(kind) = lower.strip_prefix("#+begin_") {
+            let kind = kind.split_whitespace().next().unwrap_or_default();
+            skip_until = Some(format!("#+end_{kind}"));
+            continue;
+        }
+        if DRAWER.is_match(trimmed) && lower != ":end:" {
+            skip_until = Some(":end:".into());
+            continue;
+        }
+        if trimmed.starts_with("#+") || trimmed.starts_with("# ") || trimmed == "#" {
+            continue;
+        }
+        if trimmed.starts_with(": ") || trimmed == ":" {
+            continue;
+        }
+
+        let mut excluded: Vec<Range<usize>> = LINK.find_iter(line).map(|m| m.range()).collect();
+        excluded.extend(verbatim_spans(line));
+        for range in find_urls(line) {
+            if excluded
+                .iter()
+                .any(|ex| ex.start < range.end && range.start < ex.end)
+            {
+                continue;
+            }
+            let url = line[range.clone()].to_owned();
+            // Angle links are replaced along with their brackets.
+            let angled = line[..range.start].ends_with('<') && line[range.end..].starts_with('>');
+            let span = if angled {
+                start + range.start - 1..start + range.end + 1
+            } else {
+                start + range.start..start + range.end
+            };
+            urls.push(BareUrl { url, span });
+        }
+    }
+
+    urls
+}
+
+/// Returns the byte ranges of the verbatim (`=...=`) and code (`~...~`) markup
+/// of the given line. As in Org, the opening marker follows the start of the
+/// line, a space or an opening punctuation mark, and the closing one is
+/// followed by the end of the line, a space or a punctuation mark, so that the
+/// `=` of query strings aren't markup.
+fn verbatim_spans(line: &str) -> Vec<Range<usize>> {
+    let is_pre = |c: char| c.is_whitespace() || "-({'\"".contains(c);
+    let is_post = |c: char| c.is_whitespace() || "-.,;:!?')}[\"\\".contains(c);
+
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while let Some(marker) = line[start..].chars().next() {
+        let prev = line[..start].chars().next_back();
+        let body = &line[start + 1..];
+        let opens = matches!(marker, '=' | '~')
+            && prev.is_none_or(is_pre)
+            && body.starts_with(|c: char| !c.is_whitespace());
+        let close = body.match_indices(marker).map(|(i, _)| i).find(|&i| {
+            let before = body[..i].chars().next_back();
+            let after = body[i + 1..].chars().next();
+            before.is_some_and(|c| !c.is_whitespace()) && after.is_none_or(is_post)
+        });
+        match close.filter(|_| opens) {
+            Some(close) => {
+                let end = start + 1 + close + 1;
+                spans.push(start..end);
+                start = end;
+            }
+            None => start += marker.len_utf8(),
+        }
+    }
+    spans
+}
+
+/// Formats an Org link with the given URL and description. Brackets can't be
+/// escaped in descriptions, so they are replaced by braces.
+pub fn link(url: &str, description: &str) -> String {
+    let description = description.replace('[', "{").replace(']', "}");
+    format!("[[{url}][{description}]]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the URLs found in the given document.
+    fn urls(contents: &str) -> Vec<String> {
+        bare_url_spans(contents)
+            .into_iter()
+            .map(|bare| bare.url)
+            .collect()
+    }
+
+    #[test]
+    fn finds_urls_with_query_strings() {
+        let contents = "See https://example.com/watch?v=abc&t=10 and https://example.com/plain\n";
+        assert_eq!(
+            urls(contents),
+            [
+                "https://example.com/watch?v=abc&t=10",
+                "https://example.com/plain"
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_verbatim_and_code_markup() {
+        let contents = "Run =curl https://example.com/a= or ~https://example.com/b~, \
+                        not https://example.com/c?x=1.\n";
+        assert_eq!(urls(contents), ["https://example.com/c?x=1"]);
+    }
+
+    #[test]
+    fn skips_links_blocks_and_drawers() {
+        let contents = "\
+[[https://example.com/a][A]] and [[https://example.com/b]]
+#+BEGIN_SRC sh
+curl https://example.com/c
+#+END_SRC
+:PROPERTIES:
+:URL: https://example.com/d
+:END:
+#+TITLE: https://example.com/e
+: https://example.com/f
+<https://example.com/g>
+";
+        assert_eq!(urls(contents), ["https://example.com/g"]);
+    }
+
+    #[test]
+    fn spans_angle_links_with_their_brackets() {
+        let contents = "A <https://example.com/> link";
+        let spans: Vec<_> = bare_url_spans(contents)
+            .into_iter()
+            .map(|b| b.span)
+            .collect();
+        assert_eq!(spans, vec![Range { start: 2, end: 24 }]);
+    }
+
+    #[test]
+    fn finds_verbatim_spans() {
+        assert_eq!(verbatim_spans("=a= b ~c d~"), [0..3, 6..11]);
+        assert_eq!(verbatim_spans("x=1&y=2"), Vec::<Range<usize>>::new());
+        assert_eq!(verbatim_spans("= a ="), Vec::<Range<usize>>::new());
+        assert_eq!(verbatim_spans("(=a=)"), vec![Range { start: 1, end: 4 }]);
+    }
+
+    #[test]
+    fn replaces_brackets_in_descriptions() {
+        assert_eq!(
+            link("https://example.com/", "[Draft] Notes"),
+            "[[https://example.com/][{Draft} Notes]]"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    mem,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+use hyper::client::connect::dns::Name;
+use once_cell::sync::OnceCell;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    Client,
+};
+use serde_json::{json, Value};
+use tracing::{span, Instrument};
+use url::Url;
+
+use crate::log::Line;
+
+/// The spans of the run, if they are exported.
+static RECORDER: OnceCell<Recorder> = OnceCell::new();
+
+thread_local! {
+    /// The spans entered by the current thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the spans of the run, so that they can be exported to the
+/// OpenTelemetry collector at the given URL, such as `http://localhost:4318`,
+/// with [`export`]. Must be called before any span is created, and may only be
+/// called once.
+pub fn enable(endpoint: Url) {
+    let _ = RECORDER.set(Recorder {
+        endpoint,
+        open: Mutex::default(),
+        finished: Mutex::default(),
+    });
+}
+
+/// Returns the recorder of the spans, if they are exported.
+pub fn recorder() -> Option<&'static Recorder> {
+    RECORDER.get()
+}
+
+/// Exports the spans that ended so far to the collector, over OTLP/HTTP with
+/// the JSON encoding, if they are exported.
+pub async fn export() -> Result<()> {
+    let Some(recorder) = recorder() else {
+        return Ok(());
+    };
+    let spans = mem::take(&mut *recorder.finished.lock().unwrap());
+    if spans.is_empty() {
+        return Ok(());
+    }
+    let body = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute("service.name", "titlelist".into())],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "titlelist" },
+                "spans": spans,
+            }],
+        }],
+    });
+    // The shared client may refuse private addresses, where collectors
+    // usually are.
+    let url = recorder.endpoint.join("v1/traces")?;
+    Client::new()
+        .post(url.as_str())
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .wrap_err_with(|| format!("failed to export the traces to `{url}`"))?;
+    Ok(())
+}
+
+/// The spans being recorded, by the IDs given by `tracing`, and those that
+/// ended, in the JSON encoding of OTLP.
+pub struct Recorder {
+    endpoint: Url,
+    open: Mutex<HashMap<u64, OpenSpan>>,
+    finished: Mutex<Vec<Value>>,
+}
+
+/// A span that didn't end yet.
+struct OpenSpan {
+    name: &'static str,
+    trace_id: u128,
+    span_id: u64,
+    parent_span_id: Option<u64>,
+    start: SystemTime,
+    fields: Line,
+    /// How many handles to the span exist.
+    handles: usize,
+}
+
+impl Recorder {
+    /// Starts recording the given span, which gets the given ID.
+    pub fn new_span(&self, id: &span::Id, attrs: &span::Attributes<'_>) {
+        let parent = if let Some(parent) = attrs.parent() {
+            Some(parent.into_u64())
+        } else if attrs.is_contextual() {
+            ENTERED.with(|entered| entered.borrow().last().copied())
+        } else {
+            None
+        };
+        let mut open = self.open.lock().unwrap();
+        let parent = parent.and_then(|parent| open.get(&parent));
+        let mut fields = Line::default();
+        attrs.record(&mut fields);
+        let span = OpenSpan {
+            name: attrs.metadata().name(),
+            trace_id: parent.map_or_else(
+                || random() as u128 | (random() as u128) << 64,
+                |p| p.trace_id,
+            ),
+            span_id: random(),
+            parent_span_id: parent.map(|parent| parent.span_id),
+            start: SystemTime::now(),
+            fields,
+            handles: 1,
+        };
+        open.insert(id.into_u64(), span);
+    }
+
+    /// Records the given values of the fields of a span.
+    pub fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        if let Some(span) = self.open.lock().unwrap().get_mut(&id.into_u64()) {
+            values.record(&mut span.fields);
+        }
+    }
+
+    /// Makes the span the innermost one entered by the current thread.
+    pub fn enter(&self, id: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(id.into_u64()));
+    }
+
+    /// Exits the span on the current thread.
+    pub fn exit(&self, id: &span::Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(at) = entered
+                .iter()
+                .rposition(|&entered| entered == id.into_u64())
+            {
+                entered.remove(at);
+            }
+        });
+    }
+
+    /// Records a new handle to the span.
+    pub fn clone_span(&self, id: &span::Id) {
+        if let Some(span) = self.open.lock().unwrap().get_mut(&id.into_u64()) {
+            span.handles += 1;
+        }
+    }
+
+    /// Drops a handle to the span, which ends once none is left. Returns
+    /// whether it ended.
+    pub fn try_close(&self, id: &span::Id) -> bool {
+        let mut open = self.open.lock().unwrap();
+        let Some(span) = open.get_mut(&id.into_u64()) else {
+            return false;
+        };
+        span.handles -= 1;
+        if span.handles > 0 {
+            return false;
+        }
+        let span = open.remove(&id.into_u64()).unwrap();
+        drop(open);
+        self.finished.lock().unwrap().push(span.into_json());
+        true
+    }
+}
+
+impl OpenSpan {
+    /// Ends the span now, encoding it as OTLP JSON. A span with an `error`
+    /// field has the error status.
+    fn into_json(self) -> Value {
+        let error = self
+            .fields
+            .fields
+            .iter()
+            .find(|(name, _)| name == "error")
+            .map(|(_, error)| error.clone());
+        let attributes: Vec<_> = self
+            .fields
+            .fields
+            .into_iter()
+            .map(|(name, value)| attribute(&name, value))
+            .collect();
+        let mut span = json!({
+            "traceId": format!("{:032x}", self.trace_id),
+            "spanId": format!("{:016x}", self.span_id),
+            "name": self.name,
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(self.start),
+            "endTimeUnixNano": unix_nanos(SystemTime::now()),
+            "attributes": attributes,
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = format!("{parent:016x}").into();
+        }
+        if let Some(error) = error {
+            span["status"] = json!({ "code": 2, "message": error });
+        }
+        span
+    }
+}
+
+/// Resolves host names like the system does, in a `dns` span.
+pub struct TracedResolver;
+
+impl Resolve for TracedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let span = tracing::info_span!("dns", host = name.as_str());
+        Box::pin(
+            async move {
+                let addrs: Vec<SocketAddr> =
+                    tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+                Ok(Box::new(addrs.into_iter()) as Addrs)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Encodes an attribute of a span or a resource.
+fn attribute(key: &str, value: Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({ "boolValue": value }),
+        Value::Number(value) if value.is_f64() => json!({ "doubleValue": value }),
+        Value::Number(value) => json!({ "intValue": value.to_string() }),
+        Value::String(value) => json!({ "stringValue": value }),
+        value => json!({ "stringValue": value.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Returns the given time in nanoseconds since the Unix epoch, as a string
+/// since it may not fit the numbers of JSON.
+fn unix_nanos(time: SystemTime) -> String {
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    nanos.to_string()
+}
+
+/// Returns a random number, for the IDs of traces and spans.
+fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
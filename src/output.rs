@@ -0,0 +1,684 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    io::{self, Write},
+    mem,
+    rc::Rc,
+};
+
+use clap::ValueEnum;
+use eyre::{bail, eyre, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::input::Entry;
+
+/// Title used by the template output when a page has no title.
+pub const NO_TITLE: &str = "@@@ NO TITLE @@@";
+
+/// The width of the title column of the pretty output, in terminal columns.
+/// Longer titles are truncated.
+const TITLE_WIDTH: usize = 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One line per link, formatted with `--template`.
+    Template,
+    /// CSV with a header row: `url`, `title` and the passthrough columns.
+    Csv,
+    /// A single JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// A Netscape bookmarks file. The folder structure is rebuilt from the
+    /// `folder` field, as produced by the `bookmarks` input format.
+    Bookmarks,
+    /// An OPML outline. Entries with an `xml_url` field, as produced by the
+    /// `opml` input format, are written as feeds. Folders are rebuilt from the
+    /// `folder` field.
+    Opml,
+}
+
+/// Writes the results in the selected output format.
+pub struct Output<W: Write> {
+    format: OutputFormat,
+    template: String,
+    inner: Inner<W>,
+    written: usize,
+    /// The passthrough fields of the CSV header, once written, which are
+    /// unknown when continuing an output.
+    columns: Option<Vec<String>>,
+    /// Folders currently open in the bookmarks output.
+    folders: Vec<String>,
+    /// The group of the results being written, if grouped, and how many of
+    /// them were written.
+    group: Option<(String, usize)>,
+    /// Whether the template output is written for people, in colour.
+    pretty: bool,
+}
+
+enum Inner<W: Write> {
+    Raw(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W, format: OutputFormat, template: &str) -> Self {
+        let inner = match format {
+            OutputFormat::Csv => Inner::Csv(Box::new(csv::Writer::from_writer(writer))),
+            _ => Inner::Raw(writer),
+        };
+        Output {
+            format,
+            template: template.to_owned(),
+            inner,
+            written: 0,
+            columns: None,
+            folders: Vec::new(),
+            group: None,
+            pretty: false,
+        }
+    }
+
+    /// Writes the template output for people, such as to a terminal, rather
+    /// than with the template: each result gets a coloured marker of whether
+    /// it has a title, and the titles and URLs are aligned in columns.
+    pub fn pretty(mut self) -> Self {
+        self.pretty = self.format == OutputFormat::Template;
+        self
+    }
+
+    /// Whether the output is written for people, as set by [`Output::pretty`].
+    pub fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    /// Continues an existing output that already has results, such as a
+    /// resumed output file, so that the CSV header isn't written again. Only
+    /// the formats that can be appended to are supported.
+    pub fn continuing(mut self) -> Self {
+        self.written = self.written.max(1);
+        self
+    }
+
+    /// Starts a group of results, which the following ones belong to. The
+    /// template output writes a Markdown heading before them, the JSON output
+    /// nests them under the name of the group, and the bookmarks and OPML
+    /// outputs put them in a top-level folder. The CSV and NDJSON outputs,
+    /// which can't nest, give them a `group` field.
+    pub fn group(&mut self, name: &str) -> Result<()> {
+        if let Inner::Raw(w) = &mut self.inner {
+            match self.format {
+                OutputFormat::Template => {
+                    let sep = if self.written == 0 { "" } else { "\n" };
+                    match self.pretty {
+                        true => writeln!(w, "{sep}\x1b[1m{name}\x1b[0m\n")?,
+                        false => writeln!(w, "{sep}## {name}\n")?,
+                    }
+                }
+                OutputFormat::Json => {
+                    let sep = match &self.group {
+                        None => "{\n",
+                        Some((_, 0)) => "],\n",
+                        Some(_) => "\n],\n",
+                    };
+                    writeln!(w, "{sep}{}: [", Value::from(name))?;
+                }
+                _ => (),
+            }
+        }
+        self.group = Some((name.to_owned(), 0));
+        Ok(())
+    }
+
+    /// Writes a single result. `title` is `None` if the page has no title.
+    pub fn write(&mut self, entry: &Entry, title: Option<&str>) -> Result<()> {
+        let group = self.group.as_ref().map(|(name, _)| name.as_str());
+        match &mut self.inner {
+            Inner::Csv(csv) => {
+                let group_column = group.map(|_| "group");
+                if self.written == 0 {
+                    let columns: Vec<_> = entry.fields.iter().map(|(k, _)| k.clone()).collect();
+                    let header = ["url", "title"]
+                        .into_iter()
+                        .chain(columns.iter().map(String::as_str))
+                        .chain(group_column);
+                    csv.write_record(header)?;
+                    self.columns = Some(columns);
+                }
+                // The fields are written under the columns of the header, and
+                // those that it lacks are left out, since the columns of a CSV
+                // output can't change.
+                let fields: Vec<_> = match &self.columns {
+                    Some(columns) => columns
+                        .iter()
+                        .map(|name| entry.field(name).unwrap_or_default())
+                        .collect(),
+                    None => entry.fields.iter().map(|(_, v)| v.as_str()).collect(),
+                };
+                let row = [entry.url.as_str(), title.unwrap_or_default()]
+                    .into_iter()
+                    .chain(fields)
+                    .chain(group);
+                csv.write_record(row)?;
+            }
+            Inner::Raw(w) => match self.format {
+                OutputFormat::Template if self.pretty => match title {
+                    Some(title) => {
+                        writeln!(w, "\x1b[32m✓\x1b[0m {}  {}", column(title), entry.url)?
+                    }
+                    None => writeln!(
+                        w,
+                        "\x1b[33m?\x1b[0m \x1b[2m{}\x1b[0m  {}",
+                        column("(no title)"),
+                        entry.url
+                    )?,
+                },
+                OutputFormat::Template => {
+                    let text = process_entry_template(&self.template, title, entry);
+                    writeln!(w, "{text}")?;
+                }
+                OutputFormat::Json => {
+                    let sep = match &self.group {
+                        Some((_, 0)) => "",
+                        Some(_) => ",\n",
+                        None if self.written == 0 => "[\n",
+                        None => ",\n",
+                    };
+                    write!(w, "{sep}{}", to_json(entry, title))?;
+                }
+                OutputFormat::Ndjson => {
+                    let mut json = to_json(entry, title);
+                    if let Some(group) = group {
+                        json["group"] = group.into();
+                    }
+                    writeln!(w, "{json}")?;
+                }
+                OutputFormat::Bookmarks | OutputFormat::Opml => {
+                    if self.written == 0 {
+                        w.write_all(tree_header(self.format).as_bytes())?;
+                    }
+                    let folders: Vec<_> = group.into_iter().chain(entry.folders()).collect();
+                    enter_folders(w, self.format, &mut self.folders, &folders)?;
+                    let depth = self.folders.len() + 1;
+                    if self.format == OutputFormat::Bookmarks {
+                        write_bookmark(w, depth, entry, title)?;
+                    } else {
+                        write_outline(w, depth, entry, title)?;
+                    }
+                }
+                OutputFormat::Csv => unreachable!(),
+            },
+        }
+        // Results are flushed as they come, so that the output can be consumed
+        // while the input is still being processed.
+        match &mut self.inner {
+            Inner::Csv(csv) => csv.flush()?,
+            Inner::Raw(w) => w.flush()?,
+        }
+        self.written += 1;
+        if let Some((_, written)) = &mut self.group {
+            *written += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes a URL that failed to load with the given template, in which
+    /// `%url` and `%error` are replaced. Only supported by the template
+    /// output format.
+    pub fn write_failure(&mut self, template: &str, url: &str, error: &str) -> Result<()> {
+        let (Inner::Raw(w), OutputFormat::Template) = (&mut self.inner, self.format) else {
+            bail!("failures can only be written with the template output format");
+        };
+        writeln!(w, "{}", process_error_template(template, url, error))?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Writes a URL that failed to load among the results, with the given
+    /// error dimmed. Only supported by the pretty output.
+    pub fn write_pretty_failure(&mut self, url: &str, error: &str) -> Result<()> {
+        let (Inner::Raw(w), true) = (&mut self.inner, self.pretty) else {
+            bail!("failures can only be written among the results by the pretty output");
+        };
+        writeln!(w, "\x1b[31m✗\x1b[0m {}  \x1b[2m{error}\x1b[0m", column(url))?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Writes the given JSON record among the results, such as one that
+    /// reports a failure. Only supported by the NDJSON output format.
+    pub fn write_record(&mut self, record: &Value) -> Result<()> {
+        let (Inner::Raw(w), OutputFormat::Ndjson) = (&mut self.inner, self.format) else {
+            bail!("records can only be written with the NDJSON output format");
+        };
+        writeln!(w, "{record}")?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Finishes the output, writing any trailing data and flushing the
+    /// underlying writer.
+    pub fn finish(mut self) -> Result<()> {
+        match self.inner {
+            Inner::Csv(mut csv) => csv.flush()?,
+            Inner::Raw(mut w) => {
+                match self.format {
+                    OutputFormat::Json => {
+                        let text = match &self.group {
+                            Some((_, 0)) => "]\n}\n",
+                            Some(_) => "\n]\n}\n",
+                            None if self.written == 0 => "[]\n",
+                            None => "\n]\n",
+                        };
+                        w.write_all(text.as_bytes())?;
+                    }
+                    OutputFormat::Bookmarks | OutputFormat::Opml => {
+                        if self.written == 0 {
+                            w.write_all(tree_header(self.format).as_bytes())?;
+                        }
+                        enter_folders(&mut w, self.format, &mut self.folders, &[])?;
+                        w.write_all(tree_footer(self.format).as_bytes())?;
+                    }
+                    _ => (),
+                }
+                w.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pads or truncates the given text to the width of the title column of the
+/// pretty output.
+fn column(text: &str) -> String {
+    let width = text.width();
+    if width <= TITLE_WIDTH {
+        return format!("{text}{}", " ".repeat(TITLE_WIDTH - width));
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        width += c.width().unwrap_or(0);
+        if width >= TITLE_WIDTH {
+            break;
+        }
+        truncated.push(c);
+    }
+    let width = truncated.width() + 1;
+    format!("{truncated}…{}", " ".repeat(TITLE_WIDTH - width))
+}
+
+/// Returns the URLs of the results in the given output, as written by
+/// [`Output`] with the same format and template. Fails for the formats that
+/// can't be appended to.
+pub fn parse_urls(contents: &str, format: OutputFormat, template: &str) -> Result<HashSet<String>> {
+    let mut urls = HashSet::new();
+    match format {
+        OutputFormat::Template | OutputFormat::Csv | OutputFormat::Ndjson
+            if contents.is_empty() => {}
+        OutputFormat::Template => {
+            let re = template_regex(template)?;
+            for line in contents.lines() {
+                if let Some(caps) = re.captures(line) {
+                    urls.insert(caps["url"].to_owned());
+                }
+            }
+        }
+        OutputFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(contents.as_bytes());
+            let column = reader
+                .headers()?
+                .iter()
+                .position(|name| name == "url")
+                .ok_or_else(|| eyre!("the output has no `url` column"))?;
+            for record in reader.records() {
+                if let Some(url) = record?.get(column) {
+                    urls.insert(url.to_owned());
+                }
+            }
+        }
+        OutputFormat::Ndjson => {
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                let value: Value = serde_json::from_str(line)?;
+                if let Some(url) = value["url"].as_str() {
+                    urls.insert(url.to_owned());
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bookmarks | OutputFormat::Opml => {
+            bail!("this output format can't be appended to; use `ndjson` instead of `json`")
+        }
+    }
+    Ok(urls)
+}
+
+/// Builds a regex that matches the lines produced by the given template,
+/// capturing the URL.
+fn template_regex(template: &str) -> Result<Regex> {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new("%[a-z_]+").unwrap());
+
+    let mut pattern = String::from("^");
+    let mut has_url = false;
+    let mut last = 0;
+    for m in RE.find_iter(template) {
+        pattern.push_str(&regex::escape(&template[last..m.start()]));
+        match m.as_str() {
+            "%url" if !has_url => {
+                pattern.push_str("(?P<url>.+?)");
+                has_url = true;
+            }
+            _ => pattern.push_str(".*?"),
+        }
+        last = m.end();
+    }
+    pattern.push_str(&regex::escape(&template[last..]));
+    pattern.push('$');
+
+    if !has_url {
+        bail!("the template has no `%url`, so its URLs can't be read back");
+    }
+    Ok(Regex::new(&pattern)?)
+}
+
+/// An in-memory writer whose contents can be taken while another handle to it
+/// is still being written to.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Takes the contents written so far, leaving the buffer empty.
+    pub fn take(&self) -> String {
+        let bytes = mem::take(&mut *self.0.borrow_mut());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the text that precedes the links of the formats that nest them in
+/// folders.
+fn tree_header(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Bookmarks => {
+            "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+             <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+             <TITLE>Bookmarks</TITLE>\n\
+             <H1>Bookmarks</H1>\n\
+             <DL><p>\n"
+        }
+        OutputFormat::Opml => {
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <opml version=\"2.0\">\n\
+             <head>\n\
+             \x20   <title>titlelist</title>\n\
+             </head>\n\
+             <body>\n"
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the text that follows the links of the formats that nest them in
+/// folders.
+fn tree_footer(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Bookmarks => "</DL><p>\n",
+        OutputFormat::Opml => "</body>\n</opml>\n",
+        _ => unreachable!(),
+    }
+}
+
+/// Closes and opens the folders needed to go from the currently open folders
+/// to the given ones.
+fn enter_folders(
+    w: &mut impl Write,
+    format: OutputFormat,
+    open: &mut Vec<String>,
+    folders: &[&str],
+) -> Result<()> {
+    let common = open
+        .iter()
+        .zip(folders)
+        .take_while(|(a, b)| a == *b)
+        .count();
+    while open.len() > common {
+        let i = indent(open.len());
+        match format {
+            OutputFormat::Bookmarks => writeln!(w, "{i}</DL><p>")?,
+            _ => writeln!(w, "{i}</outline>")?,
+        }
+        open.pop();
+    }
+    for folder in &folders[common..] {
+        let i = indent(open.len() + 1);
+        let name = escape_html(folder);
+        match format {
+            OutputFormat::Bookmarks => writeln!(w, "{i}<DT><H3>{name}</H3>\n{i}<DL><p>")?,
+            _ => writeln!(w, "{i}<outline text=\"{name}\">")?,
+        }
+        open.push((*folder).to_owned());
+    }
+    Ok(())
+}
+
+/// Writes a bookmark at the given nesting depth.
+fn write_bookmark(
+    w: &mut impl Write,
+    depth: usize,
+    entry: &Entry,
+    title: Option<&str>,
+) -> Result<()> {
+    write!(
+        w,
+        "{}<DT><A HREF=\"{}\"",
+        indent(depth),
+        escape_html(&entry.url)
+    )?;
+    for (k, v) in &entry.fields {
+        if matches!(k.as_str(), "add_date" | "last_modified" | "tags") {
+            write!(w, " {}=\"{}\"", k.to_uppercase(), escape_html(v))?;
+        }
+    }
+    writeln!(w, ">{}</A>", escape_html(title.unwrap_or(&entry.url)))?;
+    Ok(())
+}
+
+/// Writes an OPML outline at the given nesting depth. Entries that have a feed
+/// URL are written as `rss` outlines and the others as `link` outlines.
+fn write_outline(
+    w: &mut impl Write,
+    depth: usize,
+    entry: &Entry,
+    title: Option<&str>,
+) -> Result<()> {
+    let text = escape_html(title.unwrap_or(&entry.url));
+    let url = escape_html(&entry.url);
+    write!(
+        w,
+        "{}<outline text=\"{text}\" title=\"{text}\"",
+        indent(depth)
+    )?;
+    match entry.field("xml_url") {
+        Some(xml_url) => {
+            write!(w, " type=\"rss\" xmlUrl=\"{}\"", escape_html(xml_url))?;
+            if xml_url != entry.url {
+                write!(w, " htmlUrl=\"{url}\"")?;
+            }
+        }
+        None => write!(w, " type=\"link\" url=\"{url}\"")?,
+    }
+    writeln!(w, "/>")?;
+    Ok(())
+}
+
+/// Returns the indentation for the given nesting depth.
+fn indent(depth: usize) -> String {
+    "    ".repeat(depth)
+}
+
+/// Escapes the characters that have a special meaning in HTML text and
+/// attribute values.
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Builds the JSON object for the given result. Passthrough fields are added
+/// after `url` and `title`.
+fn to_json(entry: &Entry, title: Option<&str>) -> Value {
+    let mut map = Map::new();
+    map.insert("url".into(), entry.url.clone().into());
+    map.insert("title".into(), title.into());
+    for (k, v) in &entry.fields {
+        map.entry(k.clone()).or_insert_with(|| v.clone().into());
+    }
+    Value::Object(map)
+}
+
+/// Given a template, processes it by interpolating the given `title` and `url`
+/// strings. Expects to substitute `%title` and `%url` in the given template.
+pub fn process_template(template: &str, title: &str, url: &str) -> String {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new("%(title|url)").unwrap());
+
+    let text = RE.replace_all(template, |cap: &Captures| match &cap[0] {
+        "%title" => title,
+        "%url" => url,
+        _ => unreachable!(),
+    });
+
+    text.into_owned()
+}
+
+/// Processes a template like [`process_template`], also interpolating the
+/// fields of the given entry in place of `%` and their name, such as
+/// `%author`. The other placeholders are kept as they are.
+fn process_entry_template(template: &str, title: Option<&str>, entry: &Entry) -> String {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new("%([a-z_]+)").unwrap());
+
+    let text = RE.replace_all(template, |cap: &Captures| match &cap[1] {
+        "title" => title.unwrap_or(NO_TITLE).to_owned(),
+        "url" => entry.url.clone(),
+        name => entry.field(name).unwrap_or(&cap[0]).to_owned(),
+    });
+
+    text.into_owned()
+}
+
+/// Processes a template of failures by interpolating the given `url` and
+/// `error` strings in place of `%url` and `%error`.
+fn process_error_template(template: &str, url: &str, error: &str) -> String {
+    static RE: Lazy<Regex> = Lazy::new(|| Regex::new("%(url|error)").unwrap());
+
+    let text = RE.replace_all(template, |cap: &Captures| match &cap[0] {
+        "%url" => url,
+        "%error" => error,
+        _ => unreachable!(),
+    });
+
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an entry with the given passthrough fields.
+    fn entry(url: &str, fields: &[(&str, &str)]) -> Entry {
+        Entry {
+            url: url.to_owned(),
+            fields: fields
+                .iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Writes the given results in the given format, returning the output.
+    fn write(format: OutputFormat, results: &[(Entry, Option<&str>)]) -> String {
+        let mut buffer = Vec::new();
+        let mut output = Output::new(&mut buffer, format, "%title <%url>");
+        for (entry, title) in results {
+            output.write(entry, *title).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn writes_csv_fields_under_their_columns() {
+        let results = [
+            (
+                entry("https://a.example/", &[("tags", "x"), ("lang", "en")]),
+                Some("A"),
+            ),
+            (entry("https://b.example/", &[("lang", "fr")]), None),
+            (
+                entry("https://c.example/", &[("lang", "de"), ("tags", "y,z")]),
+                Some("C, the"),
+            ),
+        ];
+        assert_eq!(
+            write(OutputFormat::Csv, &results),
+            "url,title,tags,lang\n\
+             https://a.example/,A,x,en\n\
+             https://b.example/,,,fr\n\
+             https://c.example/,\"C, the\",\"y,z\",de\n"
+        );
+    }
+
+    #[test]
+    fn writes_csv_groups_as_a_column() {
+        let mut buffer = Vec::new();
+        let mut output = Output::new(&mut buffer, OutputFormat::Csv, "");
+        output.group("a.example").unwrap();
+        output
+            .write(&entry("https://a.example/", &[]), Some("A"))
+            .unwrap();
+        output.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "url,title,group\nhttps://a.example/,A,a.example\n"
+        );
+    }
+
+    #[test]
+    fn writes_templates() {
+        let results = [
+            (entry("https://a.example/", &[]), Some("A")),
+            (entry("https://b.example/", &[]), None),
+        ];
+        assert_eq!(
+            write(OutputFormat::Template, &results),
+            "A <https://a.example/>\n@@@ NO TITLE @@@ <https://b.example/>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_html() {
+        // The attributes are quoted with double quotes.
+        assert_eq!(
+            escape_html(r#"<a href="x">&'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;'"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use eyre::{bail, Result, WrapErr};
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE, USER_AGENT},
+    Client,
+};
+use url::Url;
+
+use crate::{config, fetch, render::RenderMode, urls};
+
+/// The overrides of the requests to some URLs, from the rules of the
+/// configuration file.
+static OVERRIDES: OnceCell<Vec<Override>> = OnceCell::new();
+
+/// The overrides of the requests to the URLs that match a rule.
+struct Override {
+    matcher: Matcher,
+    headers: HeaderMap,
+    user_agent: Option<String>,
+    /// The proxy, and the client that goes through it.
+    proxy: Option<(String, Client)>,
+    render: Option<RenderMode>,
+}
+
+/// Which URLs a rule applies to.
+enum Matcher {
+    /// A domain and its subdomains.
+    Domain(String),
+    /// A glob of the hosts, with `*` standing for any characters.
+    Glob(Regex),
+    /// A regular expression of the whole URLs.
+    Pattern(Regex),
+}
+
+impl Matcher {
+    /// Whether the given URL matches.
+    fn matches(&self, url: &str) -> bool {
+        let host = || {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_owned))
+                .unwrap_or_default()
+        };
+        match self {
+            Matcher::Domain(domain) => urls::is_within(&host(), domain),
+            Matcher::Glob(glob) => glob.is_match(&host()),
+            Matcher::Pattern(pattern) => pattern.is_match(url),
+        }
+    }
+}
+
+/// Sets the rules whose overrides apply to the requests of their URLs. Must be
+/// called after [`fetch::block_private`], and may only be called once.
+pub fn set(rules: &[config::Rule]) -> Result<()> {
+    let overrides = rules.iter().map(compile).collect::<Result<_>>()?;
+    let _ = OVERRIDES.set(overrides);
+    Ok(())
+}
+
+/// Compiles the given rule.
+fn compile(rule: &config::Rule) -> Result<Override> {
+    let matcher = match (&rule.domain, &rule.pattern) {
+        (Some(domain), None) if domain.contains('*') => {
+            let glob = domain
+                .split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".*");
+            Matcher::Glob(Regex::new(&format!("^{glob}$"))?)
+        }
+        (Some(domain), None) => Matcher::Domain(domain.clone()),
+        (None, Some(pattern)) => Matcher::Pattern(
+            Regex::new(pattern).wrap_err_with(|| format!("invalid pattern `{pattern}`"))?,
+        ),
+        _ => bail!("rules need either a `domain` or a `pattern`"),
+    };
+    let mut headers = HeaderMap::new();
+    for (name, value) in &rule.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .wrap_err_with(|| format!("invalid header name `{name}`"))?;
+        let value = HeaderValue::from_str(value)
+            .wrap_err_with(|| format!("invalid value of the header `{name}`"))?;
+        headers.insert(name, value);
+    }
+    if let Some(cookie) = &rule.cookie {
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_str(cookie).wrap_err("invalid cookie")?,
+        );
+    }
+    if let Some(user_agent) = &rule.user_agent {
+        let value = HeaderValue::from_str(user_agent).wrap_err("invalid user agent")?;
+        headers.insert(USER_AGENT, value);
+    }
+    let proxy = match &rule.proxy {
+        Some(proxy) => {
+            let client = fetch::build_client(Some(proxy))
+                .wrap_err_with(|| format!("invalid proxy `{proxy}`"))?;
+            Some((proxy.clone(), client))
+        }
+        None => None,
+    };
+    Ok(Override {
+        matcher,
+        headers,
+        user_agent: rule.user_agent.clone(),
+        proxy,
+        render: rule.render,
+    })
+}
+
+/// Returns the overrides that apply to the given URL, in the order of their
+/// rules, so that the later ones take precedence.
+fn matching(url: &str) -> impl DoubleEndedIterator<Item = &'static Override> + '_ {
+    OVERRIDES
+        .get()
+        .into_iter()
+        .flatten()
+        .filter(move |rule| rule.matcher.matches(url))
+}
+
+/// Returns the headers sent to the given URL, which replace the default ones.
+pub fn headers(url: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for rule in matching(url) {
+        headers.extend(rule.headers.clone());
+    }
+    headers
+}
+
+/// Returns the client that goes through the proxy of the given URL, if any.
+pub fn client(url: &str) -> Option<&'static Client> {
+    let (_, client) = matching(url).rev().find_map(|rule| rule.proxy.as_ref())?;
+    Some(client)
+}
+
+/// Returns the proxy of the given URL, if any.
+pub fn proxy(url: &str) -> Option<&'static str> {
+    let (proxy, _) = matching(url).rev().find_map(|rule| rule.proxy.as_ref())?;
+    Some(proxy)
+}
+
+/// Returns the user agent sent to the given URL, if overridden.
+pub fn user_agent(url: &str) -> Option<&'static str> {
+    matching(url)
+        .rev()
+        .find_map(|rule| rule.user_agent.as_deref())
+}
+
+/// Returns how the page of the given URL is rendered, if overridden.
+pub fn render_mode(url: &str) -> Option<RenderMode> {
+    matching(url).rev().find_map(|rule| rule.render)
+}
+
+/// Whether some rules render their pages, which needs a browser.
+pub fn renders() -> bool {
+    OVERRIDES
+        .get()
+        .into_iter()
+        .flatten()
+        .any(|rule| matches!(rule.render, Some(RenderMode::Always | RenderMode::Auto)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Compiles the rule described by the given JSON.
+    fn rule(rule: serde_json::Value) -> Result<Override> {
+        compile(&serde_json::from_value(rule).unwrap())
+    }
+
+    #[test]
+    fn matches_domains_with_their_subdomains() {
+        let matcher = rule(json!({ "domain": "example.com" })).unwrap().matcher;
+        assert!(matcher.matches("https://example.com/a"));
+        assert!(matcher.matches("https://www.example.com/a"));
+        assert!(!matcher.matches("https://badexample.com/a"));
+        assert!(!matcher.matches("https://example.com.evil/a"));
+    }
+
+    #[test]
+    fn matches_globs_of_the_hosts() {
+        let matcher = rule(json!({ "domain": "*.example.com" })).unwrap().matcher;
+        assert!(matcher.matches("https://www.example.com/a"));
+        assert!(matcher.matches("https://a.b.example.com/a"));
+        assert!(!matcher.matches("https://example.com/a"));
+        assert!(!matcher.matches("https://www.example.com.evil/a"));
+    }
+
+    #[test]
+    fn matches_patterns_of_the_whole_urls() {
+        let matcher = rule(json!({ "pattern": "^https://example\\.com/news/" }))
+            .unwrap()
+            .matcher;
+        assert!(matcher.matches("https://example.com/news/1"));
+        assert!(!matcher.matches("https://example.com/blog/1"));
+    }
+
+    #[test]
+    fn gathers_the_headers_of_rules() {
+        let rule = rule(json!({
+            "domain": "example.com",
+            "headers": { "Accept-Language": "pt-BR" },
+            "cookie": "consent=yes",
+            "user_agent": "Bot/1.0",
+        }))
+        .unwrap();
+        assert_eq!(rule.headers["accept-language"], "pt-BR");
+        assert_eq!(rule.headers[COOKIE], "consent=yes");
+        assert_eq!(rule.headers[USER_AGENT], "Bot/1.0");
+        assert_eq!(rule.user_agent.as_deref(), Some("Bot/1.0"));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(rule(json!({})).is_err());
+        assert!(rule(json!({ "domain": "example.com", "pattern": "." })).is_err());
+        assert!(rule(json!({ "pattern": "(" })).is_err());
+        assert!(rule(json!({ "domain": "example.com", "headers": { "a b": "c" } })).is_err());
+    }
+}
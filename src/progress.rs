@@ -0,0 +1,106 @@
+use std::{
+    io::{stderr, IsTerminal, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How often the progress line is redrawn at most.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The width of the bar of the progress line, in characters.
+const BAR_WIDTH: usize = 24;
+
+/// The progress line, while it is shown.
+static BAR: Mutex<Option<Bar>> = Mutex::new(None);
+
+/// The progress of a run, drawn as a line on the standard error.
+struct Bar {
+    /// The number of URLs of the run, if known.
+    total: Option<usize>,
+    completed: usize,
+    failed: usize,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Bar {
+    /// Draws the progress line over the previous one.
+    fn draw(&mut self) {
+        let elapsed = self.started.elapsed();
+        let rate = self.completed as f64 / elapsed.as_secs_f64().max(0.001);
+        let mut line = String::new();
+        match self.total {
+            Some(total) => {
+                let done = (self.completed * BAR_WIDTH).checked_div(total).unwrap_or(0);
+                let done = done.min(BAR_WIDTH);
+                line.push('[');
+                line.push_str(&"=".repeat(done));
+                line.push_str(&" ".repeat(BAR_WIDTH - done));
+                line.push_str(&format!("] {}/{total}", self.completed));
+            }
+            None => line.push_str(&self.completed.to_string()),
+        }
+        line.push_str(&format!(" URLs, {} failed, {rate:.1}/s", self.failed));
+        if let Some(total) = self.total.filter(|_| rate > 0.0) {
+            let left = total.saturating_sub(self.completed) as f64 / rate;
+            let eta = humantime::format_duration(Duration::from_secs(left.ceil() as u64));
+            line.push_str(&format!(", ETA {eta}"));
+        }
+        let _ = write!(stderr(), "\r\x1b[2K{line}");
+        self.drawn = Some(Instant::now());
+    }
+}
+
+/// Starts showing the progress of a run over the given number of URLs, if
+/// known, unless the standard error isn't a terminal.
+pub fn start(total: Option<usize>) {
+    if !stderr().is_terminal() {
+        return;
+    }
+    let mut bar = Bar {
+        total,
+        completed: 0,
+        failed: 0,
+        started: Instant::now(),
+        drawn: None,
+    };
+    bar.draw();
+    *BAR.lock().unwrap() = Some(bar);
+}
+
+/// Records a completed URL, which may have failed.
+pub fn advance(failed: bool) {
+    let mut bar = BAR.lock().unwrap();
+    let Some(bar) = bar.as_mut() else {
+        return;
+    };
+    bar.completed += 1;
+    bar.failed += usize::from(failed);
+    if bar
+        .drawn
+        .is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL)
+    {
+        bar.draw();
+    }
+}
+
+/// Runs the given function, which prints to the standard error, with the
+/// progress line cleared, drawing it again afterwards.
+pub fn suspend<T>(f: impl FnOnce() -> T) -> T {
+    let mut bar = BAR.lock().unwrap();
+    if bar.is_some() {
+        let _ = write!(stderr(), "\r\x1b[2K");
+    }
+    let result = f();
+    if let Some(bar) = bar.as_mut() {
+        bar.draw();
+    }
+    result
+}
+
+/// Stops showing the progress, clearing its line.
+pub fn finish() {
+    if BAR.lock().unwrap().take().is_some() {
+        let _ = write!(stderr(), "\r\x1b[2K");
+    }
+}
@@ -0,0 +1,185 @@
+use std::{env, time::Duration};
+
+use clap::ValueEnum;
+use eyre::Result;
+use serde::Deserialize;
+use tokio::{
+    sync::Mutex,
+    time::{self, Instant},
+};
+
+use crate::input::Entry;
+
+mod linkding;
+mod notion;
+mod pinboard;
+mod raindrop;
+mod shaarli;
+mod wallabag;
+
+/// The services that the results can be pushed to, mostly bookmark managers.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PushBackend {
+    /// linkding, which is self-hosted.
+    Linkding,
+    /// A Notion database, whose rows are the results.
+    Notion,
+    Pinboard,
+    /// Raindrop.io.
+    Raindrop,
+    /// Shaarli, which is self-hosted.
+    Shaarli,
+    /// Wallabag, which saves the pages to be read later.
+    Wallabag,
+}
+
+/// The `[push]` table of the configuration file, with a table per service, as
+/// in `[push.pinboard]`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    pub linkding: Option<linkding::Config>,
+    pub notion: Option<notion::Config>,
+    pub pinboard: Option<pinboard::Config>,
+    pub raindrop: Option<raindrop::Config>,
+    pub shaarli: Option<shaarli::Config>,
+    pub wallabag: Option<wallabag::Config>,
+}
+
+/// A result, as pushed to a service or written to a note.
+pub struct Link<'a> {
+    pub url: &'a str,
+    pub title: &'a str,
+    /// The `description` field of the result, if it has one.
+    pub description: Option<&'a str>,
+    /// The tags of the `tags` field of the input, which are separated by
+    /// commas.
+    pub tags: Vec<&'a str>,
+}
+
+impl<'a> Link<'a> {
+    /// Returns the link of the given result.
+    pub fn new(entry: &'a Entry, title: &'a str) -> Self {
+        let tags = entry.field("tags").unwrap_or_default().split(',');
+        Link {
+            url: &entry.url,
+            title: title.trim(),
+            description: entry.field("description").filter(|d| !d.is_empty()),
+            tags: tags.map(str::trim).filter(|tag| !tag.is_empty()).collect(),
+        }
+    }
+}
+
+/// A service that the results are pushed to.
+enum Service {
+    Linkding(linkding::Linkding),
+    Notion(notion::Notion),
+    Pinboard(pinboard::Pinboard),
+    Raindrop(raindrop::Raindrop),
+    Shaarli(shaarli::Shaarli),
+    Wallabag(wallabag::Wallabag),
+}
+
+/// Pushes the results to a service, one at a time and no more often than it
+/// allows.
+pub struct Pusher {
+    service: Service,
+    interval: Duration,
+    /// When the next result may be pushed.
+    next: Mutex<Option<Instant>>,
+}
+
+impl Pusher {
+    /// Creates a pusher to the given service, with its settings from the
+    /// configuration.
+    pub fn new(backend: PushBackend, config: &PushConfig) -> Result<Self> {
+        let (service, interval) = match backend {
+            PushBackend::Linkding => (
+                Service::Linkding(linkding::Linkding::new(config.linkding.as_ref())?),
+                Duration::ZERO,
+            ),
+            PushBackend::Notion => (
+                Service::Notion(notion::Notion::new(config.notion.as_ref())?),
+                notion::INTERVAL,
+            ),
+            PushBackend::Pinboard => (
+                Service::Pinboard(pinboard::Pinboard::new(config.pinboard.as_ref())?),
+                pinboard::INTERVAL,
+            ),
+            PushBackend::Raindrop => (
+                Service::Raindrop(raindrop::Raindrop::new(config.raindrop.as_ref())?),
+                raindrop::INTERVAL,
+            ),
+            PushBackend::Shaarli => (
+                Service::Shaarli(shaarli::Shaarli::new(config.shaarli.as_ref())?),
+                Duration::ZERO,
+            ),
+            PushBackend::Wallabag => (
+                Service::Wallabag(wallabag::Wallabag::new(config.wallabag.as_ref())?),
+                Duration::ZERO,
+            ),
+        };
+        Ok(Pusher {
+            service,
+            interval,
+            next: Mutex::default(),
+        })
+    }
+
+    /// Pushes the given link, once the gap since the previous one passed.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let mut next = self.next.lock().await;
+        if let Some(next) = *next {
+            time::sleep_until(next).await;
+        }
+        let result = match &self.service {
+            Service::Linkding(linkding) => linkding.push(link).await,
+            Service::Notion(notion) => notion.push(link).await,
+            Service::Pinboard(pinboard) => pinboard.push(link).await,
+            Service::Raindrop(raindrop) => raindrop.push(link).await,
+            Service::Shaarli(shaarli) => shaarli.push(link).await,
+            Service::Wallabag(wallabag) => wallabag.push(link).await,
+        };
+        *next = Some(Instant::now() + self.interval);
+        result
+    }
+}
+
+/// Returns the secret of a service in the given environment variable, or else
+/// the one of its table in the configuration file, if any.
+fn secret(var: &str, configured: Option<&String>) -> Option<String> {
+    env::var(var)
+        .ok()
+        .or_else(|| configured.cloned())
+        .filter(|secret| !secret.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_tags_and_the_description_of_results() {
+        let mut entry = Entry::new("https://example.com/");
+        entry
+            .fields
+            .push(("tags".into(), " rust, to read ,,".into()));
+        entry
+            .fields
+            .push(("description".into(), "A description.".into()));
+        let link = Link::new(&entry, "  Example Domain ");
+        assert_eq!(link.url, "https://example.com/");
+        assert_eq!(link.title, "Example Domain");
+        assert_eq!(link.description, Some("A description."));
+        assert_eq!(link.tags, ["rust", "to read"]);
+    }
+
+    #[test]
+    fn leaves_out_missing_tags_and_empty_descriptions() {
+        let mut entry = Entry::new("https://example.com/");
+        entry.fields.push(("description".into(), String::new()));
+        let link = Link::new(&entry, "Example Domain");
+        assert_eq!(link.description, None);
+        assert!(link.tags.is_empty());
+    }
+}
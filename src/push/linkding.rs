@@ -0,0 +1,73 @@
+use eyre::{eyre, Result, WrapErr};
+use reqwest::{header::AUTHORIZATION, Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+
+/// The `[push.linkding]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The URL of the instance, as in `https://links.example.com`.
+    url: String,
+    /// The REST API token, which `LINKDING_TOKEN` overrides.
+    token: Option<String>,
+}
+
+/// Adds the results as bookmarks to a linkding instance.
+pub struct Linkding {
+    api: String,
+    token: String,
+}
+
+impl Linkding {
+    /// Uses the instance of the configuration, with the token of
+    /// `LINKDING_TOKEN`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let config = config.ok_or_else(|| eyre!("linkding needs a `url` in `[push.linkding]`"))?;
+        let token = secret("LINKDING_TOKEN", config.token.as_ref()).ok_or_else(|| {
+            eyre!("linkding needs a token in `LINKDING_TOKEN` or `[push.linkding]`")
+        })?;
+        Ok(Linkding {
+            api: format!("{}/api/bookmarks/", config.url.trim_end_matches('/')),
+            token,
+        })
+    }
+
+    /// Adds the given link, unless it is already bookmarked, in which case
+    /// the bookmark is kept as is, since linkding would update it.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let check = self.request(Method::GET, "check/");
+        let existing = send(check.query(&[("url", link.url)])).await?;
+        if !existing["bookmark"].is_null() {
+            return Ok(());
+        }
+        let bookmark = json!({
+            "url": link.url,
+            "title": link.title,
+            "description": link.description.unwrap_or_default(),
+            "tag_names": link.tags,
+        });
+        send(self.request(Method::POST, "").json(&bookmark)).await?;
+        Ok(())
+    }
+
+    /// Starts a request to the given path of the bookmarks API.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = api_request(method, &format!("{}{path}", self.api));
+        request.header(AUTHORIZATION, format!("Token {}", self.token))
+    }
+}
+
+/// Sends the given request to linkding, returning its JSON response.
+async fn send(request: RequestBuilder) -> Result<Value> {
+    request
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("invalid response from linkding")
+}
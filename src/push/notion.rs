@@ -0,0 +1,145 @@
+use std::time::{Duration, SystemTime};
+
+use eyre::{eyre, Result, WrapErr};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+use crate::urls;
+
+/// The endpoint of the Notion API.
+const API: &str = "https://api.notion.com/v1";
+
+/// The version of the API that the requests are written for.
+const VERSION: &str = "2022-06-28";
+
+/// The gap between the requests of a row, since the API allows 3 requests a
+/// second on average, and each row makes two.
+pub const INTERVAL: Duration = Duration::from_millis(700);
+
+/// The longest text of a property, in characters.
+const TEXT_LENGTH: usize = 2000;
+
+/// The `[push.notion]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The token of an integration that the database is shared with, which
+    /// `NOTION_TOKEN` overrides.
+    token: Option<String>,
+    /// The ID of the database, as in its URL.
+    database: String,
+}
+
+/// Adds the results as rows to a Notion database, whose `Title`, `URL`,
+/// `Domain` and `Fetched At` properties are a title, a URL, a text and a date.
+pub struct Notion {
+    token: String,
+    database: String,
+}
+
+impl Notion {
+    /// Uses the database of the configuration, with the token of
+    /// `NOTION_TOKEN`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let config = config.ok_or_else(|| eyre!("Notion needs a `database` in `[push.notion]`"))?;
+        let token = secret("NOTION_TOKEN", config.token.as_ref())
+            .ok_or_else(|| eyre!("Notion needs a token in `NOTION_TOKEN` or `[push.notion]`"))?;
+        Ok(Notion {
+            token,
+            database: config.database.clone(),
+        })
+    }
+
+    /// Adds a row for the given link, unless the database already has one,
+    /// in which case it is kept as is.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let filter = json!({ "filter": { "property": "URL", "url": { "equals": link.url } } });
+        let path = format!("/databases/{}/query", self.database);
+        let existing = self.post(&path, &filter).await?;
+        if existing["results"]
+            .as_array()
+            .is_some_and(|rows| !rows.is_empty())
+        {
+            return Ok(());
+        }
+        let fetched = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        self.post("/pages", &self.row(link, &fetched)).await?;
+        Ok(())
+    }
+
+    /// Returns the row of the given link, fetched at the given time.
+    fn row(&self, link: &Link<'_>, fetched: &str) -> Value {
+        json!({
+            "parent": { "database_id": self.database },
+            "properties": {
+                "Title": { "title": text(link.title) },
+                "URL": { "url": link.url },
+                "Domain": { "rich_text": text(&urls::domain(link.url)) },
+                "Fetched At": { "date": { "start": fetched } },
+            },
+        })
+    }
+
+    /// Sends the given JSON body to the given path of the API.
+    async fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        let response = api_request(Method::POST, &format!("{API}{path}"))
+            .bearer_auth(&self.token)
+            .header("Notion-Version", VERSION)
+            .json(body)
+            .send()
+            .await?;
+        // The errors are explained in the body of the response.
+        let status = response.status();
+        let response: Value = response
+            .json()
+            .await
+            .wrap_err("invalid response from Notion")?;
+        if !status.is_success() {
+            let message = response["message"].as_str().unwrap_or_default();
+            return Err(eyre!("Notion answered `{status}`: {message}"));
+        }
+        Ok(response)
+    }
+}
+
+/// Returns the rich text of a property with the given text.
+fn text(content: &str) -> Value {
+    let content: String = content.chars().take(TEXT_LENGTH).collect();
+    json!([{ "type": "text", "text": { "content": content } }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_rows_of_the_database() {
+        let notion = Notion {
+            token: String::new(),
+            database: "abc123".to_owned(),
+        };
+        let title = "a".repeat(TEXT_LENGTH + 1);
+        let link = Link {
+            url: "https://www.example.com/a",
+            title: &title,
+            description: None,
+            tags: Vec::new(),
+        };
+        let text = |content: String| json!([{ "type": "text", "text": { "content": content } }]);
+        assert_eq!(
+            notion.row(&link, "2024-01-31T12:00:00Z"),
+            json!({
+                "parent": { "database_id": "abc123" },
+                "properties": {
+                    "Title": { "title": text("a".repeat(TEXT_LENGTH)) },
+                    "URL": { "url": "https://www.example.com/a" },
+                    "Domain": { "rich_text": text("example.com".to_owned()) },
+                    "Fetched At": { "date": { "start": "2024-01-31T12:00:00Z" } },
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use eyre::{bail, eyre, Result, WrapErr};
+use reqwest::{Method, Response};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+
+/// The endpoint of the Pinboard API that adds a bookmark.
+const ADD: &str = "https://api.pinboard.in/v1/posts/add";
+
+/// The gap between the bookmarks added, since the API allows a request every
+/// 3 seconds.
+pub const INTERVAL: Duration = Duration::from_secs(3);
+
+/// The longest title of a bookmark, in characters.
+const TITLE_LENGTH: usize = 255;
+
+/// The `[push.pinboard]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The API token, as in `user:TOKEN`, which `PINBOARD_TOKEN` overrides.
+    token: Option<String>,
+}
+
+/// Adds the results as bookmarks to Pinboard.
+pub struct Pinboard {
+    token: String,
+}
+
+impl Pinboard {
+    /// Uses the token of `PINBOARD_TOKEN`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let token = secret(
+            "PINBOARD_TOKEN",
+            config.and_then(|config| config.token.as_ref()),
+        )
+        .ok_or_else(|| eyre!("Pinboard needs a token in `PINBOARD_TOKEN` or `[push.pinboard]`"))?;
+        Ok(Pinboard { token })
+    }
+
+    /// Adds the given link, unless it is already bookmarked, in which case
+    /// the bookmark is kept as is.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        // The errors leave out the URL, which has the token.
+        let response: Value = api_request(Method::GET, ADD)
+            .query(&[("auth_token", &self.token)])
+            .query(&params(link))
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+            .map_err(reqwest::Error::without_url)?
+            .json()
+            .await
+            .wrap_err("invalid response from Pinboard")?;
+        match response["result_code"].as_str() {
+            Some("done" | "item already exists") => Ok(()),
+            Some(code) => bail!("Pinboard answered `{code}`"),
+            None => bail!("invalid response from Pinboard"),
+        }
+    }
+}
+
+/// Returns the parameters of the request that adds the given link, besides the
+/// token.
+fn params(link: &Link<'_>) -> Vec<(&'static str, String)> {
+    let title: String = link.title.chars().take(TITLE_LENGTH).collect();
+    // The tags are separated by spaces.
+    let tags: Vec<_> = link.tags.iter().map(|tag| tag.replace(' ', "_")).collect();
+    let mut params = vec![
+        ("format", "json".to_owned()),
+        ("replace", "no".to_owned()),
+        ("url", link.url.to_owned()),
+        ("description", title),
+        ("tags", tags.join(" ")),
+    ];
+    params.extend(
+        link.description
+            .map(|description| ("extended", description.to_owned())),
+    );
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_parameters_of_bookmarks() {
+        let title = "a".repeat(300);
+        let link = Link {
+            url: "https://example.com/",
+            title: &title,
+            description: Some("A description."),
+            tags: vec!["rust", "to read"],
+        };
+        let bookmark = params(&link);
+        assert_eq!(bookmark[2], ("url", "https://example.com/".to_owned()));
+        assert_eq!(bookmark[3], ("description", "a".repeat(TITLE_LENGTH)));
+        assert_eq!(bookmark[4], ("tags", "rust to_read".to_owned()));
+        assert_eq!(bookmark[5], ("extended", "A description.".to_owned()));
+        let link = Link {
+            description: None,
+            tags: Vec::new(),
+            ..link
+        };
+        let bookmark = params(&link);
+        assert_eq!(bookmark.len(), 5);
+        assert_eq!(bookmark[4], ("tags", String::new()));
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use eyre::{bail, eyre, Result, WrapErr};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+
+/// The endpoint of the Raindrop.io API, under which bookmarks are called
+/// raindrops.
+const API: &str = "https://api.raindrop.io/rest/v1";
+
+/// The gap between the requests of a bookmark, since the API allows 120
+/// requests a minute, and each bookmark makes two.
+pub const INTERVAL: Duration = Duration::from_secs(1);
+
+/// The longest excerpt of a bookmark, in characters.
+const EXCERPT_LENGTH: usize = 10000;
+
+/// The `[push.raindrop]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The test token of an app, which `RAINDROP_TOKEN` overrides.
+    token: Option<String>,
+    /// The ID of the collection that the bookmarks are added to, which
+    /// defaults to Unsorted.
+    collection: Option<i64>,
+}
+
+/// Adds the results as bookmarks to a Raindrop.io collection.
+pub struct Raindrop {
+    token: String,
+    collection: Option<i64>,
+}
+
+impl Raindrop {
+    /// Uses the token of `RAINDROP_TOKEN`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let token = secret(
+            "RAINDROP_TOKEN",
+            config.and_then(|config| config.token.as_ref()),
+        )
+        .ok_or_else(|| {
+            eyre!("Raindrop.io needs a token in `RAINDROP_TOKEN` or `[push.raindrop]`")
+        })?;
+        Ok(Raindrop {
+            token,
+            collection: config.and_then(|config| config.collection),
+        })
+    }
+
+    /// Adds the given link, unless it is already bookmarked, in which case
+    /// the bookmark is kept as is.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let existing = self
+            .post("/import/url/exists", &json!({ "urls": [link.url] }))
+            .await?;
+        if existing["ids"]
+            .as_array()
+            .is_some_and(|ids| !ids.is_empty())
+        {
+            return Ok(());
+        }
+        self.post("/raindrop", &self.raindrop(link)).await?;
+        Ok(())
+    }
+
+    /// Returns the raindrop of the given link, in the collection of the
+    /// configuration, if any.
+    fn raindrop(&self, link: &Link<'_>) -> Value {
+        let mut raindrop = json!({
+            "link": link.url,
+            "title": link.title,
+            "tags": link.tags,
+        });
+        if let Some(description) = link.description {
+            let excerpt: String = description.chars().take(EXCERPT_LENGTH).collect();
+            raindrop["excerpt"] = excerpt.into();
+        }
+        if let Some(collection) = self.collection {
+            raindrop["collection"] = json!({ "$id": collection });
+        }
+        raindrop
+    }
+
+    /// Sends the given JSON body to the given path of the API.
+    async fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        let response: Value = api_request(Method::POST, &format!("{API}{path}"))
+            .bearer_auth(&self.token)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .wrap_err("invalid response from Raindrop.io")?;
+        if response["result"] != true {
+            match response["errorMessage"].as_str() {
+                Some(message) => bail!("Raindrop.io answered `{message}`"),
+                None => bail!("invalid response from Raindrop.io"),
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_raindrops() {
+        let description = "a".repeat(EXCERPT_LENGTH + 1);
+        let link = Link {
+            url: "https://example.com/",
+            title: "Example Domain",
+            description: Some(&description),
+            tags: vec!["rust", "to read"],
+        };
+        let raindrop = Raindrop {
+            token: String::new(),
+            collection: Some(42),
+        };
+        assert_eq!(
+            raindrop.raindrop(&link),
+            json!({
+                "link": "https://example.com/",
+                "title": "Example Domain",
+                "tags": ["rust", "to read"],
+                "excerpt": "a".repeat(EXCERPT_LENGTH),
+                "collection": { "$id": 42 },
+            })
+        );
+        let unsorted = Raindrop {
+            collection: None,
+            ..raindrop
+        };
+        let link = Link {
+            description: None,
+            tags: Vec::new(),
+            ..link
+        };
+        assert_eq!(
+            unsorted.raindrop(&link),
+            json!({ "link": "https://example.com/", "title": "Example Domain", "tags": [] })
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::SystemTime;
+
+use eyre::{eyre, Result};
+use openssl::{base64, hash::MessageDigest, pkey::PKey, sign::Signer};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+
+/// The `[push.shaarli]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The URL of the instance, as in `https://links.example.com`.
+    url: String,
+    /// The REST API secret, which `SHAARLI_SECRET` overrides.
+    secret: Option<String>,
+}
+
+/// Adds the results as links to a Shaarli instance.
+pub struct Shaarli {
+    api: String,
+    secret: String,
+}
+
+impl Shaarli {
+    /// Uses the instance of the configuration, with the secret of
+    /// `SHAARLI_SECRET`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let config = config.ok_or_else(|| eyre!("Shaarli needs a `url` in `[push.shaarli]`"))?;
+        let secret = secret("SHAARLI_SECRET", config.secret.as_ref()).ok_or_else(|| {
+            eyre!("Shaarli needs a secret in `SHAARLI_SECRET` or `[push.shaarli]`")
+        })?;
+        Ok(Shaarli {
+            api: format!("{}/api/v1/links", config.url.trim_end_matches('/')),
+            secret,
+        })
+    }
+
+    /// Adds the given link, unless it is already shared, in which case
+    /// Shaarli keeps it as is.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let body = json!({
+            "url": link.url,
+            "title": link.title,
+            "description": link.description.unwrap_or_default(),
+            // Shaarli separates the tags by spaces.
+            "tags": link.tags.iter().map(|tag| tag.replace(' ', "_")).collect::<Vec<_>>(),
+            "private": false,
+        });
+        let response = api_request(Method::POST, &self.api)
+            .bearer_auth(self.token()?)
+            .json(&body)
+            .send()
+            .await?;
+        if response.status() == StatusCode::CONFLICT {
+            return Ok(());
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+
+    /// Returns a JSON Web Token signed with the secret, which the API only
+    /// accepts for a few minutes after it is issued.
+    fn token(&self) -> Result<String> {
+        let issued = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+        let header = encode(
+            json!({ "typ": "JWT", "alg": "HS512" })
+                .to_string()
+                .as_bytes(),
+        );
+        let payload = encode(json!({ "iat": issued.as_secs() }).to_string().as_bytes());
+        let message = format!("{header}.{payload}");
+        let key = PKey::hmac(self.secret.as_bytes())?;
+        let mut signer = Signer::new(MessageDigest::sha512(), &key)?;
+        signer.update(message.as_bytes())?;
+        let signature = encode(&signer.sign_to_vec()?);
+        Ok(format!("{message}.{signature}"))
+    }
+}
+
+/// Encodes the given bytes as the unpadded URL-safe Base64 of JSON Web Tokens.
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_block(bytes)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use eyre::{eyre, Result, WrapErr};
+use reqwest::{Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{sync::Mutex, time::Instant};
+
+use super::{secret, Link};
+use crate::fetch::api_request;
+
+/// How long before it expires an access token is renewed, so that it doesn't
+/// expire while in use.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The `[push.wallabag]` table of the configuration file, with the client of
+/// the API (created in the "API clients management" page of the instance) and
+/// the account that it is used as.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The URL of the instance, as in `https://app.wallabag.it`.
+    url: String,
+    client_id: String,
+    client_secret: String,
+    username: String,
+    /// The password of the account, which `WALLABAG_PASSWORD` overrides.
+    password: Option<String>,
+}
+
+/// Saves the results as entries of a Wallabag instance, to be read later.
+pub struct Wallabag {
+    url: String,
+    /// The form with which access tokens are given.
+    credentials: Vec<(&'static str, String)>,
+    /// The access token, if one was given, and when it expires, which is
+    /// locked while it is renewed so that it is only asked for once.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl Wallabag {
+    /// Uses the instance and the client of the configuration, with the
+    /// password of `WALLABAG_PASSWORD`, or else of the configuration.
+    pub fn new(config: Option<&Config>) -> Result<Self> {
+        let config = config.ok_or_else(|| {
+            eyre!("Wallabag needs a `[push.wallabag]` table in the configuration")
+        })?;
+        let password = secret("WALLABAG_PASSWORD", config.password.as_ref()).ok_or_else(|| {
+            eyre!("Wallabag needs a password in `WALLABAG_PASSWORD` or `[push.wallabag]`")
+        })?;
+        Ok(Wallabag {
+            url: config.url.trim_end_matches('/').to_owned(),
+            credentials: vec![
+                ("grant_type", "password".to_owned()),
+                ("client_id", config.client_id.clone()),
+                ("client_secret", config.client_secret.clone()),
+                ("username", config.username.clone()),
+                ("password", password),
+            ],
+            token: Mutex::default(),
+        })
+    }
+
+    /// Saves the given link with its title, unless it is already saved, in
+    /// which case the entry is kept as is.
+    pub async fn push(&self, link: &Link<'_>) -> Result<()> {
+        let exists = self
+            .request(Method::GET, "/api/entries/exists.json")
+            .await?;
+        let exists = send(exists.query(&[("url", link.url), ("return_id", "1")])).await?;
+        if !exists["exists"].is_null() && exists["exists"] != false {
+            return Ok(());
+        }
+        let entry = json!({
+            "url": link.url,
+            "title": link.title,
+            "tags": link.tags.join(","),
+        });
+        let save = self.request(Method::POST, "/api/entries.json").await?;
+        send(save.json(&entry)).await?;
+        Ok(())
+    }
+
+    /// Starts a request to the given path of the API, with an access token.
+    async fn request(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let token = self.token().await?;
+        Ok(api_request(method, &format!("{}{path}", self.url)).bearer_auth(token))
+    }
+
+    /// Returns the access token, which is given anew once it expires.
+    async fn token(&self) -> Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some((token, expires)) = &*cached {
+            if Instant::now() + EXPIRY_MARGIN < *expires {
+                return Ok(token.clone());
+            }
+        }
+        let url = format!("{}/oauth/v2/token", self.url);
+        let response = send(api_request(Method::POST, &url).form(&self.credentials))
+            .await
+            .wrap_err("Wallabag didn't give an access token")?;
+        let token = response["access_token"]
+            .as_str()
+            .ok_or_else(|| eyre!("Wallabag didn't give an access token"))?;
+        let lifetime = Duration::from_secs(response["expires_in"].as_u64().unwrap_or_default());
+        *cached = Some((token.to_owned(), Instant::now() + lifetime));
+        Ok(token.to_owned())
+    }
+}
+
+/// Sends the given request to Wallabag, returning its JSON response.
+async fn send(request: RequestBuilder) -> Result<Value> {
+    request
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err("invalid response from Wallabag")
+}
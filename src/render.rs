@@ -0,0 +1,195 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use eyre::{bail, eyre, Result, WrapErr};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::{process::Command, time};
+use url::Url;
+
+use crate::{
+    fetch::{parse_html_and_get_title, Page, Validators},
+    overrides,
+};
+
+/// Names of the Chromium executables that are looked up in the `PATH`.
+const BROWSER_NAMES: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+];
+
+/// How long the browser may take to render a page before it is killed.
+const RENDER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// When pages are rendered.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderMode {
+    /// All pages are rendered.
+    Always,
+    /// Pages are only rendered if their static title looks like a
+    /// placeholder: missing, the domain itself, or a generic one such as
+    /// "Loading" or "Just a moment...".
+    Auto,
+    /// No page is rendered, which only the rules of the configuration file
+    /// use, to override the mode of `--render`.
+    #[value(skip)]
+    Never,
+}
+
+/// Renders pages with a headless Chromium, so that the titles set by
+/// JavaScript are seen.
+pub struct Renderer {
+    browser: PathBuf,
+    /// The arguments given to the browser besides those of the renderer.
+    args: Vec<String>,
+    /// How long scripts may run, in virtual time, before the DOM is dumped.
+    budget: Duration,
+}
+
+impl Renderer {
+    /// Creates a renderer that runs the given browser or, unless given, the
+    /// first Chromium found in the `PATH`, with the given extra arguments. The
+    /// browser keeps its sandbox, since the pages it loads aren't trusted.
+    pub fn new(browser: Option<PathBuf>, args: Vec<String>, budget: Duration) -> Result<Self> {
+        let browser = match browser {
+            Some(browser) => browser,
+            None => BROWSER_NAMES
+                .iter()
+                .find_map(|name| find_in_path(name))
+                .ok_or_else(|| eyre!("no Chromium found in the `PATH` (see `--browser`)"))?,
+        };
+        Ok(Renderer {
+            browser,
+            args,
+            budget,
+        })
+    }
+
+    /// Loads the given URL in the browser, returning the page as rendered once
+    /// its scripts ran.
+    pub async fn render(&self, url: &str) -> Result<Page> {
+        let mut command = Command::new(&self.browser);
+        command
+            .arg("--headless")
+            .arg("--disable-gpu")
+            .arg(format!("--virtual-time-budget={}", self.budget.as_millis()))
+            .arg("--dump-dom")
+            .args(&self.args);
+        if let Some(user_agent) = overrides::user_agent(url) {
+            command.arg(format!("--user-agent={user_agent}"));
+        }
+        if let Some(proxy) = overrides::proxy(url) {
+            command.arg(format!("--proxy-server={proxy}"));
+        }
+        command.arg(url).kill_on_drop(true);
+        let output = time::timeout(RENDER_TIMEOUT, command.output())
+            .await
+            .map_err(|_| eyre!("timed out rendering `{url}`"))?
+            .wrap_err_with(|| format!("failed to run `{}`", self.browser.display()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("failed to render `{url}`: {}", stderr.trim());
+        }
+
+        let html = String::from_utf8_lossy(&output.stdout).into_owned();
+        let title = parse_html_and_get_title(&html).await?;
+        Ok(Page {
+            url: url.to_owned(),
+            unwrapped_url: None,
+            // The browser doesn't report the status of the page.
+            status: StatusCode::OK,
+            title,
+            html,
+            validators: Validators::default(),
+            fields: Vec::new(),
+        })
+    }
+}
+
+/// Whether the given static title of the page at the given URL looks like a
+/// placeholder that scripts would replace, or belongs to a bot check page.
+pub fn is_placeholder_title(url: &str, title: Option<&str>) -> bool {
+    /// Titles that are placeholders when they are the whole title.
+    const PLACEHOLDERS: &[&str] = &[
+        "loading",
+        "loading...",
+        "loading…",
+        "document",
+        "untitled",
+        "react app",
+        "vite app",
+    ];
+    /// Phrases of the titles of bot checks, which need scripts to go through.
+    const BOT_CHECKS: &[&str] = &[
+        "just a moment",
+        "please wait",
+        "attention required",
+        "checking your browser",
+    ];
+
+    let Some(title) = title else {
+        return true;
+    };
+    let title = title.trim().to_lowercase();
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default();
+    PLACEHOLDERS.contains(&title.as_str())
+        || BOT_CHECKS.iter().any(|p| title.contains(p))
+        || title == host
+        || Some(title.as_str()) == host.strip_prefix("www.")
+}
+
+/// Returns the path of the given executable, if it is in the `PATH`.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+/// Whether the given path is an executable file.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Whether the given path is an executable file.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_placeholder_titles() {
+        let url = "https://www.example.com/app";
+        assert!(is_placeholder_title(url, None));
+        assert!(is_placeholder_title(url, Some(" Loading... ")));
+        assert!(is_placeholder_title(url, Some("React App")));
+        assert!(is_placeholder_title(url, Some("Just a moment...")));
+        assert!(is_placeholder_title(url, Some("www.example.com")));
+        assert!(is_placeholder_title(url, Some("example.com")));
+    }
+
+    #[test]
+    fn keeps_real_titles() {
+        let url = "https://www.example.com/app";
+        assert!(!is_placeholder_title(url, Some("Loading data with Rust")));
+        assert!(!is_placeholder_title(url, Some("Example Domain")));
+        assert!(!is_placeholder_title(url, Some("Untitled Goose Game")));
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::metrics::Metrics;
+
+/// Resolves titles for the long-lived modes (the server and the stdio mode),
+/// keeping the resolved titles in an in-memory cache.
+pub struct Resolver {
+    cache_ttl: Duration,
+    /// Maps each URL to when its title was resolved, and the title itself.
+    cache: Mutex<HashMap<String, (Instant, Option<String>)>>,
+    metrics: Metrics,
+}
+
+/// The result of resolving a URL. Has the same shape as the JSON output
+/// formats.
+#[derive(Serialize)]
+pub struct Resolved {
+    pub url: String,
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Resolver {
+    pub fn new(cache_ttl: Duration) -> Self {
+        Resolver {
+            cache_ttl,
+            cache: Mutex::default(),
+            metrics: Metrics::new(),
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Resolves the title of the given URL, going through the cache. Failures
+    /// aren't cached.
+    pub async fn resolve(&self, url: String) -> Resolved {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&url)
+            .filter(|(at, _)| at.elapsed() < self.cache_ttl)
+            .map(|(_, title)| title.clone());
+        let result = match cached {
+            Some(title) => {
+                self.metrics.cache_hits.inc();
+                Ok(title)
+            }
+            None => {
+                self.metrics.cache_misses.inc();
+                self.metrics.fetch_title(&url).await
+            }
+        };
+
+        match result {
+            Ok(title) => {
+                let mut cache = self.cache.lock().unwrap();
+                cache.retain(|_, (at, _)| at.elapsed() < self.cache_ttl);
+                cache
+                    .entry(url.clone())
+                    .or_insert_with(|| (Instant::now(), title.clone()));
+                Resolved {
+                    url,
+                    title,
+                    error: None,
+                }
+            }
+            Err(error) => Resolved {
+                url,
+                title: None,
+                error: Some(format!("{error}: {}", error.root_cause())),
+            },
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use eyre::{Result, WrapErr};
+use futures::{stream, StreamExt};
+use tracing::{error, info};
+
+use crate::{fetch::load_url_and_get_title, html, markdown, org, output::escape_html};
+
+#[derive(clap::Args)]
+pub struct RewriteArgs {
+    /// Files to rewrite in place.
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Format of the files. Defaults to `org` for files with the `.org`
+    /// extension, `html` for `.html` and `.htm`, and `markdown` otherwise.
+    #[arg(long, value_enum)]
+    format: Option<DocumentFormat>,
+
+    /// Prints the changed lines instead of writing the files.
+    #[arg(long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DocumentFormat {
+    /// Bare URLs become `[title](url)`.
+    Markdown,
+    /// Bare URLs become `[[url][title]]`.
+    Org,
+    /// Anchors whose text is empty or their URL get the title as text.
+    Html,
+}
+
+impl DocumentFormat {
+    /// Guesses the format of the given file from its extension.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("org") => DocumentFormat::Org,
+            Some("html" | "htm") => DocumentFormat::Html,
+            _ => DocumentFormat::Markdown,
+        }
+    }
+}
+
+/// A replacement of a byte range of a document.
+struct Edit {
+    span: Range<usize>,
+    text: String,
+}
+
+/// Rewrites the bare URLs of the given Markdown or Org files as links whose
+/// text is the title of the page, such as `[Google](https://google.com)`. In
+/// HTML files, the anchors with no text of their own are filled with the title
+/// instead. Everything else is left untouched, byte for byte. URLs that fail to
+/// load or have no title are left as they are.
+pub async fn rewrite(args: RewriteArgs) -> Result<()> {
+    for path in &args.files {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read `{}`", path.display()))?;
+        let format = args.format.unwrap_or_else(|| DocumentFormat::of(path));
+        let bare_urls = match format {
+            DocumentFormat::Markdown => markdown::bare_url_spans(&contents),
+            DocumentFormat::Org => org::bare_url_spans(&contents),
+            DocumentFormat::Html => html::unnamed_anchor_spans(&contents),
+        };
+        let titles = fetch_titles(bare_urls.iter().map(|bare| bare.url.as_str())).await;
+        let edits: Vec<_> = bare_urls
+            .into_iter()
+            .filter_map(|bare| {
+                let title = titles.get(&bare.url)?;
+                let text = match format {
+                    DocumentFormat::Markdown => {
+                        format!("[{}]({})", escape_markdown(title), bare.url)
+                    }
+                    DocumentFormat::Org => org::link(&bare.url, title),
+                    DocumentFormat::Html => escape_html(title),
+                };
+                Some(Edit {
+                    span: bare.span,
+                    text,
+                })
+            })
+            .collect();
+        finish(path, &contents, &edits, args.dry_run)?;
+    }
+    Ok(())
+}
+
+/// Fetches the titles of the given URLs, which are only fetched once each. The
+/// URLs that fail or have no title are left out, and whitespace in the titles
+/// is collapsed, so that they fit in a single line.
+async fn fetch_titles<'a>(urls: impl Iterator<Item = &'a str>) -> HashMap<String, String> {
+    let unique: HashSet<_> = urls.collect();
+    stream::iter(unique)
+        .map(|url| async move {
+            match load_url_and_get_title(url).await {
+                Ok(Some(title)) => {
+                    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                    Some((url.to_owned(), title))
+                }
+                Ok(None) => {
+                    info!("no title for `{url}`");
+                    None
+                }
+                Err(error) => {
+                    error!("failed `{url}`: {error:#}");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(10)
+        .filter_map(|result| async move { result })
+        .collect()
+        .await
+}
+
+/// Applies the given edits, which must be sorted and not overlap, and either
+/// writes the result back to the file or, on a dry run, prints the changed
+/// lines.
+fn finish(path: &Path, contents: &str, edits: &[Edit], dry_run: bool) -> Result<()> {
+    let mut rewritten = String::with_capacity(contents.len());
+    let mut last = 0;
+    for edit in edits {
+        rewritten.push_str(&contents[last..edit.span.start]);
+        rewritten.push_str(&edit.text);
+        last = edit.span.end;
+    }
+    rewritten.push_str(&contents[last..]);
+
+    if dry_run {
+        print_diff(path, contents, &rewritten);
+        return Ok(());
+    }
+    if rewritten != contents {
+        // Writes to a temporary file first, so that the original is never left
+        // half-written.
+        let tmp = path.with_file_name(format!(
+            ".{}.titlelist",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        fs::write(&tmp, &rewritten)?;
+        fs::rename(&tmp, path).wrap_err_with(|| format!("failed to write `{}`", path.display()))?;
+    }
+    info!("rewrote {} links in `{}`", edits.len(), path.display());
+    Ok(())
+}
+
+/// Prints the lines that differ between the two versions of the file. Edits
+/// never add or remove lines, so they are compared one by one.
+fn print_diff(path: &Path, old: &str, new: &str) {
+    println!("--- {}", path.display());
+    println!("+++ {}", path.display());
+    for (i, (old_line, new_line)) in old.lines().zip(new.lines()).enumerate() {
+        if old_line != new_line {
+            println!("@@ -{0} +{0} @@", i + 1);
+            println!("-{old_line}");
+            println!("+{new_line}");
+        }
+    }
+}
+
+/// Escapes the characters that would end the text of a Markdown link early
+/// or be read as markup.
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markdown() {
+        assert_eq!(
+            escape_markdown(r"[a] *b* _c_ `d` <e> \f"),
+            r"\[a\] \*b\* \_c\_ \`d\` \<e> \\f"
+        );
+        assert_eq!(escape_markdown("Plain title"), "Plain title");
+    }
+}
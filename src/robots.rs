@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::OnceCell;
+use tracing::warn;
+use url::Url;
+
+use crate::fetch;
+
+/// The name that the groups of robots.txt files are matched against.
+const USER_AGENT: &str = "titlelist";
+
+/// Checks URLs against the robots.txt files of their hosts, which are fetched
+/// once per origin.
+#[derive(Default)]
+pub struct Robots {
+    origins: Mutex<HashMap<String, Arc<OnceCell<Rules>>>>,
+}
+
+impl Robots {
+    /// Whether the robots.txt of the host of the given URL allows fetching it.
+    /// URLs that can't be parsed are allowed, so that they fail as usual.
+    pub async fn allows(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return true;
+        };
+        let origin = url.origin().ascii_serialization();
+        let cell = Arc::clone(
+            self.origins
+                .lock()
+                .unwrap()
+                .entry(origin.clone())
+                .or_default(),
+        );
+        let rules = cell.get_or_init(|| Rules::fetch(&origin)).await;
+        let mut path = url.path().to_owned();
+        if let Some(query) = url.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        rules.allows(&path)
+    }
+}
+
+/// The rules of a robots.txt file that apply to titlelist.
+enum Rules {
+    AllowAll,
+    DisallowAll,
+    /// Patterns of paths, and whether they are allowed.
+    Patterns(Vec<(String, bool)>),
+}
+
+impl Rules {
+    /// Fetches the robots.txt of the given origin. As RFC 9309 says, a missing
+    /// file allows everything, and an unreachable one disallows everything.
+    async fn fetch(origin: &str) -> Rules {
+        let url = format!("{origin}/robots.txt");
+        let response = match fetch::get(&url).await {
+            Ok(response) => response,
+            Err(error) => {
+                warn!("failed to fetch `{url}`, so its host is skipped: {error:#}");
+                return Rules::DisallowAll;
+            }
+        };
+        let status = response.status();
+        if status.is_client_error() {
+            return Rules::AllowAll;
+        }
+        if !status.is_success() {
+            warn!("`{url}` failed with {status}, so its host is skipped");
+            return Rules::DisallowAll;
+        }
+        match response.text().await {
+            Ok(contents) => Rules::parse(&contents),
+            Err(_) => Rules::DisallowAll,
+        }
+    }
+
+    /// Parses the rules of the group that matches titlelist or, if none does,
+    /// of the `*` group.
+    fn parse(contents: &str) -> Rules {
+        // The rules of the groups named after titlelist and of `*`.
+        let (mut named, mut any) = (Vec::new(), Vec::new());
+        let (mut is_named, mut is_any) = (false, false);
+        let mut has_named = false;
+        // Whether the previous line was a `User-agent` line, so that the
+        // following one belongs to the same group.
+        let mut in_agents = false;
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        (is_named, is_any) = (false, false);
+                    }
+                    in_agents = true;
+                    let agent = value.to_ascii_lowercase();
+                    is_named |= agent == USER_AGENT;
+                    has_named |= agent == USER_AGENT;
+                    is_any |= agent == "*";
+                }
+                "allow" | "disallow" => {
+                    in_agents = false;
+                    // An empty `Disallow` allows everything.
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let rule = (value.to_owned(), key == "allow");
+                    if is_named {
+                        named.push(rule.clone());
+                    }
+                    if is_any {
+                        any.push(rule);
+                    }
+                }
+                _ => in_agents = false,
+            }
+        }
+        Rules::Patterns(if has_named { named } else { any })
+    }
+
+    /// Whether the given path, with its query, may be fetched. The longest
+    /// matching pattern wins, with allowing ones winning ties.
+    fn allows(&self, path: &str) -> bool {
+        let patterns = match self {
+            Rules::AllowAll => return true,
+            Rules::DisallowAll => return false,
+            Rules::Patterns(patterns) => patterns,
+        };
+        patterns
+            .iter()
+            .filter(|(pattern, _)| matches_path(pattern, path))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .is_none_or(|(_, allow)| *allow)
+    }
+}
+
+/// Whether the given robots.txt pattern matches the start of the given path.
+/// `*` matches any sequence of characters and a trailing `$` anchors the end.
+fn matches_path(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut pieces = pattern.split('*');
+    let first = pieces.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let pieces: Vec<_> = pieces.collect();
+    for (i, piece) in pieces.iter().enumerate() {
+        let last = i == pieces.len() - 1;
+        if last && anchored {
+            return rest.ends_with(piece);
+        }
+        match rest.find(piece) {
+            Some(at) => rest = &rest[at + piece.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_paths_with_wildcards_and_anchors() {
+        assert!(matches_path("/private", "/private/a"));
+        assert!(!matches_path("/private", "/public"));
+        assert!(matches_path("/*.pdf$", "/docs/a.pdf"));
+        assert!(!matches_path("/*.pdf$", "/docs/a.pdf?download"));
+        assert!(matches_path("/a*b*c", "/a-x-b-y-c-z"));
+        assert!(!matches_path("/a*c*b", "/a-b-c"));
+        assert!(matches_path("/exact$", "/exact"));
+        assert!(!matches_path("/exact$", "/exactly"));
+        assert!(matches_path("*", "/anything"));
+    }
+
+    #[test]
+    fn takes_the_rules_of_the_named_group_over_those_of_any() {
+        let rules = Rules::parse(
+            "User-agent: *\nDisallow: /\n\nUser-agent: Googlebot\nUser-agent: titlelist\nDisallow: /private # comment\nAllow: /private/open\n",
+        );
+        assert!(rules.allows("/public"));
+        assert!(!rules.allows("/private/a"));
+        assert!(rules.allows("/private/open/a"));
+
+        let rules = Rules::parse("User-agent: *\nDisallow: /search\nDisallow:\n");
+        assert!(!rules.allows("/search?q=1"));
+        assert!(rules.allows("/"));
+    }
+
+    #[test]
+    fn lets_allowing_patterns_win_ties() {
+        let rules = Rules::parse("User-agent: *\nDisallow: /page\nAllow: /page\n");
+        assert!(rules.allows("/page"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{sync::Arc, time::Duration};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{stdin, stdout, AsyncBufReadExt, AsyncWriteExt, BufReader, Stdout},
+    sync::Mutex,
+    task::JoinSet,
+};
+
+use crate::resolver::Resolver;
+
+/// How long resolved titles are cached by the stdio mode.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A JSON-RPC 2.0 request. Requests without an `id` are notifications, which
+/// don't get a response.
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// Speaks JSON-RPC 2.0 over the standard input and output, one message per
+/// line, until the input is closed. The only method is `title`, which takes a
+/// `url` (either as `{"url": ...}` or `[...]`) and returns an object with the
+/// `url` and its `title`, like the JSON output formats. Requests are handled
+/// concurrently, so responses may come out of order.
+pub async fn run() -> Result<()> {
+    let resolver = Arc::new(Resolver::new(CACHE_TTL));
+    let stdout = Arc::new(Mutex::new(stdout()));
+    let mut lines = BufReader::new(stdin()).lines();
+    let mut tasks = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let resolver = Arc::clone(&resolver);
+                let stdout = Arc::clone(&stdout);
+                tasks.spawn(async move {
+                    if let Some(response) = handle(&resolver, &line).await {
+                        write(&stdout, &response).await?;
+                    }
+                    Ok::<_, eyre::Report>(())
+                });
+            }
+            // Reaps the tasks that already finished.
+            Some(result) = tasks.join_next() => result??,
+        }
+    }
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+    Ok(())
+}
+
+/// Handles a single message, returning the response to be sent, if any.
+async fn handle(resolver: &Resolver, line: &str) -> Option<Response> {
+    let response = |id, outcome| {
+        Some(Response {
+            jsonrpc: "2.0",
+            id,
+            outcome,
+        })
+    };
+
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(error) => {
+            let error = RpcError::new(-32700, format!("parse error: {error}"));
+            return response(Value::Null, Outcome::Error(error));
+        }
+    };
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(error) => {
+            let error = RpcError::new(-32600, format!("invalid request: {error}"));
+            return response(Value::Null, Outcome::Error(error));
+        }
+    };
+
+    let outcome = match request.method.as_str() {
+        "title" => title(resolver, request.params).await,
+        method => Outcome::Error(RpcError::new(-32601, format!("unknown method `{method}`"))),
+    };
+    response(request.id?, outcome)
+}
+
+/// Handles the `title` method.
+async fn title(resolver: &Resolver, params: Value) -> Outcome {
+    let url = match &params {
+        Value::Object(map) => map.get("url"),
+        Value::Array(list) => list.first(),
+        _ => None,
+    };
+    let Some(url) = url.and_then(Value::as_str) else {
+        return Outcome::Error(RpcError::new(-32602, "expected a `url` parameter"));
+    };
+
+    let resolved = resolver.resolve(url.to_owned()).await;
+    match resolved.error {
+        Some(message) => Outcome::Error(RpcError {
+            code: -32000,
+            message,
+            data: Some(json!({ "url": resolved.url })),
+        }),
+        None => Outcome::Result(json!(resolved)),
+    }
+}
+
+/// Writes a response as a single line.
+async fn write(stdout: &Mutex<Stdout>, response: &Response) -> Result<()> {
+    let mut line = serde_json::to_vec(response)?;
+    line.push(b'\n');
+    let mut stdout = stdout.lock().await;
+    stdout.write_all(&line).await?;
+    stdout.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use eyre::{Report, Result};
+use futures::{
+    future::BoxFuture,
+    stream::{BoxStream, FuturesUnordered},
+    Stream, StreamExt,
+};
+
+use crate::{input::Entry, urls};
+
+/// How many entries of the input are read ahead of the oldest one whose result
+/// wasn't yielded yet, to be interleaved by host.
+const LOOKAHEAD: usize = 1000;
+
+/// Processes the entries of the input concurrently, taking turns between
+/// their hosts, so that the many URLs of a host don't hold back those of the
+/// others, and keeping at most `per_host` URLs of the same host in flight.
+pub struct Scheduler<'a, T, F> {
+    entries: Option<BoxStream<'a, Result<Entry>>>,
+    process: F,
+    concurrency: usize,
+    per_host: usize,
+    /// Whether the results are yielded in the order of the input.
+    ordered: bool,
+    /// The index of the next entry of the input.
+    read: usize,
+    /// The entries that weren't sent yet, by host, and the hosts that have
+    /// some, in the order of their turns.
+    queues: HashMap<String, VecDeque<(usize, Entry)>>,
+    turns: VecDeque<String>,
+    /// How many URLs of each host are in flight.
+    in_flight: HashMap<String, usize>,
+    running: FuturesUnordered<BoxFuture<'a, (usize, String, Result<T>)>>,
+    /// The results that are held until those before them are yielded, and
+    /// the index of the next one to yield, when ordered.
+    done: BTreeMap<usize, Result<T>>,
+    yielded: usize,
+}
+
+impl<'a, T: Send + 'a, F> Scheduler<'a, T, F>
+where
+    F: FnMut(usize, Entry) -> BoxFuture<'a, Result<T>>,
+{
+    /// Processes the given entries with `process`, which is given their index
+    /// in the input, with at most `concurrency` of them in flight.
+    pub fn new(
+        entries: BoxStream<'a, Result<Entry>>,
+        concurrency: usize,
+        per_host: Option<usize>,
+        ordered: bool,
+        process: F,
+    ) -> Self {
+        Scheduler {
+            entries: Some(entries),
+            process,
+            concurrency,
+            per_host: per_host.unwrap_or(usize::MAX),
+            ordered,
+            read: 0,
+            queues: HashMap::new(),
+            turns: VecDeque::new(),
+            in_flight: HashMap::new(),
+            running: FuturesUnordered::new(),
+            done: BTreeMap::new(),
+            yielded: 0,
+        }
+    }
+
+    /// Reads the entries that are ready, up to the lookahead, queueing them by
+    /// host. The errors of the input are returned when not ordered.
+    fn read_entries(&mut self, cx: &mut Context<'_>) -> Option<Report> {
+        while let Some(entries) = &mut self.entries {
+            let ahead = match self.ordered {
+                true => self.read - self.yielded,
+                false => self.read - self.yielded - self.running.len(),
+            };
+            if ahead >= LOOKAHEAD {
+                break;
+            }
+            match entries.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(entry))) => {
+                    let host = urls::domain(&entry.url);
+                    let queue = self.queues.entry(host.clone()).or_default();
+                    if queue.is_empty() {
+                        self.turns.push_back(host);
+                    }
+                    queue.push_back((self.read, entry));
+                }
+                Poll::Ready(Some(Err(error))) if self.ordered => {
+                    self.done.insert(self.read, Err(error));
+                }
+                Poll::Ready(Some(Err(error))) => {
+                    self.read += 1;
+                    self.yielded += 1;
+                    return Some(error);
+                }
+                Poll::Ready(None) => self.entries = None,
+                Poll::Pending => break,
+            }
+            self.read += 1;
+        }
+        None
+    }
+
+    /// Sends the queued entries while there is room, taking turns between
+    /// their hosts.
+    fn send(&mut self) {
+        let mut skipped = 0;
+        while self.running.len() < self.concurrency && skipped < self.turns.len() {
+            let host = self.turns.pop_front().unwrap();
+            let in_flight = self.in_flight.entry(host.clone()).or_default();
+            if *in_flight >= self.per_host {
+                self.turns.push_back(host);
+                skipped += 1;
+                continue;
+            }
+            *in_flight += 1;
+            skipped = 0;
+            let queue = self.queues.get_mut(&host).unwrap();
+            let (index, entry) = queue.pop_front().unwrap();
+            if queue.is_empty() {
+                self.queues.remove(&host);
+            } else {
+                self.turns.push_back(host.clone());
+            }
+            let future = (self.process)(index, entry);
+            self.running
+                .push(Box::pin(async move { (index, host, future.await) }));
+        }
+    }
+}
+
+impl<'a, T: Send + 'a, F> Stream for Scheduler<'a, T, F>
+where
+    F: FnMut(usize, Entry) -> BoxFuture<'a, Result<T>> + Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(error) = this.read_entries(cx) {
+                return Poll::Ready(Some(Err(error)));
+            }
+            if let Some(result) = this.done.remove(&this.yielded) {
+                this.yielded += 1;
+                return Poll::Ready(Some(result));
+            }
+            this.send();
+            match this.running.poll_next_unpin(cx) {
+                Poll::Ready(Some((index, host, result))) => {
+                    let in_flight = this.in_flight.get_mut(&host).unwrap();
+                    *in_flight -= 1;
+                    if *in_flight == 0 {
+                        this.in_flight.remove(&host);
+                    }
+                    if !this.ordered {
+                        this.yielded += 1;
+                        return Poll::Ready(Some(result));
+                    }
+                    this.done.insert(index, result);
+                }
+                Poll::Ready(None) if this.entries.is_none() && this.queues.is_empty() => {
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use eyre::eyre;
+    use futures::{future, stream, FutureExt};
+
+    use super::*;
+
+    /// Returns the entries of the given URLs, with an error of the input for
+    /// each `None`.
+    fn entries(urls: &[Option<&str>]) -> BoxStream<'static, Result<Entry>> {
+        let entries: Vec<_> = urls
+            .iter()
+            .map(|url| url.map(Entry::new).ok_or_else(|| eyre!("invalid line")))
+            .collect();
+        stream::iter(entries).boxed()
+    }
+
+    /// Processes an entry by giving back its URL after a delay that depends
+    /// on its index, so that the later ones complete first.
+    fn delayed(index: usize, entry: Entry) -> BoxFuture<'static, Result<String>> {
+        async move {
+            tokio::time::sleep(Duration::from_millis((5 - index as u64) * 10)).await;
+            Ok(entry.url)
+        }
+        .boxed()
+    }
+
+    /// Collects the results as their URLs, or `error` for the errors.
+    async fn results<'a>(scheduler: impl Stream<Item = Result<String>> + 'a) -> Vec<String> {
+        scheduler
+            .map(|result| result.unwrap_or_else(|_| "error".to_owned()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn yields_the_results_in_the_order_of_the_input() {
+        let urls = [
+            Some("https://a.example/1"),
+            Some("https://b.example/1"),
+            Some("https://a.example/2"),
+            Some("https://c.example/1"),
+            Some("https://b.example/2"),
+        ];
+        let scheduler = Scheduler::new(entries(&urls), 10, None, true, delayed);
+        let expected: Vec<_> = urls.iter().map(|url| url.unwrap().to_owned()).collect();
+        assert_eq!(results(scheduler).await, expected);
+    }
+
+    #[tokio::test]
+    async fn yields_the_results_as_they_complete_when_unordered() {
+        let urls = [
+            Some("https://a.example/1"),
+            Some("https://b.example/1"),
+            Some("https://c.example/1"),
+        ];
+        let scheduler = Scheduler::new(entries(&urls), 10, None, false, delayed);
+        assert_eq!(
+            results(scheduler).await,
+            [
+                "https://c.example/1",
+                "https://b.example/1",
+                "https://a.example/1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn takes_turns_between_the_hosts() {
+        let urls = [
+            Some("https://a.example/1"),
+            Some("https://a.example/2"),
+            Some("https://a.example/3"),
+            Some("https://www.b.example/1"),
+            Some("https://b.example/2"),
+        ];
+        let process = |_, entry: Entry| future::ready(Ok(entry.url)).boxed();
+        let scheduler = Scheduler::new(entries(&urls), 1, None, false, process);
+        assert_eq!(
+            results(scheduler).await,
+            [
+                "https://a.example/1",
+                "https://www.b.example/1",
+                "https://a.example/2",
+                "https://b.example/2",
+                "https://a.example/3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_at_most_per_host_urls_of_a_host_in_flight() {
+        let urls: Vec<_> = (0..12)
+            .map(|i| ["https://a.example/", "https://b.example/"][i % 2])
+            .map(Some)
+            .collect();
+        // The URLs in flight and the most that were, by host.
+        let counts: Arc<Mutex<HashMap<String, (usize, usize)>>> = Arc::default();
+        let process = {
+            let counts = Arc::clone(&counts);
+            move |_, entry: Entry| {
+                let counts = Arc::clone(&counts);
+                async move {
+                    let host = urls::domain(&entry.url);
+                    {
+                        let mut counts = counts.lock().unwrap();
+                        let (now, peak) = counts.entry(host.clone()).or_default();
+                        *now += 1;
+                        *peak = (*peak).max(*now);
+                    }
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    counts.lock().unwrap().get_mut(&host).unwrap().0 -= 1;
+                    Ok(entry.url)
+                }
+                .boxed()
+            }
+        };
+        let scheduler = Scheduler::new(entries(&urls), 10, Some(2), true, process);
+        assert_eq!(results(scheduler).await.len(), urls.len());
+        let counts = counts.lock().unwrap();
+        assert_eq!(counts["a.example"], (0, 2));
+        assert_eq!(counts["b.example"], (0, 2));
+    }
+
+    #[tokio::test]
+    async fn yields_the_errors_of_the_input_at_their_position() {
+        let urls = [
+            Some("https://a.example/1"),
+            None,
+            Some("https://b.example/1"),
+            None,
+        ];
+        let scheduler = Scheduler::new(entries(&urls), 10, None, true, delayed);
+        assert_eq!(
+            results(scheduler).await,
+            [
+                "https://a.example/1",
+                "error",
+                "https://b.example/1",
+                "error"
+            ]
+        );
+        // Unordered, they are yielded as soon as they are read.
+        let scheduler = Scheduler::new(entries(&urls), 10, None, false, delayed);
+        assert_eq!(
+            results(scheduler).await,
+            [
+                "error",
+                "error",
+                "https://b.example/1",
+                "https://a.example/1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn ends_once_the_input_and_the_queues_are_drained() {
+        let scheduler = Scheduler::new(entries(&[]), 10, None, true, delayed);
+        assert!(results(scheduler).await.is_empty());
+        let scheduler = Scheduler::new(entries(&[None, None]), 10, None, true, delayed);
+        assert_eq!(results(scheduler).await, ["error", "error"]);
+    }
+
+    #[test]
+    fn reads_at_most_the_lookahead_past_the_oldest_pending_result() {
+        let urls: Vec<_> = (0..LOOKAHEAD * 2)
+            .map(|i| Some(format!("https://example{i}.com/")))
+            .collect();
+        let urls: Vec<_> = urls.iter().map(|url| url.as_deref()).collect();
+        // The first entry never completes, so none of the results are yielded.
+        let process = |index, entry: Entry| match index {
+            0 => future::pending().boxed(),
+            _ => future::ready(Ok(entry.url)).boxed(),
+        };
+        let mut scheduler = Scheduler::new(entries(&urls), 10, None, true, process);
+        assert!(scheduler.next().now_or_never().is_none());
+        assert_eq!(scheduler.read, LOOKAHEAD);
+        assert_eq!(scheduler.done.len(), LOOKAHEAD - 1);
+    }
+}
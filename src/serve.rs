@@ -0,0 +1,296 @@
+use std::{
+    fmt, fs,
+    net::SocketAddr,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{Result, WrapErr};
+use futures::{stream, StreamExt};
+use hyper::server::accept;
+use reqwest::Response;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, net::UnixListener, sync::Mutex};
+use tracing::info;
+use url::Url;
+
+use crate::{
+    fetch::client,
+    output::process_template,
+    resolver::{Resolved, Resolver},
+};
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on. Either a TCP address, such as `127.0.0.1:8080`,
+    /// or the path of a Unix domain socket, prefixed by `unix:`.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: ListenAddr,
+
+    /// How long resolved titles are kept in the in-memory cache.
+    #[arg(long, default_value = "1h", value_parser = humantime::parse_duration)]
+    cache_ttl: Duration,
+
+    /// Enables the `/save` endpoint, appending the saved links to this file.
+    #[arg(long)]
+    save_file: Option<PathBuf>,
+
+    /// Enables the `/save` endpoint, forwarding the saved links to this URL as
+    /// a JSON `POST` request with the `url`, `title` and `text` fields.
+    #[arg(long)]
+    save_webhook: Option<Url>,
+
+    /// Template of the saved links. Use `%title` and `%url` as placeholders.
+    #[arg(long, default_value = "%title <%url>")]
+    save_template: String,
+}
+
+/// An address the server can listen on.
+#[derive(Clone)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("unix:") {
+            Some(path) if !path.is_empty() => Ok(ListenAddr::Unix(path.into())),
+            Some(_) => Err("missing socket path".into()),
+            None => s.parse().map(ListenAddr::Tcp).map_err(|e| format!("{e}")),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{addr}"),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// State shared by all requests.
+struct AppState {
+    resolver: Resolver,
+    save_file: Option<PathBuf>,
+    save_webhook: Option<Url>,
+    save_template: String,
+    /// Serializes the writes to the save file.
+    save_lock: Mutex<()>,
+}
+
+#[derive(Deserialize)]
+struct TitleQuery {
+    url: String,
+}
+
+/// The query of a `POST /save`, whose URL may be in the body instead.
+#[derive(Deserialize)]
+struct SaveQuery {
+    url: Option<String>,
+}
+
+/// Runs the HTTP server until it is killed. It exposes:
+///
+/// - `GET /title?url=<url>`, which resolves a single URL;
+/// - `POST /titles`, which takes a JSON array of URLs and resolves all of them;
+/// - `GET /metrics`, which exposes metrics in the Prometheus format;
+/// - `GET` or `POST /save?url=<url>`, if enabled, which resolves a single URL
+///   and saves the templated link to the save file or webhook, so that it can
+///   be used by a bookmarklet. A `POST` may give the URL in a form or a JSON
+///   object instead.
+pub async fn serve(args: ServeArgs) -> Result<()> {
+    let save_enabled = args.save_file.is_some() || args.save_webhook.is_some();
+    let state = Arc::new(AppState {
+        resolver: Resolver::new(args.cache_ttl),
+        save_file: args.save_file,
+        save_webhook: args.save_webhook,
+        save_template: args.save_template,
+        save_lock: Mutex::new(()),
+    });
+    let mut app = Router::new()
+        .route("/title", get(title))
+        .route("/titles", post(titles))
+        .route("/metrics", get(metrics));
+    if save_enabled {
+        app = app.route("/save", get(save).post(save_posted));
+    }
+    listen(&args.listen, app.with_state(state)).await
+}
+
+/// Serves the given app on the given address until it is killed.
+pub async fn listen(listen: &ListenAddr, app: Router) -> Result<()> {
+    match listen {
+        ListenAddr::Tcp(addr) => {
+            let server = axum::Server::try_bind(addr)
+                .wrap_err_with(|| format!("failed to listen on `{listen}`"))?;
+            info!("listening on {listen}");
+            server.serve(app.into_make_service()).await?;
+        }
+        ListenAddr::Unix(path) => {
+            let listener = bind_unix_socket(path)?;
+            let connections = stream::unfold(listener, |listener| async move {
+                let conn = listener.accept().await.map(|(stream, _)| stream);
+                Some((conn, listener))
+            });
+            info!("listening on {listen}");
+            axum::Server::builder(accept::from_stream(connections))
+                .serve(app.into_make_service())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Binds a Unix domain socket at the given path.
+pub fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    // A socket left behind by a previous run would make binding fail.
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+    UnixListener::bind(path)
+        .wrap_err_with(|| format!("failed to listen on `unix:{}`", path.display()))
+}
+
+async fn title(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TitleQuery>,
+) -> (StatusCode, Json<Resolved>) {
+    let resolver = &state.resolver;
+    resolver
+        .metrics()
+        .requests
+        .with_label_values(&["title"])
+        .inc();
+    let response = resolver.resolve(query.url).await;
+    let status = match response.error {
+        Some(_) => StatusCode::BAD_GATEWAY,
+        None => StatusCode::OK,
+    };
+    (status, Json(response))
+}
+
+async fn titles(
+    State(state): State<Arc<AppState>>,
+    Json(urls): Json<Vec<String>>,
+) -> Json<Vec<Resolved>> {
+    let resolver = &state.resolver;
+    resolver
+        .metrics()
+        .requests
+        .with_label_values(&["titles"])
+        .inc();
+    let responses = stream::iter(urls)
+        .map(|url| resolver.resolve(url))
+        .buffered(10)
+        .collect()
+        .await;
+    Json(responses)
+}
+
+async fn save(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TitleQuery>,
+) -> (StatusCode, String) {
+    save_url(&state, query.url).await
+}
+
+/// Saves the URL of the body of the request, either a form or a JSON object
+/// with a `url` field, or else of its query.
+async fn save_posted(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SaveQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let posted = match content_type.split(';').next().unwrap_or_default().trim() {
+        "application/json" => match serde_json::from_slice::<SaveQuery>(&body) {
+            Ok(posted) => posted.url,
+            Err(error) => return (StatusCode::BAD_REQUEST, format!("invalid JSON: {error}\n")),
+        },
+        "application/x-www-form-urlencoded" => url::form_urlencoded::parse(&body)
+            .find(|(key, _)| key == "url")
+            .map(|(_, url)| url.into_owned()),
+        _ => None,
+    };
+    match posted.or(query.url) {
+        Some(url) => save_url(&state, url).await,
+        None => (StatusCode::BAD_REQUEST, "missing `url`\n".to_owned()),
+    }
+}
+
+/// Resolves the given URL and saves its templated link.
+async fn save_url(state: &AppState, url: String) -> (StatusCode, String) {
+    let resolver = &state.resolver;
+    resolver
+        .metrics()
+        .requests
+        .with_label_values(&["save"])
+        .inc();
+    let resolved = resolver.resolve(url).await;
+    if let Some(error) = resolved.error {
+        return (
+            StatusCode::BAD_GATEWAY,
+            format!("failed to resolve: {error}\n"),
+        );
+    }
+    let title = resolved.title.as_deref().unwrap_or(&resolved.url);
+    let text = process_template(&state.save_template, title, &resolved.url);
+
+    match save_link(state, &resolved, &text).await {
+        Ok(()) => (StatusCode::OK, format!("saved: {text}\n")),
+        Err(error) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to save: {error:#}\n"),
+        ),
+    }
+}
+
+/// Appends the given templated link to the save file and forwards it to the
+/// webhook, whichever are configured.
+async fn save_link(state: &AppState, resolved: &Resolved, text: &str) -> Result<()> {
+    if let Some(path) = &state.save_file {
+        let _guard = state.save_lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .wrap_err_with(|| format!("failed to open `{}`", path.display()))?;
+        file.write_all(format!("{text}\n").as_bytes()).await?;
+    }
+    if let Some(webhook) = &state.save_webhook {
+        client()
+            .post(webhook.clone())
+            .json(&json!({ "url": resolved.url, "title": resolved.title, "text": text }))
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+            .wrap_err_with(|| format!("failed to post to `{webhook}`"))?;
+    }
+    Ok(())
+}
+
+/// Encodes the metrics of the server.
+async fn metrics(State(state): State<Arc<AppState>>) -> String {
+    state.resolver.metrics().encode()
+}
@@ -0,0 +1,176 @@
+use eyre::{Result, WrapErr};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde_json::Value;
+use url::Url;
+
+use crate::fetch::{request, Page, Validators};
+
+mod arxiv;
+mod crossref;
+mod custom;
+mod github;
+mod hacker_news;
+mod mastodon;
+mod oembed;
+mod reddit;
+mod stack_exchange;
+mod twitter;
+mod wikipedia;
+
+pub use custom::{
+    fields as custom_fields, is_enabled as has_custom_resolvers, set as set_custom_resolvers,
+};
+pub use twitter::set_nitter;
+
+/// How many characters of the text of a post its title has at most.
+const POST_TITLE_LENGTH: usize = 80;
+
+/// The fields that the APIs of the sites give to their pages. All the results
+/// have them when the APIs are used, empty if their API didn't give them.
+pub const FIELDS: &[&str] = &["article_url", "author", "description", "sitename", "year"];
+
+/// The title of a page and the fields given by the API of its site.
+#[derive(Default)]
+struct Metadata {
+    title: Option<String>,
+    fields: Vec<(&'static str, String)>,
+}
+
+/// Resolves the given URL through the API of its site, if one of the resolvers
+/// declared in the configuration file matches it or, if `builtin` is set, if
+/// it is one of the sites whose pages are better resolved that way: their
+/// titles are generic or only set by scripts, or their pages are heavy or
+/// often behind consent walls. Returns `None` for the other URLs.
+pub async fn resolve(url: &str, builtin: bool) -> Option<Result<Page>> {
+    let metadata = match custom::resolve(url).await {
+        Some(metadata) => metadata,
+        None if builtin => resolve_builtin(url).await?,
+        None => return None,
+    };
+    Some(metadata.map(|metadata| {
+        Page {
+            url: url.to_owned(),
+            unwrapped_url: None,
+            status: StatusCode::OK,
+            title: metadata.title,
+            html: String::new(),
+            validators: Validators::default(),
+            fields: metadata
+                .fields
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        }
+    }))
+}
+
+/// Resolves the given URL through the built-in resolver of its site, if any.
+async fn resolve_builtin(url: &str) -> Option<Result<Metadata>> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let metadata = match host {
+        "youtube.com" | "m.youtube.com" | "music.youtube.com" | "youtu.be" => {
+            oembed::resolve("https://www.youtube.com/oembed", url).await
+        }
+        "vimeo.com" | "player.vimeo.com" => {
+            oembed::resolve("https://vimeo.com/api/oembed.json", url).await
+        }
+        "twitter.com" | "mobile.twitter.com" | "x.com" | "mobile.x.com" => {
+            let (user, id) = twitter::status(&parsed)?;
+            twitter::resolve(&user, &id).await
+        }
+        "reddit.com" | "old.reddit.com" | "new.reddit.com" | "np.reddit.com" | "m.reddit.com"
+        | "redd.it" => reddit::resolve(&reddit::post(&parsed)?).await,
+        "news.ycombinator.com" => hacker_news::resolve(&hacker_news::item(&parsed)?).await,
+        "github.com" => github::resolve(&github::path(&parsed)?).await,
+        "arxiv.org" | "export.arxiv.org" => arxiv::resolve(&arxiv::id(&parsed)?).await,
+        "doi.org" | "dx.doi.org" => {
+            let doi = parsed.path().trim_start_matches('/');
+            if doi.is_empty() {
+                return None;
+            }
+            crossref::resolve(doi).await
+        }
+        _ if stack_exchange::is_site(host) => {
+            stack_exchange::resolve(host, &stack_exchange::question(&parsed)?).await
+        }
+        _ if host.ends_with(".wikipedia.org") => {
+            let (language, article) = wikipedia::article(&parsed)?;
+            wikipedia::resolve(&language, &article).await
+        }
+        // Mastodon instances may be on any host, so that their statuses are
+        // only recognized by their URLs.
+        _ => mastodon::resolve(&mastodon::status(&parsed)?).await,
+    };
+    Some(metadata)
+}
+
+/// Fetches the given URL of an API, which answers with JSON.
+async fn get_json(url: &str) -> Result<Value> {
+    send_json(request(Method::GET, url).await?, url).await
+}
+
+/// Sends the given request to the given URL of an API, which answers with
+/// JSON.
+async fn send_json(request: RequestBuilder, url: &str) -> Result<Value> {
+    request
+        .send()
+        .await
+        .wrap_err_with(|| format!("failed to get: `{url}`"))?
+        .error_for_status()?
+        .json()
+        .await
+        .wrap_err_with(|| format!("invalid JSON from `{url}`"))
+}
+
+/// Returns the title of a post of a social network, made of its author and
+/// the first line of its text, shortened if needed.
+fn post_title(author: &str, text: &str) -> String {
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty());
+    let line = line.unwrap_or_default();
+    let mut title = format!("{author}: ");
+    if line.chars().count() > POST_TITLE_LENGTH {
+        title.extend(line.chars().take(POST_TITLE_LENGTH - 1));
+        title.push('…');
+    } else {
+        title.push_str(line);
+    }
+    title
+}
+
+/// Returns the given string of a JSON value, if it isn't empty.
+fn text(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn titles_posts_with_their_author_and_first_line() {
+        assert_eq!(
+            post_title("Ferris", "\n  Hello, world!  \nMore text"),
+            "Ferris: Hello, world!"
+        );
+        let long = "a".repeat(POST_TITLE_LENGTH + 10);
+        let title = post_title("Ferris", &long);
+        assert_eq!(title.chars().count(), "Ferris: ".len() + POST_TITLE_LENGTH);
+        assert!(title.ends_with("a…"));
+        assert_eq!(post_title("Ferris", ""), "Ferris: ");
+    }
+
+    #[test]
+    fn reads_non_empty_strings() {
+        assert_eq!(text(&json!(" A ")).as_deref(), Some("A"));
+        assert_eq!(text(&json!("  ")), None);
+        assert_eq!(text(&json!(1)), None);
+    }
+}
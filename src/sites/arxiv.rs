@@ -0,0 +1,146 @@
+use eyre::{eyre, Result, WrapErr};
+use roxmltree::{Document, Node};
+use url::Url;
+
+use super::Metadata;
+use crate::fetch::get;
+
+/// The endpoint of the API of arXiv that gives the metadata of papers.
+const QUERY: &str = "https://export.arxiv.org/api/query";
+
+/// The namespace of the Atom feeds returned by the API.
+const ATOM: &str = "http://www.w3.org/2005/Atom";
+
+/// Returns the ID of the paper at the given URL, if it is the page of its
+/// abstract or its PDF.
+pub fn id(url: &Url) -> Option<String> {
+    let path = url.path();
+    let id = path
+        .strip_prefix("/abs/")
+        .or_else(|| path.strip_prefix("/pdf/"))?;
+    let id = id.trim_end_matches('/');
+    let id = id.strip_suffix(".pdf").unwrap_or(id);
+    (!id.is_empty()).then(|| id.to_owned())
+}
+
+/// Resolves the paper with the given ID through the API of arXiv, which
+/// gives its title without the ID prefixed to it by the page of its
+/// abstract, its authors and the year it was submitted in.
+pub async fn resolve(id: &str) -> Result<Metadata> {
+    let api = Url::parse_with_params(QUERY, [("id_list", id)])?;
+    let feed = get(api.as_str())
+        .await?
+        .error_for_status()?
+        .text()
+        .await
+        .wrap_err("invalid response from arXiv")?;
+    parse(&feed, id)
+}
+
+/// Reads the metadata of the paper with the given ID from the given feed
+/// returned by the API.
+fn parse(feed: &str, id: &str) -> Result<Metadata> {
+    let document = Document::parse(feed).wrap_err("invalid feed from arXiv")?;
+    // An invalid ID gives an entry describing the error.
+    let entry = child(document.root_element(), "entry")
+        .filter(|entry| text(child(*entry, "id")).is_some_and(|id| !id.contains("/api/errors")))
+        .ok_or_else(|| eyre!("no paper with the arXiv ID `{id}`"))?;
+    let mut metadata = Metadata {
+        title: text(child(entry, "title")),
+        ..Default::default()
+    };
+    let authors: Vec<_> = entry
+        .children()
+        .filter(|node| node.has_tag_name((ATOM, "author")))
+        .filter_map(|author| text(child(author, "name")))
+        .collect();
+    if !authors.is_empty() {
+        metadata.fields.push(("author", authors.join(", ")));
+    }
+    if let Some(published) = text(child(entry, "published")) {
+        metadata
+            .fields
+            .push(("year", published.chars().take(4).collect()));
+    }
+    Ok(metadata)
+}
+
+/// Returns the child of the given node of the feed with the given name.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.has_tag_name((ATOM, name)))
+}
+
+/// Returns the text of the given node of the feed, with its whitespace
+/// collapsed, if it isn't empty.
+fn text(node: Option<Node<'_, '_>>) -> Option<String> {
+    let text = node?
+        .text()?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the ID of the paper at the given URL.
+    fn paper(url: &str) -> Option<String> {
+        id(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_abstracts_and_pdfs() {
+        assert_eq!(
+            paper("https://arxiv.org/abs/2401.12345v2").as_deref(),
+            Some("2401.12345v2")
+        );
+        assert_eq!(
+            paper("https://arxiv.org/pdf/2401.12345.pdf").as_deref(),
+            Some("2401.12345")
+        );
+        assert_eq!(
+            paper("https://arxiv.org/abs/hep-th/9901001/").as_deref(),
+            Some("hep-th/9901001")
+        );
+        assert_eq!(paper("https://arxiv.org/abs/"), None);
+        assert_eq!(paper("https://arxiv.org/list/cs.PL/recent"), None);
+    }
+
+    #[test]
+    fn reads_papers_from_feeds() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+            <entry>
+                <id>http://arxiv.org/abs/2401.12345v2</id>
+                <published>2024-01-22T18:00:00Z</published>
+                <title>A Paper
+                    About Things</title>
+                <author><name>Ada Lovelace</name></author>
+                <author><name>Alan Turing</name></author>
+            </entry>
+        </feed>"#;
+        let metadata = parse(feed, "2401.12345").unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("A Paper About Things"));
+        assert_eq!(
+            metadata.fields,
+            [
+                ("author", "Ada Lovelace, Alan Turing".to_owned()),
+                ("year", "2024".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_feeds_describing_errors() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+            <entry>
+                <id>http://arxiv.org/api/errors#incorrect_id_format_for_1234</id>
+                <title>Error</title>
+            </entry>
+        </feed>"#;
+        assert!(parse(feed, "1234").is_err());
+        assert!(parse("<feed xmlns=\"http://www.w3.org/2005/Atom\"/>", "1234").is_err());
+    }
+}
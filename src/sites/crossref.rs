@@ -0,0 +1,87 @@
+use eyre::{eyre, Result};
+use serde_json::Value;
+
+use super::{get_json, text, Metadata};
+
+/// The endpoint of the API of CrossRef that gives the metadata of a work.
+const WORKS: &str = "https://api.crossref.org/works/";
+
+/// Resolves the work with the given DOI through CrossRef, which gives its
+/// title, its authors and the year it was published in, rather than the
+/// title of the landing page of its publisher.
+pub async fn resolve(doi: &str) -> Result<Metadata> {
+    let response = get_json(&format!("{WORKS}{doi}")).await?;
+    parse(&response, doi)
+}
+
+/// Reads the metadata of the work with the given DOI from the given response
+/// of the API.
+fn parse(response: &Value, doi: &str) -> Result<Metadata> {
+    let work = &response["message"];
+    let title = text(&work["title"][0]).ok_or_else(|| eyre!("no work with the DOI `{doi}`"))?;
+    let mut metadata = Metadata {
+        title: Some(title),
+        ..Default::default()
+    };
+    let authors: Vec<_> = work["author"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(author_name)
+        .collect();
+    if !authors.is_empty() {
+        metadata.fields.push(("author", authors.join(", ")));
+    }
+    if let Some(year) = work["issued"]["date-parts"][0][0].as_u64() {
+        metadata.fields.push(("year", year.to_string()));
+    }
+    Ok(metadata)
+}
+
+/// Returns the name of the given author of a work, who may be a person or an
+/// organization.
+fn author_name(author: &Value) -> Option<String> {
+    match (text(&author["given"]), text(&author["family"])) {
+        (Some(given), Some(family)) => Some(format!("{given} {family}")),
+        (None, Some(family)) => Some(family),
+        _ => text(&author["name"]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_works_from_responses() {
+        let response = json!({
+            "message": {
+                "title": [" A Paper About Things "],
+                "author": [
+                    { "given": "Ada", "family": "Lovelace" },
+                    { "family": "Turing" },
+                    { "name": "The Consortium" },
+                    { "given": "Nobody" },
+                ],
+                "issued": { "date-parts": [[2024, 1, 22]] },
+            },
+        });
+        let metadata = parse(&response, "10.1000/1").unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("A Paper About Things"));
+        assert_eq!(
+            metadata.fields,
+            [
+                ("author", "Ada Lovelace, Turing, The Consortium".to_owned()),
+                ("year", "2024".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_responses_without_a_title() {
+        assert!(parse(&json!({ "message": { "title": [] } }), "10.1000/1").is_err());
+        assert!(parse(&json!({ "status": "error" }), "10.1000/1").is_err());
+    }
+}
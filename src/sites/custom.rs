@@ -0,0 +1,187 @@
+use eyre::{bail, Result, WrapErr};
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde_json::Value;
+
+use super::{get_json, Metadata};
+use crate::config;
+
+/// The resolvers declared in the configuration file.
+static RESOLVERS: OnceCell<Vec<Resolver>> = OnceCell::new();
+
+/// A resolver of the URLs that match a pattern through an API.
+struct Resolver {
+    pattern: Regex,
+    endpoint: String,
+    title: String,
+    fields: Vec<(String, String)>,
+}
+
+/// Sets the resolvers declared in the configuration file. May only be called
+/// once.
+pub fn set(resolvers: &[config::Resolver]) -> Result<()> {
+    let resolvers = resolvers.iter().map(compile).collect::<Result<_>>()?;
+    let _ = RESOLVERS.set(resolvers);
+    Ok(())
+}
+
+/// Compiles the given resolver of the configuration file.
+fn compile(resolver: &config::Resolver) -> Result<Resolver> {
+    let pattern = Regex::new(&resolver.pattern)
+        .wrap_err_with(|| format!("invalid pattern `{}`", resolver.pattern))?;
+    let pointers = std::iter::once(&resolver.title).chain(resolver.fields.values());
+    if let Some(pointer) = pointers
+        .clone()
+        .find(|p| !p.is_empty() && !p.starts_with('/'))
+    {
+        bail!("invalid JSON pointer `{pointer}`, which should start with `/`");
+    }
+    Ok(Resolver {
+        pattern,
+        endpoint: resolver.endpoint.clone(),
+        title: resolver.title.clone(),
+        fields: resolver
+            .fields
+            .iter()
+            .map(|(name, pointer)| (name.clone(), pointer.clone()))
+            .collect(),
+    })
+}
+
+/// Whether some resolvers were declared.
+pub fn is_enabled() -> bool {
+    RESOLVERS
+        .get()
+        .is_some_and(|resolvers| !resolvers.is_empty())
+}
+
+/// Returns the names of the fields of the declared resolvers, in order and
+/// without duplicates.
+pub fn fields() -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    for resolver in RESOLVERS.get().into_iter().flatten() {
+        for (name, _) in &resolver.fields {
+            if !fields.contains(&name.as_str()) {
+                fields.push(name.as_str());
+            }
+        }
+    }
+    fields
+}
+
+/// Resolves the given URL through the first declared resolver whose pattern
+/// matches it, if any. Its endpoint is given the capture groups of the
+/// pattern, as `$1` or `${name}`, and the title and the fields are read from
+/// the JSON response at their JSON pointers.
+pub async fn resolve(url: &str) -> Option<Result<Metadata>> {
+    let resolvers = RESOLVERS.get()?;
+    let (resolver, endpoint) = resolvers
+        .iter()
+        .find_map(|resolver| Some((resolver, resolver.endpoint(url)?)))?;
+    Some(fetch(resolver, &endpoint).await)
+}
+
+impl Resolver {
+    /// Returns the endpoint for the given URL, if it matches the pattern.
+    fn endpoint(&self, url: &str) -> Option<String> {
+        let captures = self.pattern.captures(url)?;
+        let mut endpoint = String::new();
+        captures.expand(&self.endpoint, &mut endpoint);
+        Some(endpoint)
+    }
+}
+
+/// Fetches the given endpoint of the given resolver, reading the title and
+/// the fields from its response.
+async fn fetch(resolver: &'static Resolver, endpoint: &str) -> Result<Metadata> {
+    let response = get_json(endpoint).await?;
+    read(resolver, endpoint, &response)
+}
+
+/// Reads the title and the fields of the given resolver from the given
+/// response of its endpoint.
+fn read(resolver: &'static Resolver, endpoint: &str, response: &Value) -> Result<Metadata> {
+    let string = |pointer: &str| match response.pointer(pointer)? {
+        Value::String(string) => Some(string.trim().to_owned()).filter(|s| !s.is_empty()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    };
+    let Some(title) = string(&resolver.title) else {
+        bail!("`{endpoint}` has no title at `{}`", resolver.title);
+    };
+    Ok(Metadata {
+        title: Some(title),
+        fields: resolver
+            .fields
+            .iter()
+            .filter_map(|(name, pointer)| Some((name.as_str(), string(pointer)?)))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Compiles the resolver described by the given JSON.
+    fn resolver(resolver: Value) -> Result<Resolver> {
+        compile(&serde_json::from_value(resolver).unwrap())
+    }
+
+    #[test]
+    fn fills_endpoints_with_the_capture_groups() {
+        let resolver = resolver(json!({
+            "pattern": "^https://example\\.com/(?P<kind>[a-z]+)/([0-9]+)",
+            "endpoint": "https://api.example.com/${kind}?id=$2",
+            "title": "/title",
+        }))
+        .unwrap();
+        assert_eq!(
+            resolver
+                .endpoint("https://example.com/video/42?t=1")
+                .as_deref(),
+            Some("https://api.example.com/video?id=42")
+        );
+        assert_eq!(resolver.endpoint("https://example.org/video/42"), None);
+    }
+
+    #[test]
+    fn reads_titles_and_fields_at_their_pointers() {
+        let resolver = Box::leak(Box::new(
+            resolver(json!({
+                "pattern": ".",
+                "endpoint": "https://api.example.com",
+                "title": "/data/title",
+                "fields": { "author": "/data/author/0", "views": "/views", "missing": "/none" },
+            }))
+            .unwrap(),
+        ));
+        let response = json!({
+            "data": { "title": "  A Title ", "author": ["Someone"] },
+            "views": 1000,
+        });
+        let metadata = read(resolver, "https://api.example.com", &response).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("A Title"));
+        assert_eq!(
+            metadata.fields,
+            [
+                ("author", "Someone".to_owned()),
+                ("views", "1000".to_owned())
+            ]
+        );
+        let response = json!({ "data": { "title": "" } });
+        assert!(read(resolver, "https://api.example.com", &response).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_patterns_and_pointers() {
+        let invalid = |pattern: &str, title: &str| {
+            resolver(json!({ "pattern": pattern, "endpoint": "", "title": title })).is_err()
+        };
+        assert!(invalid("(", "/title"));
+        assert!(invalid(".", "title"));
+        assert!(!invalid(".", ""));
+    }
+}
@@ -0,0 +1,217 @@
+use eyre::{eyre, Result, WrapErr};
+use once_cell::sync::Lazy;
+use reqwest::{header::ACCEPT, Method};
+use serde_json::Value;
+use url::Url;
+
+use super::{send_json, text, Metadata};
+use crate::fetch::{error_status, request};
+
+/// The endpoint of the API of GitHub.
+const API: &str = "https://api.github.com";
+
+/// The token with which the API is used, which raises its rate limit.
+static TOKEN: Lazy<Option<String>> = Lazy::new(|| {
+    std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+});
+
+/// The first segments of the paths of GitHub that aren't the names of users or
+/// organizations, so that their pages aren't taken for repositories.
+const RESERVED: &[&str] = &[
+    "about",
+    "apps",
+    "collections",
+    "enterprise",
+    "events",
+    "explore",
+    "features",
+    "marketplace",
+    "notifications",
+    "orgs",
+    "pricing",
+    "settings",
+    "sponsors",
+    "topics",
+    "trending",
+    "users",
+];
+
+/// Returns the path in the API of the repository, issue or pull request at
+/// the given URL, if it is one of them.
+pub fn path(url: &Url) -> Option<String> {
+    let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    if RESERVED.contains(segments.first()?) {
+        return None;
+    }
+    match segments[..] {
+        [owner, repo] => Some(format!("repos/{owner}/{repo}")),
+        // Pull requests are issues too, whose endpoint tells them apart.
+        [owner, repo, "issues" | "pull", number, ..]
+            if number.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            Some(format!("repos/{owner}/{repo}/issues/{number}"))
+        }
+        _ => None,
+    }
+}
+
+/// Resolves the repository, issue or pull request at the given path of the
+/// API. Repositories are titled with their name and description, and issues
+/// with their number and title.
+pub async fn resolve(path: &str) -> Result<Metadata> {
+    let url = format!("{API}/{path}");
+    let mut request = request(Method::GET, &url)
+        .await?
+        .header(ACCEPT, "application/vnd.github+json");
+    if let Some(token) = TOKEN.as_deref() {
+        request = request.bearer_auth(token);
+    }
+    let response = match send_json(request, &url).await {
+        Err(error) if TOKEN.is_none() && matches!(error_status(&error), Some(403 | 429)) => {
+            return Err(error)
+                .wrap_err("rate limited by GitHub; a token may be set in `GITHUB_TOKEN`");
+        }
+        response => response?,
+    };
+    metadata(&response).wrap_err_with(|| format!("invalid response from `{url}`"))
+}
+
+/// Reads the metadata of a repository, an issue or a pull request from the
+/// given response of the API. Repositories are titled with their name and
+/// their description, and the others with their kind, number and title.
+fn metadata(response: &Value) -> Result<Metadata> {
+    let title = match &response["number"] {
+        Value::Null => {
+            let name = text(&response["full_name"]).ok_or_else(|| eyre!("no repository"))?;
+            match text(&response["description"]) {
+                Some(description) => format!("{name}: {description}"),
+                None => name,
+            }
+        }
+        number => {
+            let kind = match response["pull_request"].is_null() {
+                true => "Issue",
+                false => "Pull request",
+            };
+            let title = text(&response["title"]).unwrap_or_default();
+            format!("{kind} #{number}: {title}")
+        }
+    };
+    let mut metadata = Metadata {
+        title: Some(title),
+        ..Default::default()
+    };
+    let author = match response["user"].is_null() {
+        true => &response["owner"]["login"],
+        false => &response["user"]["login"],
+    };
+    if let Some(author) = text(author) {
+        metadata.fields.push(("author", author));
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the API path of the given GitHub URL.
+    fn api_path(url: &str) -> Option<String> {
+        path(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_repositories_issues_and_pull_requests() {
+        assert_eq!(
+            api_path("https://github.com/rust-lang/rust/").as_deref(),
+            Some("repos/rust-lang/rust")
+        );
+        assert_eq!(
+            api_path("https://github.com/rust-lang/rust/issues/1").as_deref(),
+            Some("repos/rust-lang/rust/issues/1")
+        );
+        assert_eq!(
+            api_path("https://github.com/rust-lang/rust/pull/2/files").as_deref(),
+            Some("repos/rust-lang/rust/issues/2")
+        );
+        assert_eq!(api_path("https://github.com/rust-lang"), None);
+        assert_eq!(api_path("https://github.com/"), None);
+        assert_eq!(
+            api_path("https://github.com/rust-lang/rust/issues/new"),
+            None
+        );
+        assert_eq!(
+            api_path("https://github.com/rust-lang/rust/tree/master"),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_the_pages_that_arent_repositories() {
+        for url in [
+            "https://github.com/topics/rust",
+            "https://github.com/orgs/rust-lang",
+            "https://github.com/settings/profile",
+            "https://github.com/features/actions",
+            "https://github.com/marketplace/actions",
+            "https://github.com/sponsors/someone",
+            "https://github.com/apps/dependabot",
+        ] {
+            assert_eq!(api_path(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn titles_repositories_with_their_description() {
+        let response = json!({
+            "full_name": "rust-lang/rust",
+            "description": "Empowering everyone to build reliable and efficient software.",
+            "owner": { "login": "rust-lang" },
+        });
+        let repository = metadata(&response).unwrap();
+        assert_eq!(
+            repository.title.as_deref(),
+            Some("rust-lang/rust: Empowering everyone to build reliable and efficient software.")
+        );
+        assert_eq!(repository.fields, [("author", "rust-lang".to_owned())]);
+        let response = json!({ "full_name": "someone/dotfiles", "description": null });
+        let repository = metadata(&response).unwrap();
+        assert_eq!(repository.title.as_deref(), Some("someone/dotfiles"));
+        assert!(repository.fields.is_empty());
+    }
+
+    #[test]
+    fn tells_issues_and_pull_requests_apart() {
+        let issue = json!({
+            "number": 1,
+            "title": "Crash on empty input",
+            "user": { "login": "reporter" },
+            "owner": null,
+        });
+        let issue = metadata(&issue).unwrap();
+        assert_eq!(
+            issue.title.as_deref(),
+            Some("Issue #1: Crash on empty input")
+        );
+        assert_eq!(issue.fields, [("author", "reporter".to_owned())]);
+        let pull_request = json!({
+            "number": 2,
+            "title": "Fix the crash on empty input",
+            "user": { "login": "contributor" },
+            "pull_request": { "url": "https://api.github.com/repos/o/r/pulls/2" },
+        });
+        assert_eq!(
+            metadata(&pull_request).unwrap().title.as_deref(),
+            Some("Pull request #2: Fix the crash on empty input")
+        );
+    }
+
+    #[test]
+    fn rejects_responses_without_a_repository() {
+        assert!(metadata(&json!({ "message": "Not Found" })).is_err());
+    }
+}
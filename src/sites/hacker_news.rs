@@ -0,0 +1,117 @@
+use eyre::{bail, Result};
+use scraper::Html;
+use serde_json::Value;
+use url::Url;
+
+use super::{get_json, post_title, text, Metadata};
+
+/// The endpoint of the API of Hacker News that gives its items.
+const ITEMS: &str = "https://hacker-news.firebaseio.com/v0/item/";
+
+/// Returns the ID of the item at the given URL, if it is one.
+pub fn item(url: &Url) -> Option<String> {
+    if url.path() != "/item" {
+        return None;
+    }
+    let (_, id) = url.query_pairs().find(|(name, _)| name == "id")?;
+    (!id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())).then(|| id.into_owned())
+}
+
+/// Resolves the item with the given ID through the API of Hacker News. Stories
+/// are titled with their title, and give the URL of the article they link to,
+/// if any, while comments are titled with their author and text.
+pub async fn resolve(id: &str) -> Result<Metadata> {
+    let item = get_json(&format!("{ITEMS}{id}.json")).await?;
+    if item.is_null() {
+        bail!("no Hacker News item with the ID {id}");
+    }
+    Ok(metadata(&item))
+}
+
+/// Reads the metadata of the given item, a story or a comment.
+fn metadata(item: &Value) -> Metadata {
+    let author = text(&item["by"]);
+    let title = text(&item["title"]).or_else(|| {
+        // The text of the comments is HTML, whose paragraphs start with a
+        // `<p>` and are the lines of the text.
+        let html = text(&item["text"])?.replace("<p>", "\n<p>");
+        let text: String = Html::parse_fragment(&html).root_element().text().collect();
+        Some(post_title(author.as_deref().unwrap_or("[deleted]"), &text))
+    });
+    let mut metadata = Metadata {
+        title,
+        ..Default::default()
+    };
+    if let Some(author) = author {
+        metadata.fields.push(("author", author));
+    }
+    if let Some(article) = text(&item["url"]) {
+        metadata.fields.push(("article_url", article));
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the ID of the item at the given URL.
+    fn id(url: &str) -> Option<String> {
+        item(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_items() {
+        assert_eq!(
+            id("https://news.ycombinator.com/item?id=39037271").as_deref(),
+            Some("39037271")
+        );
+        assert_eq!(
+            id("https://news.ycombinator.com/item?p=2&id=1").as_deref(),
+            Some("1")
+        );
+        assert_eq!(id("https://news.ycombinator.com/item?id="), None);
+        assert_eq!(id("https://news.ycombinator.com/item?id=abc"), None);
+        assert_eq!(id("https://news.ycombinator.com/user?id=pg"), None);
+    }
+
+    #[test]
+    fn titles_stories_with_their_title() {
+        let story = metadata(&json!({
+            "type": "story",
+            "by": "someone",
+            "title": "Show HN: A tool that titles links",
+            "url": "https://example.com/tool",
+        }));
+        assert_eq!(
+            story.title.as_deref(),
+            Some("Show HN: A tool that titles links")
+        );
+        assert_eq!(
+            story.fields,
+            [
+                ("author", "someone".to_owned()),
+                ("article_url", "https://example.com/tool".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn titles_comments_with_their_author_and_text() {
+        let comment = metadata(&json!({
+            "type": "comment",
+            "by": "someone",
+            "text": "I&#x27;ve used it <i>for years</i>.<p>Second paragraph.",
+        }));
+        assert_eq!(
+            comment.title.as_deref(),
+            Some("someone: I've used it for years.")
+        );
+        assert_eq!(comment.fields, [("author", "someone".to_owned())]);
+        let deleted = metadata(&json!({ "type": "comment", "text": "[flagged]" }));
+        assert_eq!(deleted.title.as_deref(), Some("[deleted]: [flagged]"));
+        assert!(deleted.fields.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use eyre::{bail, Result, WrapErr};
+use scraper::Html;
+use serde_json::Value;
+use url::Url;
+
+use super::{get_json, post_title, text, Metadata};
+
+/// Returns the endpoint of the API of the instance for the status at the given
+/// URL, if it is the URL of a status, as in `https://mastodon.social/@user/1`
+/// or `https://mastodon.social/users/user/statuses/1`.
+pub fn status(url: &Url) -> Option<Url> {
+    let segments: Vec<_> = url.path_segments()?.collect();
+    let id = match segments[..] {
+        [user, id] if user.starts_with('@') => id,
+        ["users", _, "statuses", id] => id,
+        _ => return None,
+    };
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    url.join(&format!("/api/v1/statuses/{id}")).ok()
+}
+
+/// Resolves the status at the given endpoint of the API of its instance to a
+/// title made of its author and the first line of its text, or its content
+/// warning, if it has one.
+pub async fn resolve(api: &Url) -> Result<Metadata> {
+    let status = get_json(api.as_str()).await?;
+    metadata(&status).wrap_err_with(|| format!("invalid response from `{api}`"))
+}
+
+/// Reads the metadata of the given status of the API.
+fn metadata(status: &Value) -> Result<Metadata> {
+    // Instances answer with an error of their own for the missing statuses.
+    if status["id"].is_null() {
+        bail!("no status");
+    }
+    let account = &status["account"];
+    let author = text(&account["display_name"])
+        .or_else(|| text(&account["acct"]))
+        .unwrap_or_default();
+    let text = match text(&status["spoiler_text"]) {
+        Some(warning) => warning,
+        None => {
+            // The content is HTML, whose paragraphs and line breaks are the
+            // lines of the text.
+            let content = status["content"].as_str().unwrap_or_default();
+            let content = content
+                .replace("</p>", "</p>\n")
+                .replace("<br>", "\n")
+                .replace("<br />", "\n");
+            Html::parse_fragment(&content)
+                .root_element()
+                .text()
+                .collect()
+        }
+    };
+    Ok(Metadata {
+        title: Some(post_title(&author, &text)),
+        fields: vec![("author", author)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the endpoint of the API for the status at the given URL.
+    fn api(url: &str) -> Option<String> {
+        status(&Url::parse(url).unwrap()).map(String::from)
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_statuses() {
+        assert_eq!(
+            api("https://mastodon.social/@Gargron/111111111111111111").as_deref(),
+            Some("https://mastodon.social/api/v1/statuses/111111111111111111")
+        );
+        assert_eq!(
+            api("https://hachyderm.io/users/someone/statuses/42").as_deref(),
+            Some("https://hachyderm.io/api/v1/statuses/42")
+        );
+        assert_eq!(api("https://mastodon.social/@Gargron"), None);
+        assert_eq!(api("https://mastodon.social/@Gargron/media"), None);
+        assert_eq!(api("https://example.com/blog/42"), None);
+    }
+
+    #[test]
+    fn titles_statuses_with_their_author_and_first_line() {
+        let status = metadata(&json!({
+            "id": "1",
+            "account": { "display_name": "Someone", "acct": "someone@example.social" },
+            "spoiler_text": "",
+            "content": "<p>First line<br>second line</p><p>Another paragraph</p>",
+        }))
+        .unwrap();
+        assert_eq!(status.title.as_deref(), Some("Someone: First line"));
+        assert_eq!(status.fields, [("author", "Someone".to_owned())]);
+        let status = metadata(&json!({
+            "id": "2",
+            "account": { "display_name": "", "acct": "someone" },
+            "spoiler_text": "Spoilers for the finale",
+            "content": "<p>The ending</p>",
+        }))
+        .unwrap();
+        assert_eq!(
+            status.title.as_deref(),
+            Some("someone: Spoilers for the finale")
+        );
+    }
+
+    #[test]
+    fn rejects_errors_of_the_instances() {
+        assert!(metadata(&json!({ "error": "Record not found" })).is_err());
+    }
+}
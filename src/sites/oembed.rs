@@ -0,0 +1,19 @@
+use eyre::Result;
+use url::Url;
+
+use super::{get_json, text, Metadata};
+
+/// Resolves the given URL through the given oEmbed endpoint, which gives the
+/// title of the embedded content and its author.
+pub async fn resolve(endpoint: &str, url: &str) -> Result<Metadata> {
+    let api = Url::parse_with_params(endpoint, [("url", url), ("format", "json")])?;
+    let response = get_json(api.as_str()).await?;
+    let mut metadata = Metadata {
+        title: text(&response["title"]),
+        ..Default::default()
+    };
+    if let Some(author) = text(&response["author_name"]) {
+        metadata.fields.push(("author", author));
+    }
+    Ok(metadata)
+}
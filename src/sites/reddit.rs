@@ -0,0 +1,112 @@
+use eyre::{eyre, Result, WrapErr};
+use serde_json::Value;
+use url::Url;
+
+use super::{get_json, text, Metadata};
+
+/// The Reddit host whose API is used for the posts of all its hosts.
+const API: &str = "https://www.reddit.com";
+
+/// Returns the path of the post at the given URL, if it is one: that of its
+/// comments, or its ID for the short links of `redd.it`.
+pub fn post(url: &Url) -> Option<String> {
+    let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    match segments[..] {
+        ["r", subreddit, "comments", id, ..] => Some(format!("r/{subreddit}/comments/{id}")),
+        ["comments", id, ..] => Some(format!("comments/{id}")),
+        [id] if url.host_str() == Some("redd.it") => Some(format!("comments/{id}")),
+        _ => None,
+    }
+}
+
+/// Resolves the post at the given path through its JSON listing, which gives
+/// its title, its author, and its subreddit as the site name.
+pub async fn resolve(post: &str) -> Result<Metadata> {
+    let url = format!("{API}/{post}/.json");
+    let response = get_json(&url).await?;
+    metadata(&response).wrap_err_with(|| format!("invalid response from `{url}`"))
+}
+
+/// Reads the metadata of a post from the given response of the API.
+fn metadata(response: &Value) -> Result<Metadata> {
+    // The first listing has the post, and the second one its comments.
+    let data = &response[0]["data"]["children"][0]["data"];
+    let title = text(&data["title"]).ok_or_else(|| eyre!("no post"))?;
+    let mut metadata = Metadata {
+        title: Some(title),
+        ..Default::default()
+    };
+    if let Some(author) = text(&data["author"]) {
+        metadata.fields.push(("author", author));
+    }
+    if let Some(subreddit) = text(&data["subreddit_name_prefixed"]) {
+        metadata.fields.push(("sitename", subreddit));
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the path of the post at the given URL.
+    fn path(url: &str) -> Option<String> {
+        post(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_posts() {
+        assert_eq!(
+            path("https://www.reddit.com/r/rust/comments/abc123/a_title/").as_deref(),
+            Some("r/rust/comments/abc123")
+        );
+        assert_eq!(
+            path("https://old.reddit.com/comments/abc123").as_deref(),
+            Some("comments/abc123")
+        );
+        assert_eq!(
+            path("https://redd.it/abc123").as_deref(),
+            Some("comments/abc123")
+        );
+        assert_eq!(path("https://www.reddit.com/r/rust/"), None);
+        assert_eq!(path("https://www.reddit.com/abc123"), None);
+    }
+
+    #[test]
+    fn reads_posts_from_listings() {
+        let response = json!([
+            {
+                "data": {
+                    "children": [{
+                        "data": {
+                            "title": " What's everyone working on this week? ",
+                            "author": "someone",
+                            "subreddit_name_prefixed": "r/rust",
+                        },
+                    }],
+                },
+            },
+            { "data": { "children": [] } },
+        ]);
+        let metadata = metadata(&response).unwrap();
+        assert_eq!(
+            metadata.title.as_deref(),
+            Some("What's everyone working on this week?")
+        );
+        assert_eq!(
+            metadata.fields,
+            [
+                ("author", "someone".to_owned()),
+                ("sitename", "r/rust".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_listings_without_a_post() {
+        assert!(metadata(&json!({ "message": "Not Found", "error": 404 })).is_err());
+        assert!(metadata(&json!([{ "data": { "children": [] } }])).is_err());
+    }
+}
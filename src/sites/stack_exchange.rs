@@ -0,0 +1,129 @@
+use std::io::Read;
+
+use eyre::{bail, eyre, Result, WrapErr};
+use flate2::read::GzDecoder;
+use scraper::Html;
+use serde_json::Value;
+use url::Url;
+
+use super::{text, Metadata};
+use crate::fetch::get;
+
+/// The endpoint of the API of Stack Exchange that gives its questions.
+const QUESTIONS: &str = "https://api.stackexchange.com/2.3/questions/";
+
+/// The sites of the Stack Exchange network that aren't subdomains of
+/// `stackexchange.com`.
+const SITES: &[&str] = &[
+    "stackoverflow.com",
+    "superuser.com",
+    "serverfault.com",
+    "askubuntu.com",
+    "mathoverflow.net",
+    "stackapps.com",
+];
+
+/// Whether the given host is a site of the Stack Exchange network, or one of
+/// the localized versions of Stack Overflow.
+pub fn is_site(host: &str) -> bool {
+    SITES.contains(&host)
+        || host.ends_with(".stackexchange.com")
+        || host.ends_with(".stackoverflow.com")
+}
+
+/// Returns the ID of the question at the given URL, if it is one.
+pub fn question(url: &Url) -> Option<String> {
+    let mut segments = url.path_segments()?;
+    let id = match (segments.next()?, segments.next()?) {
+        ("questions" | "q", id) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => id,
+        _ => return None,
+    };
+    Some(id.to_owned())
+}
+
+/// Resolves the question with the given ID on the given site through the API
+/// of Stack Exchange, which gives its title and its author.
+pub async fn resolve(site: &str, id: &str) -> Result<Metadata> {
+    let api = Url::parse_with_params(&format!("{QUESTIONS}{id}"), [("site", site)])?;
+    let response = get(api.as_str()).await?;
+    let status = response.status();
+    let data = response.bytes().await?;
+    // The responses are always compressed, even when not asked to be.
+    let mut json = Vec::new();
+    let json = match data.starts_with(&[0x1f, 0x8b]) {
+        true => {
+            GzDecoder::new(&data[..])
+                .read_to_end(&mut json)
+                .wrap_err("invalid gzipped response from Stack Exchange")?;
+            &json
+        }
+        false => &data[..],
+    };
+    let response: Value =
+        serde_json::from_slice(json).wrap_err("invalid response from Stack Exchange")?;
+    if let Some(error) = text(&response["error_message"]) {
+        bail!("Stack Exchange answered with {status}: {error}");
+    }
+    let question = &response["items"][0];
+    let title = decode(&question["title"]).ok_or_else(|| eyre!("no question {id} on {site}"))?;
+    let mut metadata = Metadata {
+        title: Some(title),
+        ..Default::default()
+    };
+    if let Some(author) = decode(&question["owner"]["display_name"]) {
+        metadata.fields.push(("author", author));
+    }
+    Ok(metadata)
+}
+
+/// Returns the given string of the API, whose HTML entities are decoded, if
+/// it isn't empty.
+fn decode(value: &Value) -> Option<String> {
+    let html = Html::parse_fragment(&text(value)?);
+    Some(html.root_element().text().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the ID of the question at the given URL.
+    fn id(url: &str) -> Option<String> {
+        question(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_sites_of_the_network() {
+        assert!(is_site("stackoverflow.com"));
+        assert!(is_site("unix.stackexchange.com"));
+        assert!(is_site("pt.stackoverflow.com"));
+        assert!(!is_site("stackexchange.com.example"));
+        assert!(!is_site("example.com"));
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_questions() {
+        assert_eq!(
+            id("https://stackoverflow.com/questions/11227809/why-is-it-faster").as_deref(),
+            Some("11227809")
+        );
+        assert_eq!(
+            id("https://unix.stackexchange.com/q/12345/678").as_deref(),
+            Some("12345")
+        );
+        assert_eq!(id("https://stackoverflow.com/questions/"), None);
+        assert_eq!(id("https://stackoverflow.com/questions/tagged/rust"), None);
+        assert_eq!(id("https://stackoverflow.com/users/1/jeff"), None);
+    }
+
+    #[test]
+    fn decodes_the_entities_of_titles() {
+        assert_eq!(
+            decode(&json!("Why is &quot;x&quot; &lt; y &amp; z?")).as_deref(),
+            Some("Why is \"x\" < y & z?")
+        );
+        assert_eq!(decode(&json!("")), None);
+    }
+}
@@ -0,0 +1,117 @@
+use eyre::{bail, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use scraper::{Html, Selector};
+use url::Url;
+
+use super::{get_json, post_title, text, Metadata};
+use crate::fetch::load_html;
+
+/// The oEmbed endpoint of Twitter, which gives the text of the tweets.
+const OEMBED: &str = "https://publish.twitter.com/oembed";
+
+/// The Nitter instance through which the tweets are resolved, if any.
+static NITTER: OnceCell<Url> = OnceCell::new();
+
+/// Resolves the tweets through the given Nitter instance rather than the
+/// oEmbed endpoint of Twitter. May only be called once.
+pub fn set_nitter(instance: Url) {
+    let _ = NITTER.set(instance);
+}
+
+/// Returns the user and the ID of the tweet at the given URL, if it is one.
+pub fn status(url: &Url) -> Option<(String, String)> {
+    let mut segments = url.path_segments()?;
+    let user = segments.next()?;
+    let id = match (segments.next()?, segments.next()?) {
+        ("status" | "statuses", id) if id.bytes().all(|b| b.is_ascii_digit()) => id,
+        _ => return None,
+    };
+    Some((user.to_owned(), id.to_owned()))
+}
+
+/// Resolves the given tweet to a title made of its author and its text.
+pub async fn resolve(user: &str, id: &str) -> Result<Metadata> {
+    let (author, text) = match NITTER.get() {
+        Some(instance) => from_nitter(instance, user, id).await?,
+        None => from_oembed(user, id).await?,
+    };
+    Ok(Metadata {
+        title: Some(post_title(&author, &text)),
+        fields: vec![("author", author)],
+    })
+}
+
+/// Returns the author and the text of the tweet from the oEmbed endpoint,
+/// whose embedding HTML has its text in a paragraph.
+async fn from_oembed(user: &str, id: &str) -> Result<(String, String)> {
+    static PARAGRAPH: Lazy<Selector> = Lazy::new(|| Selector::parse("p").unwrap());
+
+    let tweet = format!("https://twitter.com/{user}/status/{id}");
+    let api = Url::parse_with_params(OEMBED, [("url", tweet.as_str()), ("omit_script", "1")])?;
+    let response = get_json(api.as_str()).await?;
+    let html = Html::parse_fragment(response["html"].as_str().unwrap_or_default());
+    let text: String = html
+        .select(&PARAGRAPH)
+        .next()
+        .map(|p| p.text().collect())
+        .unwrap_or_default();
+    let author = text_or(&response["author_name"], user);
+    Ok((author, text))
+}
+
+/// Returns the author and the text of the tweet from its page on the given
+/// Nitter instance, which has them in its Open Graph meta tags.
+async fn from_nitter(instance: &Url, user: &str, id: &str) -> Result<(String, String)> {
+    static TITLE: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(r#"meta[property="og:title"]"#).unwrap());
+    static DESCRIPTION: Lazy<Selector> =
+        Lazy::new(|| Selector::parse(r#"meta[property="og:description"]"#).unwrap());
+
+    let page = instance.join(&format!("{user}/status/{id}"))?;
+    let html = Html::parse_document(&load_html(page.as_str()).await?);
+    let content = |selector| {
+        html.select(selector)
+            .next()
+            .and_then(|meta| meta.value().attr("content"))
+            .map(str::to_owned)
+    };
+    let Some(text) = content(&DESCRIPTION) else {
+        bail!("`{page}` has no tweet");
+    };
+    Ok((content(&TITLE).unwrap_or_else(|| user.to_owned()), text))
+}
+
+/// Returns the given string of a JSON value, or the default if it is empty.
+fn text_or(value: &serde_json::Value, default: &str) -> String {
+    text(value).unwrap_or_else(|| default.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the user and the ID of the tweet at the given URL.
+    fn tweet(url: &str) -> Option<(String, String)> {
+        status(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_tweets() {
+        let expected = Some(("rustlang".to_owned(), "1234567890".to_owned()));
+        assert_eq!(
+            tweet("https://twitter.com/rustlang/status/1234567890"),
+            expected
+        );
+        assert_eq!(
+            tweet("https://x.com/rustlang/status/1234567890/photo/1"),
+            expected
+        );
+        assert_eq!(
+            tweet("https://twitter.com/rustlang/statuses/1234567890"),
+            expected
+        );
+        assert_eq!(tweet("https://twitter.com/rustlang"), None);
+        assert_eq!(tweet("https://twitter.com/rustlang/status/abc"), None);
+        assert_eq!(tweet("https://twitter.com/rustlang/likes/1234567890"), None);
+    }
+}
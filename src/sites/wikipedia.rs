@@ -0,0 +1,94 @@
+use eyre::{eyre, Result, WrapErr};
+use serde_json::Value;
+use url::Url;
+
+use super::{get_json, text, Metadata};
+
+/// Returns the language and the title of the Wikipedia article at the given
+/// URL, if it is one, the title being kept as encoded in the URL.
+pub fn article(url: &Url) -> Option<(String, String)> {
+    let language = url.host_str()?.split('.').next()?;
+    let article = url.path().strip_prefix("/wiki/")?;
+    if article.is_empty() {
+        return None;
+    }
+    Some((language.to_owned(), article.to_owned()))
+}
+
+/// Resolves the given article through the summary endpoint of the REST API,
+/// in the Wikipedia of its language, which gives its title without the
+/// suffix of the site, and its extract as description.
+pub async fn resolve(language: &str, article: &str) -> Result<Metadata> {
+    let url = format!("https://{language}.wikipedia.org/api/rest_v1/page/summary/{article}");
+    let response = get_json(&url).await?;
+    metadata(&response).wrap_err_with(|| format!("invalid response from `{url}`"))
+}
+
+/// Reads the metadata of an article from the given summary of the API, which
+/// is described by its extract.
+fn metadata(response: &Value) -> Result<Metadata> {
+    let title = text(&response["title"]).ok_or_else(|| eyre!("no article"))?;
+    let mut metadata = Metadata {
+        title: Some(title),
+        ..Default::default()
+    };
+    if let Some(extract) = text(&response["extract"]) {
+        metadata.fields.push(("description", extract));
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Returns the language and the article at the given URL.
+    fn language_and_article(url: &str) -> Option<(String, String)> {
+        article(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn recognizes_the_urls_of_articles() {
+        assert_eq!(
+            language_and_article("https://en.wikipedia.org/wiki/Rust_(programming_language)"),
+            Some(("en".to_owned(), "Rust_(programming_language)".to_owned()))
+        );
+        assert_eq!(
+            language_and_article("https://pt.m.wikipedia.org/wiki/Ferrugem"),
+            Some(("pt".to_owned(), "Ferrugem".to_owned()))
+        );
+        assert_eq!(language_and_article("https://en.wikipedia.org/wiki/"), None);
+        assert_eq!(
+            language_and_article("https://en.wikipedia.org/w/index.php?title=Rust"),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_articles_from_summaries() {
+        let response = json!({
+            "title": "Rust (programming language)",
+            "extract": "Rust is a general-purpose programming language.",
+        });
+        let article = metadata(&response).unwrap();
+        assert_eq!(
+            article.title.as_deref(),
+            Some("Rust (programming language)")
+        );
+        assert_eq!(
+            article.fields,
+            [(
+                "description",
+                "Rust is a general-purpose programming language.".to_owned()
+            )]
+        );
+        let article = metadata(&json!({ "title": "Ferrugem", "extract": "" })).unwrap();
+        assert!(article.fields.is_empty());
+        assert!(metadata(
+            &json!({ "type": "https://mediawiki.org/wiki/HyperSwitch/errors/not_found" })
+        )
+        .is_err());
+    }
+}
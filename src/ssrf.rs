@@ -0,0 +1,116 @@
+use std::net::{IpAddr, SocketAddr};
+
+use eyre::{bail, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::net;
+use tracing::Instrument;
+use url::{Host, Url};
+
+/// Resolves host names like the system does, but refuses the names that
+/// resolve to private addresses, so that URLs can't reach the local network.
+/// Since every connection resolves its host again, this also covers the hosts
+/// of redirects.
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let span = tracing::info_span!("dns", host = name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = net::lookup_host((name.as_str(), 0))
+                .instrument(span)
+                .await?
+                .collect();
+            if let Some(addr) = addrs.iter().find(|addr| is_private(addr.ip())) {
+                return Err(format!(
+                    "refusing to connect to `{}`, which resolves to the private address {}",
+                    name.as_str(),
+                    addr.ip()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Fails if the host of the given URL is a private IP address, which skips
+/// the name resolution.
+pub fn check_url(url: &Url) -> Result<()> {
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        _ => return Ok(()),
+    };
+    if is_private(ip) {
+        bail!("refusing to fetch `{url}`, whose host is the private address {ip}");
+    }
+    Ok(())
+}
+
+/// Whether the given address is a loopback, private (RFC 1918 or unique
+/// local), link-local or unspecified address.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_private(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the given address is private.
+    fn private(ip: &str) -> bool {
+        is_private(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn recognizes_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(private(ip), "{ip}");
+        }
+        for ip in [
+            "93.184.216.34",
+            "172.32.0.1",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(!private(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn refuses_urls_whose_host_is_a_private_address() {
+        let check = |url: &str| check_url(&Url::parse(url).unwrap());
+        assert!(check("http://127.0.0.1:8080/").is_err());
+        assert!(check("http://[::1]/").is_err());
+        assert!(check("https://93.184.216.34/").is_ok());
+        // Host names are only checked once resolved.
+        assert!(check("http://localhost/").is_ok());
+    }
+}
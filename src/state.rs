@@ -0,0 +1,53 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use eyre::{Result, WrapErr};
+
+/// A file that records the URLs completed by a run, one per line, so that an
+/// interrupted run can be continued where it left off.
+pub struct State {
+    file: File,
+}
+
+impl State {
+    /// Opens the state file at the given path. If `continuing`, the URLs it
+    /// already records are returned, and new ones are appended to them.
+    /// Otherwise, the file is emptied, starting a new run.
+    pub fn open(path: &Path, continuing: bool) -> Result<(Self, HashSet<String>)> {
+        let completed = if continuing {
+            completed(path)?
+        } else {
+            HashSet::new()
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(continuing)
+            .truncate(!continuing)
+            .open(path)
+            .wrap_err_with(|| format!("failed to open `{}`", path.display()))?;
+        Ok((State { file }, completed))
+    }
+
+    /// Records the given URL as completed. The record is synced to disk right
+    /// away, so that it survives a crash.
+    pub fn record(&mut self, url: &str) -> Result<()> {
+        writeln!(self.file, "{url}")?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Returns the URLs recorded by the state file at the given path, without
+/// opening it for writing.
+pub fn completed(path: &Path) -> Result<HashSet<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().map(str::to_owned).collect()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(error) => Err(error).wrap_err_with(|| format!("failed to read `{}`", path.display())),
+    }
+}
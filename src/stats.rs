@@ -0,0 +1,118 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use crate::{fetch::FailureKind, urls};
+
+/// How many of the slowest hosts the summary lists.
+const SLOWEST_HOSTS: usize = 5;
+
+/// The statistics of a run, summarized at its end.
+pub struct Stats {
+    started: Instant,
+    succeeded: usize,
+    no_title: usize,
+    skipped: usize,
+    failures: BTreeMap<FailureKind, usize>,
+    bytes: u64,
+    /// How many pages of each host were fetched, and how long they took.
+    hosts: HashMap<String, (u32, Duration)>,
+}
+
+impl Stats {
+    /// Starts the statistics of a run that starts now.
+    pub fn new() -> Self {
+        Stats {
+            started: Instant::now(),
+            succeeded: 0,
+            no_title: 0,
+            skipped: 0,
+            failures: BTreeMap::new(),
+            bytes: 0,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Records a URL that was loaded, of `bytes` downloaded bytes, which took
+    /// the given time.
+    pub fn success(&mut self, url: &str, has_title: bool, bytes: usize, elapsed: Duration) {
+        self.succeeded += 1;
+        if !has_title {
+            self.no_title += 1;
+        }
+        self.bytes += bytes as u64;
+        let host = self.hosts.entry(urls::domain(url)).or_default();
+        host.0 += 1;
+        host.1 += elapsed;
+    }
+
+    /// Records a URL that was skipped, such as one disallowed by robots.txt.
+    pub fn skip(&mut self) {
+        self.skipped += 1;
+    }
+
+    /// Records a URL that failed to load.
+    pub fn failure(&mut self, kind: FailureKind) {
+        *self.failures.entry(kind).or_default() += 1;
+    }
+
+    /// Prints the summary to the standard error.
+    pub fn print(&self) {
+        let failed: usize = self.failures.values().sum();
+        let total = self.succeeded + self.skipped + failed;
+        let elapsed = Duration::from_millis(self.started.elapsed().as_millis() as u64);
+        eprintln!("{total} URLs in {}", humantime::format_duration(elapsed));
+        eprintln!(
+            "  {} succeeded ({} without a title), {failed} failed, {} skipped",
+            self.succeeded, self.no_title, self.skipped
+        );
+        for (kind, count) in &self.failures {
+            eprintln!("  {count} failed with {}", kind.name());
+        }
+        eprintln!("  {} downloaded", format_bytes(self.bytes));
+
+        let mut hosts: Vec<_> = self.hosts.iter().collect();
+        hosts.sort_by_key(|(_, &(count, total))| std::cmp::Reverse(total / count));
+        if !hosts.is_empty() {
+            eprintln!("  slowest hosts:");
+        }
+        for (host, &(count, total)) in hosts.into_iter().take(SLOWEST_HOSTS) {
+            let average = Duration::from_millis((total / count).as_millis() as u64);
+            eprintln!(
+                "    {host}: {} on average, over {count} pages",
+                humantime::format_duration(average)
+            );
+        }
+    }
+}
+
+/// Formats the given number of bytes with a binary unit.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_bytes_with_binary_units() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_bytes(3 << 40), "3.0 TiB");
+        assert_eq!(format_bytes(2048 << 40), "2048.0 TiB");
+    }
+}
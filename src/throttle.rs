@@ -0,0 +1,137 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::Mutex,
+    time::Duration,
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::time::{self, Instant};
+use tracing::trace;
+use url::Url;
+
+/// The minimum gap between the requests to the same host.
+static DELAY: OnceCell<Duration> = OnceCell::new();
+
+/// The random gap added between requests, and whether it is added between
+/// the requests to the same host rather than between all of them.
+static JITTER: OnceCell<(Jitter, bool)> = OnceCell::new();
+
+/// When the next request to each host may be sent. The requests to all hosts
+/// share the empty host.
+static NEXT: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// A range of random durations.
+#[derive(Clone, Copy)]
+pub struct Jitter {
+    min: Duration,
+    max: Duration,
+}
+
+impl Jitter {
+    /// Parses a range such as `100ms..2s`, or a maximum such as `2s`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parse = |s: &str| humantime::parse_duration(s.trim()).map_err(|e| e.to_string());
+        let (min, max) = match s.split_once("..") {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => (Duration::ZERO, parse(s)?),
+        };
+        if min > max {
+            return Err("the minimum is greater than the maximum".into());
+        }
+        Ok(Jitter { min, max })
+    }
+
+    /// Returns a random duration of the range.
+    fn sample(&self) -> Duration {
+        let random = RandomState::new().build_hasher().finish();
+        let span = (self.max - self.min).as_nanos() as u64;
+        self.min + Duration::from_nanos(random.checked_rem(span).unwrap_or_default())
+    }
+}
+
+/// Sets the minimum gap between the requests to the same host. May only be
+/// called once.
+pub fn set_delay(delay: Duration) {
+    let _ = DELAY.set(delay);
+}
+
+/// Adds a random gap between all the requests or, if `per_host` is set,
+/// between the requests to the same host. May only be called once.
+pub fn set_jitter(jitter: Jitter, per_host: bool) {
+    let _ = JITTER.set((jitter, per_host));
+}
+
+/// Waits until a request to the host of the given URL may be sent, reserving
+/// its slot. Requests to different hosts only wait for each other with a
+/// global jitter.
+pub async fn wait(url: &str) {
+    let delay = DELAY.get().copied();
+    let jitter = JITTER.get().copied();
+    if delay.is_none() && jitter.is_none() {
+        return;
+    }
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default();
+
+    let slot = {
+        let mut next = NEXT.lock().unwrap();
+        let now = Instant::now();
+        let mut reserve = |key: &str, gap: Duration| {
+            let slot = next.get(key).map_or(now, |&next| next.max(now));
+            next.insert(key.to_owned(), slot + gap);
+            slot
+        };
+        let mut slot = now;
+        if let Some((jitter, false)) = jitter {
+            slot = slot.max(reserve("", jitter.sample()));
+        }
+        let host_jitter = match jitter {
+            Some((jitter, true)) => Some(jitter.sample()),
+            _ => None,
+        };
+        if delay.is_some() || host_jitter.is_some() {
+            let gap = delay.unwrap_or_default() + host_jitter.unwrap_or_default();
+            slot = slot.max(reserve(&host, gap));
+        }
+        slot
+    };
+    if slot > Instant::now() {
+        trace!("waiting {:?} to request `{url}`", slot - Instant::now());
+    }
+    time::sleep_until(slot).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges_and_maximums() {
+        let jitter = Jitter::parse("100ms..2s").unwrap();
+        assert_eq!(
+            (jitter.min, jitter.max),
+            (Duration::from_millis(100), Duration::from_secs(2))
+        );
+        let jitter = Jitter::parse("2s").unwrap();
+        assert_eq!(
+            (jitter.min, jitter.max),
+            (Duration::ZERO, Duration::from_secs(2))
+        );
+        assert!(Jitter::parse("2s..1s").is_err());
+        assert!(Jitter::parse("soon").is_err());
+    }
+
+    #[test]
+    fn samples_durations_within_the_range() {
+        let jitter = Jitter::parse("100ms..200ms").unwrap();
+        for _ in 0..100 {
+            let sample = jitter.sample();
+            assert!((jitter.min..jitter.max).contains(&sample));
+        }
+        let jitter = Jitter::parse("1s..1s").unwrap();
+        assert_eq!(jitter.sample(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use reqwest::{header::AUTHORIZATION, Method};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::fetch::api_request;
+
+/// The endpoint of the free plan of DeepL, used unless another one is given.
+const DEEPL_ENDPOINT: &str = "https://api-free.deepl.com/v2/translate";
+
+/// The translation services that titles can be translated with.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// LibreTranslate, usually self-hosted.
+    LibreTranslate,
+    DeepL,
+}
+
+/// The `[translation]` table of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranslationConfig {
+    pub backend: Backend,
+    /// The URL of the endpoint that translates texts, which is required for
+    /// LibreTranslate.
+    pub endpoint: Option<String>,
+    pub api_key: Option<String>,
+}
+
+/// Translates titles to a language through a translation service.
+pub struct Translator {
+    backend: Backend,
+    endpoint: String,
+    api_key: Option<String>,
+    /// The language that titles are translated to, as an ISO 639-1 code.
+    target: String,
+    /// The translations of the titles translated so far, since the same
+    /// titles are often found many times.
+    translations: Mutex<HashMap<String, String>>,
+}
+
+impl Translator {
+    /// Creates a translator to the given language, with the service of the
+    /// given configuration.
+    pub fn new(config: &TranslationConfig, target: &str) -> Result<Self> {
+        let endpoint = match (config.backend, &config.endpoint) {
+            (_, Some(endpoint)) => endpoint.clone(),
+            (Backend::DeepL, None) => DEEPL_ENDPOINT.to_owned(),
+            (Backend::LibreTranslate, None) => {
+                bail!("LibreTranslate needs an `endpoint` in `[translation]`");
+            }
+        };
+        if matches!(config.backend, Backend::DeepL) && config.api_key.is_none() {
+            bail!("DeepL needs an `api_key` in `[translation]`");
+        }
+        Ok(Translator {
+            backend: config.backend,
+            endpoint,
+            api_key: config.api_key.clone(),
+            target: target.to_lowercase(),
+            translations: Mutex::default(),
+        })
+    }
+
+    /// Translates the given title, whose language is given if it was
+    /// detected. Titles already in the target language are kept as is.
+    pub async fn translate(&self, title: &str, language: Option<&str>) -> Result<String> {
+        if language == Some(self.target.as_str()) {
+            return Ok(title.to_owned());
+        }
+        if let Some(translation) = self.translations.lock().unwrap().get(title) {
+            return Ok(translation.clone());
+        }
+        let translation = match self.backend {
+            Backend::LibreTranslate => self.libre_translate(title).await,
+            Backend::DeepL => self.deepl(title).await,
+        }
+        .wrap_err_with(|| format!("failed to translate `{title}`"))?;
+        self.translations
+            .lock()
+            .unwrap()
+            .insert(title.to_owned(), translation.clone());
+        Ok(translation)
+    }
+
+    /// Translates the given text with LibreTranslate, which detects its
+    /// language.
+    async fn libre_translate(&self, text: &str) -> Result<String> {
+        let mut body = json!({
+            "q": text,
+            "source": "auto",
+            "target": self.target,
+            "format": "text",
+        });
+        if let Some(api_key) = &self.api_key {
+            body["api_key"] = api_key.as_str().into();
+        }
+        let response = self.post(&body, None).await?;
+        translation(&response["translatedText"])
+    }
+
+    /// Translates the given text with DeepL, which detects its language.
+    async fn deepl(&self, text: &str) -> Result<String> {
+        let body = json!({
+            "text": [text],
+            "target_lang": self.target.to_uppercase(),
+        });
+        let api_key = self
+            .api_key
+            .as_deref()
+            .map(|key| format!("DeepL-Auth-Key {key}"));
+        let response = self.post(&body, api_key.as_deref()).await?;
+        translation(&response["translations"][0]["text"])
+    }
+
+    /// Sends the given JSON body to the endpoint, with the given
+    /// `Authorization` header, if any.
+    async fn post(&self, body: &Value, authorization: Option<&str>) -> Result<Value> {
+        let mut request = api_request(Method::POST, &self.endpoint).json(body);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        request
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .wrap_err("invalid response from the translation service")
+    }
+}
+
+/// Returns the translation in the given value of the response, if it has one.
+fn translation(value: &Value) -> Result<String> {
+    value
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| eyre!("the translation service gave no translation"))
+}
@@ -0,0 +1,445 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+use eyre::{Result, WrapErr};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use url::{quirks, Url};
+
+use crate::amp;
+
+/// Query parameters added by analytics and ad platforms to track clicks. A
+/// trailing `*` matches any suffix.
+const TRACKING_PARAMS: &[&str] = &[
+    "utm_*",
+    "fbclid",
+    "gclid",
+    "gclsrc",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "ttclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "oly_anon_id",
+    "oly_enc_id",
+    "vero_id",
+    "ref_src",
+];
+
+/// The rewrites applied to the URLs of the input, and to the URLs that replace
+/// them in the output.
+#[derive(Clone, Default)]
+pub struct UrlRewrites {
+    pub normalize: bool,
+    pub unwrap_amp: bool,
+    pub cleaner: Option<UrlCleaner>,
+}
+
+impl UrlRewrites {
+    /// Applies the enabled rewrites to the given URL, which gets the `https`
+    /// scheme if it has none.
+    pub fn apply(&self, url: &str) -> String {
+        let mut url = infer_scheme(url);
+        if self.normalize {
+            url = normalize(&url);
+        }
+        if let Some(unwrapped) = amp::unwrap_cache_url(&url).filter(|_| self.unwrap_amp) {
+            url = unwrapped;
+        }
+        if let Some(cleaner) = &self.cleaner {
+            url = cleaner.clean(&url);
+        }
+        url
+    }
+}
+
+/// Prepends `https://` to the given URL if it has no scheme but starts with a
+/// host, as in `example.com/foo` or `localhost:8000`. DOIs, as in
+/// `doi:10.1000/182`, are turned into their `doi.org` URL, and arXiv IDs, as
+/// in `arXiv:2401.12345` or `arXiv:hep-th/9901001`, into that of their
+/// abstract. The prefix is needed, since the IDs alone look like numbers.
+pub fn infer_scheme(url: &str) -> String {
+    /// The arXiv IDs since 2007, and those before.
+    static ARXIV_ID: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^[0-9]{4}\.[0-9]{4,5}(v[0-9]+)?$").unwrap());
+    static OLD_ARXIV_ID: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^[a-z-]+(\.[A-Z]{2})?/[0-9]{7}(v[0-9]+)?$").unwrap());
+
+    let url = url.trim();
+    let prefixed = |prefix: &str| {
+        url.get(..prefix.len())
+            .filter(|start| start.eq_ignore_ascii_case(prefix))
+            .map(|_| url[prefix.len()..].trim_start())
+    };
+    if let Some(doi) = prefixed("doi:") {
+        return format!("https://doi.org/{doi}");
+    }
+    if let Some(id) =
+        prefixed("arxiv:").filter(|id| ARXIV_ID.is_match(id) || OLD_ARXIV_ID.is_match(id))
+    {
+        return format!("https://arxiv.org/abs/{id}");
+    }
+    if url.contains("://") || url.starts_with("//") {
+        return url.to_owned();
+    }
+    let authority = url.split(['/', '?', '#']).next().unwrap_or_default();
+    // Anything else before a colon is a scheme, as in `mailto:`.
+    let port = authority.split_once(':').map(|(_, port)| port);
+    if authority.contains('@') || port.is_some_and(|port| !port.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return url.to_owned();
+    }
+    let inferred = format!("https://{url}");
+    let is_host = Url::parse(&inferred).is_ok_and(|parsed| {
+        parsed
+            .host_str()
+            .is_some_and(|host| host.contains('.') || host == "localhost" || port.is_some())
+    });
+    if is_host {
+        inferred
+    } else {
+        url.to_owned()
+    }
+}
+
+/// Normalizes the given URL, so that equivalent URLs are written the same
+/// way: the scheme and the host are lowercased, the default port, the
+/// fragment, an empty query and dot segments are removed, and the path is
+/// percent-encoded consistently. Scheme-relative URLs get the `https` scheme.
+/// URLs that can't be parsed are only trimmed.
+pub fn normalize(url: &str) -> String {
+    let url = url.trim();
+    let parsed = match url.strip_prefix("//") {
+        Some(rest) => Url::parse(&format!("https://{rest}")),
+        None => Url::parse(url),
+    };
+    let Ok(mut parsed) = parsed else {
+        return url.to_owned();
+    };
+    parsed.set_fragment(None);
+    if parsed.query() == Some("") {
+        parsed.set_query(None);
+    }
+    if let Some(host) = parsed.host_str().filter(|host| host.ends_with('.')) {
+        let host = host.trim_end_matches('.').to_owned();
+        let _ = parsed.set_host(Some(&host));
+    }
+    parsed.into()
+}
+
+/// Selects the URLs to process by their host or by patterns.
+pub struct UrlFilter {
+    only_hosts: Vec<String>,
+    excluded_hosts: Vec<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl UrlFilter {
+    /// Creates a filter that only keeps the URLs of the given hosts (and their
+    /// subdomains), if any, and that match any of the given patterns, if any,
+    /// as long as their host isn't excluded and they match none of the
+    /// excluded patterns.
+    pub fn new(
+        only_hosts: &[String],
+        excluded_hosts: &[String],
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).wrap_err_with(|| format!("invalid pattern `{p}`")))
+                .collect::<Result<Vec<_>>>()
+        };
+        let to_ascii = |hosts: &[String]| {
+            hosts
+                .iter()
+                .map(|host| quirks::domain_to_ascii(host.trim_start_matches("*.")))
+                .collect()
+        };
+        Ok(UrlFilter {
+            only_hosts: to_ascii(only_hosts),
+            excluded_hosts: to_ascii(excluded_hosts),
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Whether the filter keeps every URL.
+    pub fn is_empty(&self) -> bool {
+        self.only_hosts.is_empty()
+            && self.excluded_hosts.is_empty()
+            && self.include.is_empty()
+            && self.exclude.is_empty()
+    }
+
+    /// Whether the given URL is kept.
+    pub fn matches(&self, url: &str) -> bool {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        let in_hosts = |hosts: &[String]| hosts.iter().any(|domain| is_within(&host, domain));
+        (self.only_hosts.is_empty() || in_hosts(&self.only_hosts))
+            && !in_hosts(&self.excluded_hosts)
+            && (self.include.is_empty() || self.include.iter().any(|p| p.is_match(url)))
+            && !self.exclude.iter().any(|p| p.is_match(url))
+    }
+}
+
+/// Returns the host of the given URL without a leading `www.`, or an empty
+/// string if it has none.
+pub fn domain(url: &str) -> String {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default();
+    match host.strip_prefix("www.") {
+        Some(domain) => domain.to_owned(),
+        None => host,
+    }
+}
+
+/// Whether the given host is the given domain or one of its subdomains.
+pub fn is_within(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// How internationalized domain names are written in the output.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HostForm {
+    /// In Unicode, as in `bücher.example`.
+    Unicode,
+    /// In Punycode, as in `xn--bcher-kva.example`.
+    Punycode,
+}
+
+/// Writes the host of the given URL in the given form, leaving the rest of
+/// the URL untouched.
+pub fn display_host(url: &str, form: HostForm) -> String {
+    let Some(span) = host_span(url) else {
+        return url.to_owned();
+    };
+    let host = &url[span.clone()];
+    let converted = match form {
+        HostForm::Unicode => quirks::domain_to_unicode(host),
+        HostForm::Punycode => quirks::domain_to_ascii(host),
+    };
+    if converted.is_empty() {
+        return url.to_owned();
+    }
+    let mut displayed = url.to_owned();
+    displayed.replace_range(span, &converted);
+    displayed
+}
+
+/// Whether a label of the host of the given URL mixes letters of the Latin,
+/// Greek, Cyrillic or Armenian scripts, as lookalikes of other domains do
+/// (such as `аpple.com` with a Cyrillic `а`).
+pub fn has_mixed_scripts(url: &str) -> bool {
+    /// Returns the script of the given letter, if it is of a confusable one.
+    fn script(c: char) -> Option<u8> {
+        match c {
+            'a'..='z' | 'A'..='Z' | '\u{c0}'..='\u{24f}' => Some(0),
+            '\u{370}'..='\u{3ff}' => Some(1),
+            '\u{400}'..='\u{52f}' => Some(2),
+            '\u{530}'..='\u{58f}' => Some(3),
+            _ => None,
+        }
+    }
+
+    let Some(span) = host_span(url) else {
+        return false;
+    };
+    let host = quirks::domain_to_unicode(&url[span]);
+    host.split('.').any(|label| {
+        let mut scripts = label.chars().filter_map(script);
+        let first = scripts.next();
+        scripts.any(|script| Some(script) != first)
+    })
+}
+
+/// Returns the span of the host of the given URL, without its user info and
+/// port.
+fn host_span(url: &str) -> Option<Range<usize>> {
+    let start = url.find("://")? + 3;
+    let len = url[start..]
+        .find(['/', '?', '#'])
+        .unwrap_or(url.len() - start);
+    let authority = &url[start..start + len];
+    let host_start = authority.rfind('@').map_or(0, |at| at + 1);
+    let host = &authority[host_start..];
+    // IPv6 addresses have colons of their own.
+    let host_len = if host.starts_with('[') {
+        host.find(']').map_or(host.len(), |end| end + 1)
+    } else {
+        host.find(':').unwrap_or(host.len())
+    };
+    let host_start = start + host_start;
+    (host_len > 0).then_some(host_start..host_start + host_len)
+}
+
+/// Removes the tracking parameters from URLs.
+#[derive(Clone)]
+pub struct UrlCleaner {
+    strip: Vec<String>,
+    keep: Vec<String>,
+}
+
+impl UrlCleaner {
+    /// Creates a cleaner that removes the known tracking parameters and the
+    /// given ones, except for those that should be kept. Parameter patterns
+    /// may end with `*` to match any suffix.
+    pub fn new(strip: &[String], keep: &[String]) -> Self {
+        let strip = TRACKING_PARAMS
+            .iter()
+            .map(|&p| p.to_owned())
+            .chain(strip.iter().cloned())
+            .collect();
+        UrlCleaner {
+            strip,
+            keep: keep.to_vec(),
+        }
+    }
+
+    /// Removes the tracking parameters from the given URL. The other
+    /// parameters are left untouched, byte for byte. URLs that can't be
+    /// parsed are returned as they are.
+    pub fn clean(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else {
+            return url.to_owned();
+        };
+        let Some(query) = parsed.query() else {
+            return url.to_owned();
+        };
+        let kept: Vec<_> = query
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or_default();
+                let name = percent_decode(name);
+                let matches = |p: &String| matches_param(p, &name);
+                !self.strip.iter().any(matches) || self.keep.iter().any(matches)
+            })
+            .collect();
+        if kept.len() == query.split('&').count() {
+            return url.to_owned();
+        }
+        let kept = kept.join("&");
+        parsed.set_query((!kept.is_empty()).then_some(kept.as_str()));
+        parsed.into()
+    }
+}
+
+/// Whether the given parameter name matches the given pattern.
+fn matches_param(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// Decodes the percent-encoded bytes of the given query component.
+fn percent_decode(s: &str) -> String {
+    url::form_urlencoded::parse(s.as_bytes())
+        .next()
+        .map(|(name, _)| name.into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_schemes() {
+        assert_eq!(infer_scheme("example.com/foo"), "https://example.com/foo");
+        assert_eq!(infer_scheme("localhost:8000"), "https://localhost:8000");
+        assert_eq!(infer_scheme("http://example.com"), "http://example.com");
+        assert_eq!(
+            infer_scheme("mailto:me@example.com"),
+            "mailto:me@example.com"
+        );
+        assert_eq!(infer_scheme("not a url"), "not a url");
+    }
+
+    #[test]
+    fn turns_dois_and_prefixed_arxiv_ids_into_urls() {
+        assert_eq!(
+            infer_scheme("doi:10.1000/182"),
+            "https://doi.org/10.1000/182"
+        );
+        assert_eq!(
+            infer_scheme("DOI: 10.1000/182"),
+            "https://doi.org/10.1000/182"
+        );
+        assert_eq!(
+            infer_scheme("arXiv:2401.12345v2"),
+            "https://arxiv.org/abs/2401.12345v2"
+        );
+        assert_eq!(
+            infer_scheme("arxiv:hep-th/9901001"),
+            "https://arxiv.org/abs/hep-th/9901001"
+        );
+    }
+
+    #[test]
+    fn leaves_numbers_that_look_like_arxiv_ids() {
+        assert_eq!(infer_scheme("2401.12345"), "2401.12345");
+        assert_eq!(infer_scheme("1234.5678"), "1234.5678");
+    }
+
+    #[test]
+    fn strips_tracking_parameters() {
+        let cleaner = UrlCleaner::new(&["ref".to_owned()], &["utm_keep".to_owned()]);
+        assert_eq!(
+            cleaner.clean("https://a.example/?utm_source=x&id=1&fbclid=y&ref=z#top"),
+            "https://a.example/?id=1#top"
+        );
+        assert_eq!(
+            cleaner.clean("https://a.example/?utm_medium=x&utm_keep=1"),
+            "https://a.example/?utm_keep=1"
+        );
+        assert_eq!(
+            cleaner.clean("https://a.example/?utm%5Fsource=x"),
+            "https://a.example/"
+        );
+    }
+
+    #[test]
+    fn leaves_the_other_parameters_byte_for_byte() {
+        let cleaner = UrlCleaner::new(&[], &[]);
+        let url = "https://a.example/?q=a%20b+c&empty=&flag";
+        assert_eq!(cleaner.clean(url), url);
+        assert_eq!(
+            cleaner.clean("not a url?utm_source=x"),
+            "not a url?utm_source=x"
+        );
+    }
+
+    #[test]
+    fn normalizes_urls() {
+        assert_eq!(
+            normalize(" HTTPS://Example.COM:443/a/./b/../c?#frag "),
+            "https://example.com/a/c"
+        );
+        assert_eq!(normalize("http://example.com."), "http://example.com/");
+        assert_eq!(normalize("//example.com/a b"), "https://example.com/a%20b");
+        assert_eq!(
+            normalize("http://example.com:8080/?q=1"),
+            "http://example.com:8080/?q=1"
+        );
+        assert_eq!(normalize("not a url"), "not a url");
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use eyre::{Result, WrapErr};
+use futures::{stream, Stream};
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc as tokio_mpsc;
+
+use crate::input::Entry;
+
+/// How long to wait for a file system event before checking the file anyway,
+/// in case an event was missed.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns an endless stream of the entries of the given line-based file,
+/// separated by the given byte. After the current contents are read, the file
+/// is followed by name, like `tail -F`: appended lines are yielded as they are
+/// written, and the file is read again from the start if it is truncated or
+/// replaced (e.g., by log rotation).
+pub fn follow(path: PathBuf, sep: u8) -> impl Stream<Item = Result<Entry>> + Send {
+    spawn_stream(move |tx| follow_file(&path, sep, tx))
+}
+
+/// Runs the given producer in a dedicated thread, returning a stream of the
+/// entries it sends. If the producer fails, its error is the last item of the
+/// stream. The producer should return once the receiving end is closed.
+pub fn spawn_stream(
+    producer: impl FnOnce(&tokio_mpsc::Sender<Result<Entry>>) -> Result<()> + Send + 'static,
+) -> impl Stream<Item = Result<Entry>> + Send {
+    let (tx, rx) = tokio_mpsc::channel(64);
+    thread::spawn(move || {
+        if let Err(error) = producer(&tx) {
+            let _ = tx.blocking_send(Err(error));
+        }
+    });
+    stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+/// Follows the given file, sending its entries through `tx`. Only returns on
+/// error or once the receiving end is closed.
+fn follow_file(path: &Path, sep: u8, tx: &tokio_mpsc::Sender<Result<Entry>>) -> Result<()> {
+    let (events_tx, events_rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(events_tx)?;
+    // Watches the parent directory so that the file can be replaced.
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .wrap_err_with(|| format!("failed to watch `{}`", dir.display()))?;
+
+    let open = || File::open(path).wrap_err_with(|| format!("failed to open `{}`", path.display()));
+    let mut file = open()?;
+    let mut pending = Vec::new();
+    let mut buf = [0; 8192];
+
+    loop {
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buf[..n]);
+            while let Some(i) = pending.iter().position(|&b| b == sep) {
+                let record: Vec<_> = pending.drain(..=i).collect();
+                let line = String::from_utf8_lossy(&record);
+                let line = line.trim();
+                if !line.is_empty() && tx.blocking_send(Ok(Entry::new(line))).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+
+        // Waits for something to happen, then drains the other events.
+        let _ = events_rx.recv_timeout(POLL_INTERVAL);
+        while events_rx.try_recv().is_ok() {}
+        if tx.is_closed() {
+            return Ok(());
+        }
+
+        // The file may have been removed, and not yet created again.
+        let Ok(meta) = fs::metadata(path) else {
+            continue;
+        };
+        if !same_file(&meta, &file.metadata()?) {
+            file = open()?;
+            pending.clear();
+        } else if meta.len() < file.stream_position()? {
+            file.seek(SeekFrom::Start(0))?;
+            pending.clear();
+        }
+    }
+}
+
+/// Whether the two metadata belong to the same file.
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Whether the two metadata belong to the same file. Replaced files can't be
+/// detected on this platform, only truncated ones.
+#[cfg(not(unix))]
+fn same_file(_: &fs::Metadata, _: &fs::Metadata) -> bool {
+    true
+}